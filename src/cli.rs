@@ -1,5 +1,9 @@
+use crate::export::dot::DEFAULT_MIN_SIMILARITY;
+use crate::extract::ExtractFormat;
+use crate::validation::ReportFormat;
+use crate::xsconfig::{ExamplePolicy, OutputFormat};
 use clap::builder::styling::{AnsiColor, Effects, Styles};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 // This function creates the explicit styles for clap's help message.
@@ -15,49 +19,343 @@ fn styles() -> clap::builder::Styles {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, styles = styles())]
 pub struct Cli {
-    /// Directory containing XML files to process
-    #[arg(value_name = "DIRECTORY")]
-    pub input_dir: PathBuf,
+    #[command(flatten)]
+    pub global: GlobalArgs,
 
-    /// Output JSON file path
-    #[arg(short, long, value_name = "FILE")]
-    pub output: Option<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
 
+/// Options shared by every command
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
     /// Configuration file path
     #[arg(
         short,
         long,
+        global = true,
         value_name = "FILE",
         default_value = "config/default.toml"
     )]
     pub config: String,
 
     /// Number of parallel threads (0 = auto-detect)
-    #[arg(short = 't', long)]
+    #[arg(short = 't', long, global = true)]
     pub threads: Option<usize>,
 
-    /// Maximum directory traversal depth (0 = unlimited)
-    #[arg(short = 'd', long)]
-    pub max_depth: Option<usize>,
-
     /// Log level (trace, debug, info, warn, error)
-    #[arg(short = 'l', long, default_value = "info")]
+    #[arg(short = 'l', long, global = true, default_value = "info")]
     pub log_level: String,
 
     /// Disable progress bar
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub no_progress: bool,
 
+    /// Verbose output (equivalent to --log-level debug)
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Directory containing XML files to process
+    #[arg(value_name = "DIRECTORY")]
+    pub input_dir: PathBuf,
+
+    /// Output JSON file path
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Maximum directory traversal depth (0 = unlimited)
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+
     /// Disable pretty-print JSON output
     #[arg(long)]
     pub no_pretty: bool,
 
-    /// Verbose output (equivalent to --log-level debug)
-    #[arg(short, long)]
-    pub verbose: bool,
+    /// Output format (json, ndjson, csv, sqlite)
+    #[arg(short = 'f', long, value_name = "FORMAT")]
+    pub format: Option<OutputFormat>,
+
+    /// Also write a self-contained HTML report to this file
+    #[arg(long, value_name = "FILE")]
+    pub html: Option<String>,
+
+    /// Also write a Graphviz DOT graph of the structure groups to this file
+    #[arg(long, value_name = "FILE")]
+    pub dot: Option<String>,
+
+    /// Minimum similarity (0-1) for linking two groups in the DOT graph
+    #[arg(long, value_name = "SIMILARITY", default_value_t = DEFAULT_MIN_SIMILARITY)]
+    pub dot_min_similarity: f64,
+
+    /// Fail if any file has a skeleton not approved in this baseline file
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Rewrite the baseline file to approve every skeleton of this run
+    #[arg(long, requires = "baseline")]
+    pub update_baseline: bool,
+
+    /// Number of example files kept per structure group
+    #[arg(long, value_name = "N")]
+    pub examples: Option<usize>,
+
+    /// Policy for choosing example files
+    #[arg(long, value_name = "POLICY")]
+    pub example_policy: Option<ExamplePolicy>,
+
+    /// Seed for the random example policy
+    #[arg(long, value_name = "SEED")]
+    pub example_seed: Option<u64>,
+
+    /// Store example paths only, without full structures
+    #[arg(long)]
+    pub example_paths_only: bool,
 }
 
-impl Cli {
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Group XML files by structural skeleton and write the results
+    Analyze(AnalyzeArgs),
+
+    /// Validate XML files against the TEI rules
+    Validate(ValidateArgs),
+
+    /// Print the skeleton of a structure group or an XML file as a tree
+    Show(ShowArgs),
+
+    /// Compare two results files and report structural drift
+    Diff(DiffArgs),
+
+    /// Run an XPath query or a structural pattern against a corpus
+    Query(QueryArgs),
+
+    /// Extract records from every XML file with a TOML or YAML template
+    Extract(ExtractArgs),
+
+    /// Derive a RELAX NG schema from the skeleton of a group or a file
+    Schema(SchemaArgs),
+
+    /// Print the documentation of a validation rule, or list all rules
+    Explain(ExplainArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ExplainArgs {
+    /// Rule id; lists the built-in and configured rules if omitted
+    #[arg(value_name = "RULE")]
+    pub rule: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// XML files or directories to validate
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    /// Only print the corpus summary, not the report of each file
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// Number of worst files listed in the summary
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub worst: usize,
+
+    /// Skip a rule by id (repeatable)
+    #[arg(long, value_name = "RULE")]
+    pub disable: Vec<String>,
+
+    /// Also check the asserts of a Schematron schema or ODD (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub schematron: Vec<PathBuf>,
+
+    /// Also check the content model of a RELAX NG schema, e.g. tei_all.rng
+    /// (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub relaxng: Vec<PathBuf>,
+
+    /// Also check the declarations of an XML Schema (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub xsd: Vec<PathBuf>,
+
+    /// Also check the element and attribute declarations of a DTD
+    /// (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub dtd: Vec<PathBuf>,
+
+    /// Also check each file against the DTD of its DOCTYPE and the XML
+    /// Schemas of its xsi:schemaLocation
+    #[arg(long)]
+    pub declared_schemas: bool,
+
+    /// Apply the fixes rules offer (such as an inferred <pb> @n), write the
+    /// patched files and report what remains
+    #[arg(long, conflicts_with = "fix_dry_run")]
+    pub fix: bool,
+
+    /// Print the fixes as a unified diff instead of applying them
+    #[arg(long)]
+    pub fix_dry_run: bool,
+
+    /// Maximum directory traversal depth (config value if not given)
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct ShowArgs {
+    /// Group hash (looked up in a results file) or path to an XML file
+    #[arg(value_name = "HASH|FILE")]
+    pub target: String,
+
+    /// Results JSON file to look up group hashes in
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        default_value = "xml_structures.json"
+    )]
+    pub results: PathBuf,
+
+    /// Maximum depth to render (0 = unlimited)
+    #[arg(short = 'd', long, default_value_t = 0)]
+    pub depth: usize,
+
+    /// Show the full structure instead of the merged skeleton
+    #[arg(long)]
+    pub full: bool,
+
+    /// Print the skeleton as a Graphviz DOT graph instead of a tree
+    #[arg(long, conflicts_with = "full")]
+    pub dot: bool,
+
+    /// Disable colored output
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Results JSON file of the earlier run
+    #[arg(value_name = "OLD")]
+    pub old: PathBuf,
+
+    /// Results JSON file of the later run
+    #[arg(value_name = "NEW")]
+    pub new: PathBuf,
+
+    /// Print the diff as JSON instead of a summary
+    #[arg(long)]
+    pub json: bool,
+
+    /// Also write the diff as JSON to this file
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// List every entry in the summary instead of the first 20 per section
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// XPath 1.0 expression, e.g. "//div[@type='chapter']/head", or a
+    /// structural pattern with --pattern
+    #[arg(value_name = "EXPR")]
+    pub expr: String,
+
+    /// Directory (or single XML file) to query; the results JSON file with --groups
+    #[arg(value_name = "PATH")]
+    pub input: PathBuf,
+
+    /// Treat EXPR as a structural pattern, e.g. "TEI{teiHeader,text{*}}",
+    /// and list the files whose structure matches
+    #[arg(long)]
+    pub pattern: bool,
+
+    /// Match the pattern against the groups of a results file instead of files
+    #[arg(long, requires = "pattern")]
+    pub groups: bool,
+
+    /// Print matches as JSON instead of one line per match
+    #[arg(long)]
+    pub json: bool,
+
+    /// Only print the number of matches per file
+    #[arg(long, conflicts_with = "json")]
+    pub count: bool,
+
+    /// Maximum directory traversal depth (config value if not given)
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+    /// Extraction template (.toml, .yaml or .yml), or a directory of
+    /// per-group templates named <hash>.toml plus an optional default.toml
+    #[arg(value_name = "TEMPLATE")]
+    pub template: PathBuf,
+
+    /// Directory (or single XML file) to extract from
+    #[arg(value_name = "PATH", required_unless_present = "generate")]
+    pub input: Option<PathBuf>,
+
+    /// Output file, or output directory for per-group templates
+    /// (default: standard output)
+    #[arg(short, long, value_name = "FILE|DIR")]
+    pub output: Option<PathBuf>,
+
+    /// Output format (default: from the output file extension, else csv)
+    #[arg(short, long, value_enum)]
+    pub format: Option<ExtractFormat>,
+
+    /// Write starter templates for the groups of this results file to
+    /// TEMPLATE instead of extracting
+    #[arg(long, value_name = "RESULTS", conflicts_with_all = ["input", "output", "format"])]
+    pub generate: Option<PathBuf>,
+
+    /// Only generate the template of this group hash
+    #[arg(long, value_name = "HASH", requires = "generate")]
+    pub group: Option<u64>,
+
+    /// Maximum directory traversal depth (config value if not given)
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaArgs {
+    /// Group hash (looked up in a results file) or path to an XML file
+    #[arg(value_name = "HASH|FILE")]
+    pub target: String,
+
+    /// Results JSON file to look up group hashes in
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        default_value = "xml_structures.json"
+    )]
+    pub results: PathBuf,
+
+    /// Default namespace of the elements, e.g. http://www.tei-c.org/ns/1.0
+    /// [default: the namespace of the root]
+    #[arg(long, value_name = "URI")]
+    pub ns: Option<String>,
+
+    /// Write the schema to this file instead of standard output
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+impl GlobalArgs {
     /// Get the effective log level
     pub fn effective_log_level(&self) -> String {
         if self.verbose {
@@ -72,37 +370,56 @@ impl Cli {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_verbose_overrides_log_level() {
-        let cli = Cli {
-            input_dir: PathBuf::from("."),
-            output: None,
+    fn global_args(verbose: bool) -> GlobalArgs {
+        GlobalArgs {
             config: "config/default.toml".to_string(),
             threads: None,
-            max_depth: None,
             log_level: "info".to_string(),
             no_progress: false,
-            no_pretty: false,
-            verbose: true,
-        };
+            verbose,
+        }
+    }
 
-        assert_eq!(cli.effective_log_level(), "debug");
+    #[test]
+    fn test_verbose_overrides_log_level() {
+        assert_eq!(global_args(true).effective_log_level(), "debug");
     }
 
     #[test]
     fn test_default_log_level() {
-        let cli = Cli {
-            input_dir: PathBuf::from("."),
-            output: None,
-            config: "config/default.toml".to_string(),
-            threads: None,
-            max_depth: None,
-            log_level: "info".to_string(),
-            no_progress: false,
-            no_pretty: false,
-            verbose: false,
-        };
+        assert_eq!(global_args(false).effective_log_level(), "info");
+    }
+
+    #[test]
+    fn test_parse_show_command() {
+        let cli = Cli::parse_from(["xml-structer", "show", "123", "--depth", "2"]);
+
+        match cli.command {
+            Command::Show(args) => {
+                assert_eq!(args.target, "123");
+                assert_eq!(args.depth, 2);
+            }
+            _ => panic!("expected show command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_analyze_command() {
+        let cli = Cli::parse_from(["xml-structer", "analyze", "corpus", "-t", "4"]);
+
+        assert_eq!(cli.global.threads, Some(4));
+        match cli.command {
+            Command::Analyze(args) => assert_eq!(args.input_dir, PathBuf::from("corpus")),
+            _ => panic!("expected analyze command"),
+        }
+    }
+
+    #[test]
+    fn test_global_options_before_command() {
+        let cli = Cli::parse_from(["xml-structer", "-v", "--no-progress", "query", "//p", "."]);
 
-        assert_eq!(cli.effective_log_level(), "info");
+        assert!(cli.global.verbose);
+        assert!(cli.global.no_progress);
+        assert!(matches!(cli.command, Command::Query(_)));
     }
 }
@@ -0,0 +1,186 @@
+use super::Session;
+use crate::cli::AnalyzeArgs;
+use crate::export::{adjust_extension, dot, html, write_result};
+use crate::processor::{print_summary, process_xml_files_with, Baseline, ProcessingResult};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Group the files of a directory by skeleton and write the results
+pub fn run(args: &AnalyzeArgs, session: &Session) -> Result<()> {
+    let mut config = session
+        .config
+        .clone()
+        .merge_with_cli(args.output.clone(), None);
+
+    // Override max_depth if provided via CLI
+    if let Some(max_depth) = args.max_depth {
+        config.processing.max_depth = max_depth;
+    }
+
+    // Override output format
+    if let Some(format) = args.format {
+        config.output.format = format;
+    }
+
+    if args.html.is_some() {
+        config.output.html_report = args.html.clone();
+    }
+
+    if args.dot.is_some() {
+        config.output.dot_graph = args.dot.clone();
+    }
+
+    // Override example selection
+    if let Some(count) = args.examples {
+        config.examples.count = count;
+    }
+    if let Some(policy) = args.example_policy {
+        config.examples.policy = policy;
+    }
+    if let Some(seed) = args.example_seed {
+        config.examples.seed = seed;
+    }
+    if args.example_paths_only {
+        config.examples.paths_only = true;
+    }
+
+    // Override pretty print
+    if args.no_pretty {
+        config.output.pretty_print = false;
+    }
+
+    info!("🚀 XML Structure Analyzer starting...");
+    info!("Input directory: {}", args.input_dir.display());
+    info!("Output file: {}", config.output.output_file);
+
+    let start_time = Instant::now();
+
+    // Find all XML files
+    info!("🔍 Scanning for XML files...");
+    let xml_files = session.input_files(&args.input_dir, Some(config.processing.max_depth))?;
+
+    info!("Found {} XML files", xml_files.len());
+
+    // Create progress bar
+    let progress_bar = session.progress_bar(xml_files.len());
+
+    // Process files
+    info!("⚙️  Processing XML files...");
+    let mut result = process_xml_files_with(xml_files, progress_bar, &config.examples)
+        .context("Failed to process XML files")?;
+    let root = if args.input_dir.is_file() {
+        args.input_dir.parent().unwrap_or(Path::new(""))
+    } else {
+        args.input_dir.as_path()
+    };
+    result.root = Some(root.to_string_lossy().to_string());
+
+    // Write results; only the configured default name follows --format, an
+    // explicit --output is used as given
+    let output_path = match &args.output {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            let format = config.output.format;
+            if path.extension().is_none_or(|e| e != format.extension()) {
+                warn!(
+                    "Output file {} doesn't have the .{} extension of the {} format",
+                    path.display(),
+                    format.extension(),
+                    format.extension()
+                );
+            }
+            path
+        }
+        None => adjust_extension(&config.output_file_path(), config.output.format),
+    };
+    let written = write_result(
+        &result,
+        &output_path,
+        config.output.format,
+        config.output.pretty_print,
+    )
+    .context("Failed to write results")?;
+
+    if let Some(report_path) = &config.output.html_report {
+        html::write_report(&result, Path::new(report_path))
+            .context("Failed to write HTML report")?;
+        println!("🌐 HTML report saved to: {}", report_path);
+    }
+
+    if let Some(graph_path) = &config.output.dot_graph {
+        dot::write_result_graph(&result, Path::new(graph_path), args.dot_min_similarity)
+            .context("Failed to write DOT graph")?;
+        println!("🕸️  DOT graph saved to: {}", graph_path);
+    }
+
+    // Print summary
+    print_summary(&result);
+
+    let elapsed = start_time.elapsed();
+    println!("\n⏱️  Total time: {:.2}s", elapsed.as_secs_f64());
+    for path in &written {
+        println!("✅ Results saved to: {}", path.display());
+    }
+
+    if let Some(baseline_path) = &args.baseline {
+        check_baseline(&result, baseline_path, args.update_baseline)?;
+    }
+
+    info!("Processing completed successfully");
+
+    Ok(())
+}
+
+/// Update the baseline from this run, or fail if a skeleton isn't approved
+fn check_baseline(result: &ProcessingResult, baseline_path: &Path, update: bool) -> Result<()> {
+    let existing = if baseline_path.exists() {
+        Some(Baseline::from_file(baseline_path)?)
+    } else if update {
+        None
+    } else {
+        anyhow::bail!("Baseline file not found: {}", baseline_path.display());
+    };
+
+    if update {
+        let baseline = Baseline::from_result(result, existing.as_ref());
+        baseline.write_to_file(baseline_path)?;
+        println!(
+            "📌 Baseline updated: {} approved skeletons in {}",
+            baseline.skeletons.len(),
+            baseline_path.display()
+        );
+        return Ok(());
+    }
+
+    let violations = existing.unwrap_or_default().check(result);
+    if violations.is_empty() {
+        println!("✅ All skeletons are approved by the baseline");
+        return Ok(());
+    }
+
+    let files: usize = violations.iter().map(|v| v.files.len()).sum();
+    eprintln!("\n❌ {} files have skeletons not in the baseline:", files);
+    for violation in &violations {
+        eprintln!(
+            "\n  Skeleton {} <{}> ({} files):",
+            violation.hash,
+            violation.root,
+            violation.files.len()
+        );
+        for file in &violation.files {
+            eprintln!("    {}", file);
+        }
+    }
+    eprintln!(
+        "\nRun `xml-structer show <HASH> --results <FILE>` to inspect a skeleton, \
+         or rerun with --update-baseline to approve them."
+    );
+
+    anyhow::bail!(
+        "{} unapproved skeletons found ({} files)",
+        violations.len(),
+        files
+    )
+}
@@ -0,0 +1,114 @@
+use crate::cli::DiffArgs;
+use crate::processor::{diff_results, load_result, ResultDiff};
+use anyhow::{Context, Result};
+use console::style;
+use std::fs;
+
+/// Number of entries listed per section in the human-readable summary
+const SUMMARY_LIMIT: usize = 20;
+
+/// Compare two results files and report structural drift
+pub fn run(args: &DiffArgs) -> Result<()> {
+    let old = load_result(&args.old)?;
+    let new = load_result(&args.new)?;
+    let diff = diff_results(&old, &new);
+
+    if args.json || args.output.is_some() {
+        let json = serde_json::to_string_pretty(&diff)?;
+        match &args.output {
+            Some(path) => {
+                fs::write(path, json)
+                    .with_context(|| format!("Failed to write to {}", path.display()))?;
+                println!("✅ Diff saved to: {}", path.display());
+            }
+            None => println!("{}", json),
+        }
+        if args.json {
+            return Ok(());
+        }
+    }
+
+    print_diff(&diff, args.all);
+    Ok(())
+}
+
+/// Human-readable drift summary
+pub fn print_diff(diff: &ResultDiff, all: bool) {
+    if diff.is_empty() {
+        println!("{}", style("✔ No structural drift").green());
+        return;
+    }
+
+    let limit = if all { usize::MAX } else { SUMMARY_LIMIT };
+
+    println!("\n{}", style("Structural Drift").bold().underlined());
+    println!(
+        "  {} moved, {} added, {} removed files",
+        diff.moved_files.len(),
+        diff.added_files.len(),
+        diff.removed_files.len()
+    );
+    println!(
+        "  {} new, {} vanished skeletons, {} count changes",
+        diff.new_groups.len(),
+        diff.vanished_groups.len(),
+        diff.count_changes.len()
+    );
+
+    section("🆕 New skeletons", diff.new_groups.len(), limit);
+    for g in diff.new_groups.iter().take(limit) {
+        println!(
+            "  {} {} <{}> {} files",
+            style("+").green(),
+            g.hash,
+            g.root,
+            g.count
+        );
+    }
+
+    section("🗑️  Vanished skeletons", diff.vanished_groups.len(), limit);
+    for g in diff.vanished_groups.iter().take(limit) {
+        println!(
+            "  {} {} <{}> {} files",
+            style("-").red(),
+            g.hash,
+            g.root,
+            g.count
+        );
+    }
+
+    section("🔀 Moved files", diff.moved_files.len(), limit);
+    for f in diff.moved_files.iter().take(limit) {
+        println!("  {}  {} → {}", f.path, f.old_group, f.new_group);
+    }
+
+    section("📈 Count changes", diff.count_changes.len(), limit);
+    for c in diff.count_changes.iter().take(limit) {
+        let delta = c.new_count as i64 - c.old_count as i64;
+        println!(
+            "  {} <{}> {} → {} ({:+})",
+            c.hash, c.root, c.old_count, c.new_count, delta
+        );
+    }
+
+    section("➕ Added files", diff.added_files.len(), limit);
+    for f in diff.added_files.iter().take(limit) {
+        println!("  {} {}  {}", style("+").green(), f.path, f.group);
+    }
+
+    section("➖ Removed files", diff.removed_files.len(), limit);
+    for f in diff.removed_files.iter().take(limit) {
+        println!("  {} {}  {}", style("-").red(), f.path, f.group);
+    }
+}
+
+fn section(title: &str, len: usize, limit: usize) {
+    if len == 0 {
+        return;
+    }
+    if len > limit {
+        println!("\n{} ({}, showing {}):", title, len, limit);
+    } else {
+        println!("\n{} ({}):", title, len);
+    }
+}
@@ -0,0 +1,38 @@
+use super::Session;
+use crate::cli::ExplainArgs;
+use crate::validation::Registry;
+use anyhow::{Context, Result};
+use console::style;
+
+/// Print the documentation of one validation rule, or list all rules
+pub fn run(args: &ExplainArgs, session: &Session) -> Result<()> {
+    let registry = Registry::with_config(&session.config.rules)?;
+
+    let Some(id) = &args.rule else {
+        let width = registry.rules().map(|r| r.id().len()).max().unwrap_or(0);
+        for rule in registry.rules() {
+            println!(
+                "{:width$}  {:7}  {}",
+                rule.id(),
+                rule.default_severity().as_str(),
+                rule.description(),
+                width = width
+            );
+        }
+        return Ok(());
+    };
+
+    let rule = registry.get(id).with_context(|| {
+        format!(
+            "Unknown rule id: {} (run `xml-structer explain` to list all rules)",
+            id
+        )
+    })?;
+    println!("{}", style(rule.id()).bold());
+    println!("Default severity: {}", rule.default_severity().as_str());
+    println!("\n{}", rule.description());
+    if let Some(help) = rule.help() {
+        println!("\n{} {}", style("Help:").cyan(), help);
+    }
+    Ok(())
+}
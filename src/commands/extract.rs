@@ -0,0 +1,154 @@
+use super::Session;
+use crate::cli::ExtractArgs;
+use crate::extract::{
+    extract_files, extract_files_by_group, write_records, ExtractFailure, ExtractFormat,
+    ExtractTemplate, Record, TemplateKey, TemplateSet,
+};
+use crate::processor::load_result;
+use anyhow::{Context, Result};
+use console::style;
+use indicatif::ProgressBar;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// Run an extraction template (or a directory of per-group templates) over
+/// a directory or file, or generate starter templates
+pub fn run(args: &ExtractArgs, session: &Session) -> Result<()> {
+    if let Some(results) = &args.generate {
+        return generate(args, results);
+    }
+    let input = args.input.as_deref().context("Input path is required")?;
+    let files = session.input_files(input, args.max_depth)?;
+
+    if args.template.is_dir() {
+        return run_per_group(args, session, &files);
+    }
+
+    let template = ExtractTemplate::from_file(&args.template)?;
+    let format = args
+        .format
+        .or_else(|| args.output.as_deref().and_then(ExtractFormat::from_path))
+        .unwrap_or_default();
+
+    let result = extract_files(&files, &template, progress_bar(args, session, files.len()));
+
+    let header = template.header();
+    match &args.output {
+        Some(path) => {
+            write_file(path, &header, &result.records, format)?;
+            println!(
+                "✅ {} records from {} files saved to: {}",
+                result.records.len(),
+                files.len() - result.failures.len(),
+                path.display()
+            );
+        }
+        None => write_records(io::stdout().lock(), &header, &result.records, format)?,
+    }
+
+    print_failures(&result.failures);
+    Ok(())
+}
+
+/// Extract each file with the template of its structure group, writing
+/// one `<hash>.<format>` file per template into the output directory
+fn run_per_group(args: &ExtractArgs, session: &Session, files: &[String]) -> Result<()> {
+    let output_dir = args
+        .output
+        .as_deref()
+        .context("Per-group templates need an output directory (-o DIR)")?;
+    let templates = TemplateSet::from_dir(&args.template)?;
+    let format = args.format.unwrap_or_default();
+
+    let result =
+        extract_files_by_group(files, &templates, progress_bar(args, session, files.len()));
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    for group in &result.groups {
+        let template = &templates.templates[&group.key];
+        let path = output_dir.join(format!("{}.{}", group.key, format.extension()));
+        write_file(&path, &template.header(), &group.records, format)?;
+        let label = match group.key {
+            TemplateKey::Group(_) => "group",
+            TemplateKey::Default => "default template",
+        };
+        println!(
+            "✅ {} records ({}) saved to: {}",
+            group.records.len(),
+            label,
+            path.display()
+        );
+    }
+
+    if !result.unmatched.is_empty() {
+        eprintln!(
+            "{} {} files have no template for their group (add default.toml to cover them)",
+            style("⚠️").yellow(),
+            result.unmatched.len()
+        );
+    }
+    print_failures(&result.failures);
+    Ok(())
+}
+
+/// Write starter templates for the groups of a results file
+fn generate(args: &ExtractArgs, results: &Path) -> Result<()> {
+    let result = load_result(results)?;
+    let mut templates = TemplateSet::from_result(&result)?;
+
+    if let Some(hash) = args.group {
+        let template = templates
+            .templates
+            .remove(&TemplateKey::Group(hash))
+            .with_context(|| format!("Group {} not found in {}", hash, results.display()))?;
+        if args.template.extension().is_some_and(|e| e == "toml") {
+            fs::write(&args.template, template.to_toml())
+                .with_context(|| format!("Failed to write {}", args.template.display()))?;
+            println!("📝 Template saved to: {}", args.template.display());
+            return Ok(());
+        }
+        templates.templates = [(TemplateKey::Group(hash), template)].into();
+    }
+
+    let written = templates.write_to_dir(&args.template)?;
+    println!(
+        "📝 {} templates saved to: {}",
+        written.len(),
+        args.template.display()
+    );
+    Ok(())
+}
+
+fn write_file(
+    path: &Path,
+    header: &[&str],
+    records: &[Record],
+    format: ExtractFormat,
+) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    write_records(BufWriter::new(file), header, records, format)
+        .with_context(|| format!("Failed to write to {}", path.display()))
+}
+
+fn progress_bar(args: &ExtractArgs, session: &Session, len: usize) -> Option<ProgressBar> {
+    // Keep standard output clean when records are written there
+    if args.output.is_none() {
+        None
+    } else {
+        session.progress_bar(len)
+    }
+}
+
+fn print_failures(failures: &[ExtractFailure]) {
+    for failure in failures {
+        eprintln!(
+            "{} {}: {}",
+            style("⚠️").yellow(),
+            failure.file,
+            failure.message
+        );
+    }
+}
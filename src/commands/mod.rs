@@ -0,0 +1,83 @@
+pub mod analyze;
+pub mod diff;
+pub mod explain;
+pub mod extract;
+pub mod query;
+pub mod schema;
+pub mod show;
+pub mod validate;
+
+use crate::cli::GlobalArgs;
+use crate::processor::create_progress_bar;
+use crate::utils::{find_xml_files, init_logging, validate_directory};
+use crate::xsconfig::XsConfig;
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use std::path::Path;
+use tracing::info;
+
+/// State shared by every command: the configuration file merged with the
+/// global command-line options
+pub struct Session {
+    pub config: XsConfig,
+    pub progress: bool,
+}
+
+impl Session {
+    /// Load the configuration file and apply the global options
+    pub fn from_args(global: &GlobalArgs) -> Result<Self> {
+        let config = if Path::new(&global.config).exists() {
+            XsConfig::from_file(&global.config)
+                .with_context(|| format!("Failed to load config from {}", global.config))?
+        } else {
+            eprintln!("⚠️  Config file not found, using defaults");
+            XsConfig::default()
+        };
+
+        let mut config = config.merge_with_cli(None, global.threads);
+        config.logging.level = global.effective_log_level();
+
+        Ok(Self {
+            config,
+            progress: !global.no_progress,
+        })
+    }
+
+    /// Initialize logging and the rayon thread pool
+    pub fn init(&self) -> Result<()> {
+        let log_file = self.config.log_file_path();
+        init_logging(&self.config.logging.level, log_file.as_deref())
+            .context("Failed to initialize logging")?;
+
+        if self.config.processing.num_threads > 0 {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.config.processing.num_threads)
+                .build_global()
+                .context("Failed to configure thread pool")?;
+            info!("Using {} threads", self.config.processing.num_threads);
+        } else {
+            info!("Using auto-detected thread count");
+        }
+        Ok(())
+    }
+
+    /// The input file itself, or the XML files below the input directory
+    /// (configured extensions, `max_depth` or the configured depth)
+    pub fn input_files(&self, input: &Path, max_depth: Option<usize>) -> Result<Vec<String>> {
+        if input.is_file() {
+            return Ok(vec![input.to_string_lossy().to_string()]);
+        }
+        validate_directory(input).context("Input directory validation failed")?;
+        find_xml_files(
+            input,
+            &self.config.processing.file_extensions,
+            max_depth.unwrap_or(self.config.processing.max_depth),
+        )
+        .context("Failed to find XML files")
+    }
+
+    /// Progress bar over `len` files, unless disabled
+    pub fn progress_bar(&self, len: usize) -> Option<ProgressBar> {
+        self.progress.then(|| create_progress_bar(len))
+    }
+}
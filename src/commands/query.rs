@@ -0,0 +1,146 @@
+use super::Session;
+use crate::cli::QueryArgs;
+use crate::processor::load_result;
+use crate::query::{query_files, QueryResult, StructurePattern, XPath};
+use anyhow::{Context, Result};
+use console::style;
+use indicatif::ProgressBar;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Longest fragment printed per match line
+const FRAGMENT_WIDTH: usize = 120;
+
+/// Evaluate an XPath expression or a structural pattern over a directory
+/// or file and print the matches
+pub fn run(args: &QueryArgs, session: &Session) -> Result<()> {
+    if args.pattern {
+        return run_pattern(args, session);
+    }
+
+    let xpath: XPath = args
+        .expr
+        .parse()
+        .with_context(|| format!("Invalid XPath expression: {}", args.expr))?;
+
+    let files = session.input_files(&args.input, args.max_depth)?;
+    let result = query_files(&files, &xpath, progress_bar(args, session, files.len()));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if args.count {
+        print_counts(&result);
+    } else {
+        print_matches(&result);
+    }
+    Ok(())
+}
+
+fn run_pattern(args: &QueryArgs, session: &Session) -> Result<()> {
+    let pattern: StructurePattern = args
+        .expr
+        .parse()
+        .with_context(|| format!("Invalid structural pattern: {}", args.expr))?;
+
+    if args.groups {
+        let result = load_result(&args.input)?;
+        let groups = pattern.matching_groups(&result);
+        if args.json {
+            let summaries: Vec<_> = groups
+                .iter()
+                .map(|g| {
+                    json!({
+                        "hash": g.skeleton.hash,
+                        "root": g.skeleton.root,
+                        "count": g.count,
+                        "files": g.files,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&summaries)?);
+        } else if args.count {
+            println!("{}", groups.len());
+        } else {
+            for g in &groups {
+                println!(
+                    "{}  <{}>  {} files",
+                    g.skeleton.hash, g.skeleton.root, g.count
+                );
+            }
+            let files: usize = groups.iter().map(|g| g.count).sum();
+            eprintln!("\n🔎 {} groups ({} files) match", groups.len(), files);
+        }
+        return Ok(());
+    }
+
+    let files = session.input_files(&args.input, args.max_depth)?;
+    let matched = pattern.matching_files(&files, progress_bar(args, session, files.len()));
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&matched)?);
+    } else if args.count {
+        println!("{}", matched.len());
+    } else {
+        for file in &matched {
+            println!("{}", file);
+        }
+        eprintln!("\n🔎 {} of {} files match", matched.len(), files.len());
+    }
+    Ok(())
+}
+
+fn progress_bar(args: &QueryArgs, session: &Session, len: usize) -> Option<ProgressBar> {
+    if args.json || args.count {
+        None
+    } else {
+        session.progress_bar(len)
+    }
+}
+
+fn print_matches(result: &QueryResult) {
+    for m in &result.matches {
+        println!(
+            "{}  {}",
+            style(format!("{}:{}:{}", m.file, m.line, m.column)).cyan(),
+            one_line(&m.fragment)
+        );
+    }
+    print_footer(result);
+}
+
+fn print_counts(result: &QueryResult) {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for m in &result.matches {
+        *counts.entry(m.file.as_str()).or_default() += 1;
+    }
+    for (file, count) in counts {
+        println!("{}  {}", count, file);
+    }
+    print_footer(result);
+}
+
+fn print_footer(result: &QueryResult) {
+    eprintln!(
+        "\n🔎 {} matches in {} files",
+        result.matches.len(),
+        result.matched_files()
+    );
+    for failure in &result.failures {
+        eprintln!(
+            "{} {}: {}",
+            style("⚠️").yellow(),
+            failure.file,
+            failure.message
+        );
+    }
+}
+
+/// Collapse whitespace and truncate a fragment to a single display line
+fn one_line(fragment: &str) -> String {
+    let collapsed = fragment.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > FRAGMENT_WIDTH {
+        let truncated: String = collapsed.chars().take(FRAGMENT_WIDTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        collapsed
+    }
+}
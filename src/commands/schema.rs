@@ -0,0 +1,46 @@
+use crate::cli::SchemaArgs;
+use crate::export::rng::skeleton_to_rng;
+use crate::processor::{load_result, SkeletonSignature};
+use crate::struct_processor::parse_xml_structure;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Print or write a RELAX NG schema derived from a group or file skeleton
+pub fn run(args: &SchemaArgs) -> Result<()> {
+    let skeleton = load_skeleton(&args.target, &args.results)?;
+    let rng = skeleton_to_rng(&skeleton, args.ns.as_deref());
+
+    match &args.output {
+        Some(path) => {
+            fs::write(path, rng)
+                .with_context(|| format!("Failed to write to {}", path.display()))?;
+            println!("📐 Schema saved to: {}", path.display());
+        }
+        None => print!("{}", rng),
+    }
+    Ok(())
+}
+
+/// Skeleton of an XML file, or of a group looked up by hash in a results file
+fn load_skeleton(target: &str, results: &Path) -> Result<SkeletonSignature> {
+    let path = Path::new(target);
+    if path.is_file() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let structure = parse_xml_structure(&content)
+            .with_context(|| format!("Failed to parse XML structure: {}", path.display()))?;
+        return Ok(structure.to_skeleton());
+    }
+
+    let hash: u64 = target
+        .parse()
+        .with_context(|| format!("'{}' is neither an existing file nor a group hash", target))?;
+    let result = load_result(results)?;
+    result
+        .groups
+        .into_iter()
+        .find(|g| g.skeleton.hash == hash)
+        .map(|g| g.skeleton)
+        .with_context(|| format!("Group {} not found in {}", hash, results.display()))
+}
@@ -0,0 +1,76 @@
+use crate::cli::ShowArgs;
+use crate::export::dot::skeleton_to_dot;
+use crate::processor::{load_result, render_skeleton, render_structure, TreeOptions};
+use crate::struct_processor::parse_xml_structure;
+use anyhow::{Context, Result};
+use is_terminal::IsTerminal;
+use std::fs;
+use std::path::Path;
+
+/// Print the skeleton of a file or of a group from a results file
+pub fn run(args: &ShowArgs) -> Result<()> {
+    let options = TreeOptions {
+        max_depth: args.depth,
+        color: !args.no_color && std::io::stdout().is_terminal(),
+    };
+
+    let path = Path::new(&args.target);
+    if path.is_file() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let structure = parse_xml_structure(&content)
+            .with_context(|| format!("Failed to parse XML structure: {}", path.display()))?;
+        let skeleton = structure.to_skeleton();
+
+        if args.dot {
+            print!("{}", skeleton_to_dot(&skeleton));
+            return Ok(());
+        }
+
+        println!("{} (group {})\n", path.display(), skeleton.hash);
+        if args.full {
+            print!("{}", render_structure(&structure, &options));
+        } else {
+            print!("{}", render_skeleton(&skeleton, &options));
+        }
+        return Ok(());
+    }
+
+    let hash: u64 = args.target.parse().with_context(|| {
+        format!(
+            "'{}' is neither an existing file nor a group hash",
+            args.target
+        )
+    })?;
+    let result = load_result(&args.results)?;
+    let (rank, group) = result
+        .groups
+        .iter()
+        .enumerate()
+        .find(|(_, g)| g.skeleton.hash == hash)
+        .with_context(|| format!("Group {} not found in {}", hash, args.results.display()))?;
+
+    if args.dot {
+        print!("{}", skeleton_to_dot(&group.skeleton));
+        return Ok(());
+    }
+
+    println!("Group #{} ({}) - {} files\n", rank + 1, hash, group.count);
+    match (args.full, group.example_structure()) {
+        (true, Some(structure)) => print!("{}", render_structure(structure, &options)),
+        (true, None) => {
+            eprintln!("⚠️  No example structure stored for this group, showing skeleton");
+            print!("{}", render_skeleton(&group.skeleton, &options));
+        }
+        (false, _) => print!("{}", render_skeleton(&group.skeleton, &options)),
+    }
+
+    if !group.examples.is_empty() {
+        println!("\nExamples:");
+        for example in &group.examples {
+            println!("  {} ({} bytes)", example.path, example.size);
+        }
+    }
+
+    Ok(())
+}
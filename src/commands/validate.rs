@@ -0,0 +1,132 @@
+use super::Session;
+use crate::cli::ValidateArgs;
+use crate::validation::{
+    github_annotations, to_junit_xml, validate_files, CorpusReport, DeclaredSchemas, Dtd, Patch,
+    PbSequence, Registry, RelaxNg, ReportFormat, SarifLog, Schematron, XmlSchema,
+};
+use anyhow::{Context, Result};
+use console::style;
+use serde_json::json;
+use std::fs;
+
+/// Validate files and directories against the built-in TEI rules and the
+/// configured `[[rules]]` (plus any given or declared schemas) in parallel; fails if any file has errors
+pub fn run(args: &ValidateArgs, session: &Session) -> Result<()> {
+    let mut files = Vec::new();
+    for path in &args.paths {
+        files.extend(session.input_files(path, args.max_depth)?);
+    }
+
+    let mut registry = Registry::with_config(&session.config.rules)?;
+    registry.replace(PbSequence::new(session.config.pb_sequence.clone()))?;
+    for path in &args.schematron {
+        registry.register(Schematron::from_file(path)?)?;
+    }
+    for path in &args.relaxng {
+        registry.register(RelaxNg::from_file(path)?)?;
+    }
+    for path in &args.xsd {
+        registry.register(XmlSchema::from_file(path)?)?;
+    }
+    for path in &args.dtd {
+        registry.register(Dtd::from_file(path)?)?;
+    }
+    if args.declared_schemas {
+        registry.register(DeclaredSchemas::new())?;
+    }
+    for id in &args.disable {
+        registry.disable(id)?;
+    }
+    let mut result = validate_files(&files, &registry, session.progress_bar(files.len()));
+    if args.fix_dry_run {
+        return apply_fixes(&result, false);
+    }
+    if args.fix {
+        apply_fixes(&result, true)?;
+        result = validate_files(&files, &registry, session.progress_bar(files.len()));
+    }
+    let summary = result.summary(args.worst);
+
+    match args.format {
+        ReportFormat::Text => {
+            if !args.summary_only {
+                for file in &result.files {
+                    if file.report.messages().next().is_some() {
+                        println!("\n{}", style(&file.file).bold());
+                        file.report.print();
+                    }
+                }
+                for failure in &result.failures {
+                    println!("\n{}", style(&failure.file).bold());
+                    println!("{} {}", style("✗").red(), failure.message);
+                }
+            }
+            summary.print();
+        }
+        ReportFormat::Json => {
+            let out = json!({
+                "files": result.files,
+                "failures": result.failures,
+                "summary": summary,
+            });
+            println!("{}", serde_json::to_string_pretty(&out)?);
+        }
+        ReportFormat::Sarif => {
+            println!(
+                "{}",
+                SarifLog::from_corpus(&result, &registry).to_json_string()?
+            );
+        }
+        ReportFormat::Junit => print!("{}", to_junit_xml(&result)),
+        ReportFormat::Github => print!("{}", github_annotations(&result)),
+    }
+
+    if !result.is_valid() {
+        anyhow::bail!(
+            "{} of {} files have validation errors",
+            summary.files_with_errors,
+            summary.files
+        );
+    }
+    if args.format == ReportFormat::Text {
+        println!(
+            "\n{}",
+            style(format!("✔ {} files passed validation", summary.files)).green()
+        );
+    }
+    Ok(())
+}
+
+/// Write the fixes offered in each file's report, or print them as a
+/// unified diff; the tally goes to stderr so the diff can be piped to `patch`
+fn apply_fixes(result: &CorpusReport, write: bool) -> Result<()> {
+    let (mut applied, mut skipped, mut patched_files) = (0, 0, 0);
+    for file in &result.files {
+        let source = fs::read_to_string(&file.file)
+            .with_context(|| format!("Failed to read file: {}", file.file))?;
+        let patch = Patch::from_report(&source, &file.report);
+        skipped += patch.skipped;
+        if patch.is_empty() {
+            continue;
+        }
+        if write {
+            fs::write(&file.file, patch.apply(&source))
+                .with_context(|| format!("Failed to write file: {}", file.file))?;
+        } else {
+            print!("{}", patch.unified_diff(&file.file, &source));
+        }
+        applied += patch.applied;
+        patched_files += 1;
+    }
+    eprintln!(
+        "{} {} fixes in {} files{}",
+        if write { "Applied" } else { "Would apply" },
+        applied,
+        patched_files,
+        match skipped {
+            0 => String::new(),
+            n => format!(" ({} conflicting fixes left for another run)", n),
+        }
+    );
+    Ok(())
+}
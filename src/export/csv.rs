@@ -0,0 +1,101 @@
+use crate::processor::ProcessingResult;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Path of the groups table written next to the files table:
+/// `results.csv` -> `results.groups.csv`
+pub fn groups_path(output_path: &Path) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    output_path.with_file_name(format!("{}.groups.csv", stem))
+}
+
+/// Write one row per file: path, group hash and group rank
+pub fn write_files(result: &ProcessingResult, output_path: &Path) -> Result<()> {
+    info!("Writing CSV file table to: {}", output_path.display());
+
+    let mut writer = ::csv::Writer::from_path(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    writer.write_record(["path", "group_hash", "group_rank"])?;
+
+    for (i, group) in result.groups.iter().enumerate() {
+        let hash = group.skeleton.hash.to_string();
+        let rank = (i + 1).to_string();
+        for path in &group.files {
+            writer.write_record([path.as_str(), &hash, &rank])?;
+        }
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    Ok(())
+}
+
+/// Write one row per structure group
+pub fn write_groups(result: &ProcessingResult, output_path: &Path) -> Result<()> {
+    info!("Writing CSV group table to: {}", output_path.display());
+
+    let mut writer = ::csv::Writer::from_path(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    writer.write_record(["group_rank", "group_hash", "count", "root", "signature"])?;
+
+    for (i, group) in result.groups.iter().enumerate() {
+        writer.write_record([
+            (i + 1).to_string(),
+            group.skeleton.hash.to_string(),
+            group.count.to_string(),
+            group.skeleton.root.clone(),
+            group.signature_string(),
+        ])?;
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{StructureGroup, XmlStructure};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_groups_path() {
+        assert_eq!(
+            groups_path(Path::new("out/results.csv")),
+            PathBuf::from("out/results.groups.csv")
+        );
+    }
+
+    #[test]
+    fn test_write_files_and_groups() {
+        let mut group = StructureGroup::new(XmlStructure::new("book".to_string()).to_skeleton());
+        group.add_file("a, b.xml".to_string(), 7);
+        let result = ProcessingResult {
+            total_files: 1,
+            unique_structures: 1,
+            groups: vec![group],
+            root: None,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let files_path = temp_dir.path().join("out.csv");
+        let groups_path = groups_path(&files_path);
+        write_files(&result, &files_path).unwrap();
+        write_groups(&result, &groups_path).unwrap();
+
+        let files = std::fs::read_to_string(&files_path).unwrap();
+        assert!(files.starts_with("path,group_hash,group_rank\n"));
+        assert!(files.contains("\"a, b.xml\""));
+
+        let groups = std::fs::read_to_string(&groups_path).unwrap();
+        assert_eq!(groups.lines().count(), 2);
+        assert!(groups.lines().nth(1).unwrap().starts_with("1,"));
+    }
+}
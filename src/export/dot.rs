@@ -0,0 +1,204 @@
+use crate::processor::{ProcessingResult, SkeletonSignature};
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::path::Path;
+use tracing::info;
+
+/// Default minimum similarity for linking two groups in the corpus graph
+pub const DEFAULT_MIN_SIMILARITY: f64 = 0.5;
+
+/// Render a skeleton as a DOT digraph: one node per element path,
+/// parent/child edges, attribute keys in the node labels
+pub fn skeleton_to_dot(skeleton: &SkeletonSignature) -> String {
+    let paths = skeleton.paths();
+    let ids: HashMap<&str, usize> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.path.as_str(), i))
+        .collect();
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph skeleton {{").unwrap();
+    writeln!(dot, "  rankdir=LR;").unwrap();
+    writeln!(dot, "  node [shape=box, fontname=\"Helvetica\"];").unwrap();
+
+    for (i, path) in paths.iter().enumerate() {
+        let mut label = path.name().to_string();
+        if !path.attributes.is_empty() {
+            label.push('\n');
+            label.push_str(
+                &path
+                    .attributes
+                    .iter()
+                    .map(|a| format!("@{}", a))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+        writeln!(dot, "  n{} [label=\"{}\"];", i, escape_dot(&label)).unwrap();
+    }
+
+    for (i, path) in paths.iter().enumerate() {
+        if let Some((parent, _)) = path.path.rsplit_once('/') {
+            if let Some(parent_id) = ids.get(parent) {
+                writeln!(dot, "  n{} -> n{};", parent_id, i).unwrap();
+            }
+        }
+    }
+
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+/// Render a whole result as an undirected graph of structure groups.
+/// Nodes are sized by file count; groups whose element/attribute path sets
+/// have a Jaccard similarity of at least `min_similarity` are linked.
+pub fn result_to_dot(result: &ProcessingResult, min_similarity: f64) -> String {
+    let features: Vec<BTreeSet<String>> = result
+        .groups
+        .iter()
+        .map(|g| skeleton_features(&g.skeleton))
+        .collect();
+    let max_count = result.groups.iter().map(|g| g.count).max().unwrap_or(1) as f64;
+
+    let mut dot = String::new();
+    writeln!(dot, "graph groups {{").unwrap();
+    writeln!(dot, "  layout=neato;").unwrap();
+    writeln!(dot, "  overlap=false;").unwrap();
+    writeln!(
+        dot,
+        "  node [shape=circle, style=filled, fillcolor=\"#cfe0fa\", fontname=\"Helvetica\"];"
+    )
+    .unwrap();
+
+    for (i, group) in result.groups.iter().enumerate() {
+        // Area proportional to the file count
+        let width = 0.6 + 2.4 * (group.count as f64 / max_count).sqrt();
+        let label = format!(
+            "#{} <{}>\n{} files",
+            i + 1,
+            group.skeleton.root,
+            group.count
+        );
+        writeln!(
+            dot,
+            "  g{} [label=\"{}\", width={:.2}, tooltip=\"{}\"];",
+            i,
+            escape_dot(&label),
+            width,
+            group.skeleton.hash
+        )
+        .unwrap();
+    }
+
+    for i in 0..features.len() {
+        for j in (i + 1)..features.len() {
+            let similarity = jaccard(&features[i], &features[j]);
+            if similarity >= min_similarity {
+                writeln!(
+                    dot,
+                    "  g{} -- g{} [label=\"{:.2}\", penwidth={:.1}];",
+                    i,
+                    j,
+                    similarity,
+                    1.0 + 3.0 * similarity
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+/// Write the corpus group graph to a `.dot` file
+pub fn write_result_graph(
+    result: &ProcessingResult,
+    output_path: &Path,
+    min_similarity: f64,
+) -> Result<()> {
+    info!("Writing DOT graph to: {}", output_path.display());
+    std::fs::write(output_path, result_to_dot(result, min_similarity))
+        .with_context(|| format!("Failed to write to {}", output_path.display()))
+}
+
+/// Element paths and `path/@attr` keys of a skeleton
+fn skeleton_features(skeleton: &SkeletonSignature) -> BTreeSet<String> {
+    let mut features = BTreeSet::new();
+    for path in skeleton.paths() {
+        for attr in &path.attributes {
+            features.insert(format!("{}/@{}", path.path, attr));
+        }
+        features.insert(path.path);
+    }
+    features
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Escape a string for a double-quoted DOT label (newlines become `\n`)
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::StructureGroup;
+    use crate::struct_processor::parse_xml_structure;
+
+    fn group(xml: &str, files: usize) -> StructureGroup {
+        let mut group = StructureGroup::new(parse_xml_structure(xml).unwrap().to_skeleton());
+        for i in 0..files {
+            group.add_file(format!("{}.xml", i), xml.len() as u64);
+        }
+        group
+    }
+
+    #[test]
+    fn test_skeleton_to_dot() {
+        let skeleton = parse_xml_structure(r#"<TEI><text><div type="a"/></text></TEI>"#)
+            .unwrap()
+            .to_skeleton();
+        let dot = skeleton_to_dot(&skeleton);
+
+        assert!(dot.starts_with("digraph skeleton {"));
+        assert!(dot.contains("n2 [label=\"div\\n@type\"];"));
+        assert!(dot.contains("n0 -> n1;"));
+        assert!(dot.contains("n1 -> n2;"));
+    }
+
+    #[test]
+    fn test_result_to_dot_links_similar_groups() {
+        let result = ProcessingResult {
+            total_files: 6,
+            unique_structures: 3,
+            groups: vec![
+                group("<a><b/><c/></a>", 4),
+                group("<a><b/><c/><d/></a>", 1),
+                group("<x><y/></x>", 1),
+            ],
+            root: None,
+        };
+        let dot = result_to_dot(&result, DEFAULT_MIN_SIMILARITY);
+
+        assert!(dot.contains("g0 -- g1 [label=\"0.75\""));
+        assert!(!dot.contains("g0 -- g2"));
+        assert!(dot.contains("g0 [label=\"#1 <a>\\n4 files\", width=3.00"));
+    }
+
+    #[test]
+    fn test_escape_dot() {
+        assert_eq!(escape_dot("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
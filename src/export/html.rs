@@ -0,0 +1,200 @@
+use crate::processor::{ProcessingResult, StructureGroup};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 { margin-bottom: 0.2rem; }
+.summary { color: #666; margin-bottom: 1rem; }
+#search { width: 100%; max-width: 40rem; padding: 0.4rem; font-size: 1rem; margin-bottom: 1rem; }
+.group { border: 1px solid #ddd; border-radius: 6px; padding: 0.6rem 1rem; margin-bottom: 1rem; }
+.group h2 { font-size: 1.1rem; margin: 0.2rem 0; }
+.count { background: #2d6cdf; color: #fff; border-radius: 10px; padding: 0 0.5rem; font-size: 0.9rem; }
+.hash { color: #888; font-family: monospace; font-size: 0.85rem; }
+.tree ul { list-style: none; margin: 0; padding-left: 1.2rem; border-left: 1px dotted #bbb; }
+.tree summary, .tree .leaf { font-family: monospace; cursor: default; }
+.tree summary { cursor: pointer; }
+.el { color: #7a3e9d; font-weight: bold; }
+.attr { background: #eef3e6; color: #47702a; border-radius: 4px; padding: 0 0.3rem; margin-left: 0.2rem; font-size: 0.85rem; }
+.files li { font-family: monospace; font-size: 0.9rem; }
+.hidden { display: none; }
+"#;
+
+const SCRIPT: &str = r#"
+document.getElementById('search').addEventListener('input', function (e) {
+  var q = e.target.value.toLowerCase();
+  var shown = 0;
+  document.querySelectorAll('.group').forEach(function (g) {
+    var hit = !q || g.textContent.toLowerCase().indexOf(q) !== -1;
+    g.classList.toggle('hidden', !hit);
+    if (hit) shown++;
+  });
+  document.getElementById('shown').textContent = shown;
+});
+"#;
+
+/// Write a self-contained HTML report listing every structure group with a
+/// collapsible skeleton tree, its files and a client-side search box
+pub fn write_report(result: &ProcessingResult, output_path: &Path) -> Result<()> {
+    info!("Writing HTML report to: {}", output_path.display());
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    let mut w = BufWriter::new(file);
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html lang=\"en\"><head><meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>XML Structure Report</title>")?;
+    writeln!(w, "<style>{}</style></head><body>", STYLE)?;
+    writeln!(w, "<h1>XML Structure Report</h1>")?;
+    writeln!(
+        w,
+        "<div class=\"summary\">{} files, {} unique structures, <span id=\"shown\">{}</span> shown</div>",
+        result.total_files, result.unique_structures, result.groups.len()
+    )?;
+    writeln!(
+        w,
+        "<input id=\"search\" type=\"search\" placeholder=\"Filter by element, attribute or file path\">"
+    )?;
+
+    for (i, group) in result.groups.iter().enumerate() {
+        write_group(&mut w, i + 1, group)?;
+    }
+
+    writeln!(w, "<script>{}</script></body></html>", SCRIPT)?;
+    w.flush()
+        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    Ok(())
+}
+
+fn write_group(w: &mut impl Write, rank: usize, group: &StructureGroup) -> Result<()> {
+    writeln!(w, "<section class=\"group\">")?;
+    writeln!(
+        w,
+        "<h2>#{} <span class=\"el\">&lt;{}&gt;</span> <span class=\"count\">{} files</span> <span class=\"hash\">{}</span></h2>",
+        rank,
+        escape_html(&group.skeleton.root),
+        group.count,
+        group.skeleton.hash
+    )?;
+
+    writeln!(w, "<div class=\"tree\">")?;
+    write_tree(w, &group.skeleton.root, &group.skeleton.skeleton, true)?;
+    writeln!(w, "</div>")?;
+
+    writeln!(
+        w,
+        "<details class=\"files\"><summary>Files ({})</summary><ul>",
+        group.files.len()
+    )?;
+    for path in &group.files {
+        writeln!(w, "<li>{}</li>", escape_html(path))?;
+    }
+    writeln!(w, "</ul></details>")?;
+
+    writeln!(w, "</section>")?;
+    Ok(())
+}
+
+/// Render one skeleton node; nodes with children become `<details>`
+fn write_tree(w: &mut impl Write, name: &str, node: &Value, open: bool) -> Result<()> {
+    let mut label = format!("<span class=\"el\">{}</span>", escape_html(name));
+    for attr in skeleton_attributes(node) {
+        label.push_str(&format!(
+            "<span class=\"attr\">@{}</span>",
+            escape_html(attr)
+        ));
+    }
+
+    let children: Vec<(&String, &Value)> = node
+        .as_object()
+        .map(|m| m.iter().filter(|(k, _)| *k != "@attributes").collect())
+        .unwrap_or_default();
+
+    if children.is_empty() {
+        writeln!(w, "<div class=\"leaf\">{}</div>", label)?;
+        return Ok(());
+    }
+
+    writeln!(
+        w,
+        "<details{}><summary>{}</summary><ul>",
+        if open { " open" } else { "" },
+        label
+    )?;
+    for (child_name, child) in children {
+        write!(w, "<li>")?;
+        write_tree(w, child_name, child, false)?;
+        writeln!(w, "</li>")?;
+    }
+    writeln!(w, "</ul></details>")?;
+    Ok(())
+}
+
+/// Attribute keys of a skeleton node
+pub(crate) fn skeleton_attributes(node: &Value) -> impl Iterator<Item = &str> {
+    node.get("@attributes")
+        .and_then(|a| a.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str())
+}
+
+/// Escape text for use in HTML content and attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::parse_xml_structure;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_report_contains_tree_and_files() {
+        let structure =
+            parse_xml_structure(r#"<TEI><text><div type="a"><head/></div></text></TEI>"#).unwrap();
+        let mut group = StructureGroup::new(structure.to_skeleton());
+        group.add_file("dir/<odd>.xml".to_string(), 56);
+        let result = ProcessingResult {
+            total_files: 1,
+            unique_structures: 1,
+            groups: vec![group],
+            root: None,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("report.html");
+        write_report(&result, &path).unwrap();
+
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.contains("<span class=\"el\">div</span><span class=\"attr\">@type</span>"));
+        assert!(html.contains("<div class=\"leaf\"><span class=\"el\">head</span></div>"));
+        assert!(html.contains("dir/&lt;odd&gt;.xml"));
+        assert!(html.contains("id=\"search\""));
+    }
+}
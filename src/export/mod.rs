@@ -0,0 +1,52 @@
+pub mod csv;
+pub mod dot;
+pub mod html;
+pub mod ndjson;
+pub mod rng;
+pub mod sqlite;
+
+use crate::processor::{write_result_to_file, ProcessingResult};
+use crate::xsconfig::OutputFormat;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Write a processing result in the requested format.
+/// Returns every file that was written.
+pub fn write_result(
+    result: &ProcessingResult,
+    output_path: &Path,
+    format: OutputFormat,
+    pretty: bool,
+) -> Result<Vec<PathBuf>> {
+    match format {
+        OutputFormat::Json => {
+            write_result_to_file(result, output_path, pretty)?;
+            Ok(vec![output_path.to_path_buf()])
+        }
+        OutputFormat::Ndjson => {
+            ndjson::write_files(result, output_path)?;
+            Ok(vec![output_path.to_path_buf()])
+        }
+        OutputFormat::Csv => {
+            let groups_path = csv::groups_path(output_path);
+            csv::write_files(result, output_path)?;
+            csv::write_groups(result, &groups_path)?;
+            Ok(vec![output_path.to_path_buf(), groups_path])
+        }
+        OutputFormat::Sqlite => {
+            sqlite::write_database(result, output_path)?;
+            Ok(vec![output_path.to_path_buf()])
+        }
+    }
+}
+
+/// Swap a `.json` extension for the one matching `format`, so the default
+/// output file name from the configuration follows `--format` (an explicit
+/// `--output` is left alone)
+pub fn adjust_extension(path: &Path, format: OutputFormat) -> PathBuf {
+    if path.extension().is_some_and(|e| e == "json") {
+        path.with_extension(format.extension())
+    } else {
+        path.to_path_buf()
+    }
+}
@@ -0,0 +1,77 @@
+use crate::processor::ProcessingResult;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+/// One NDJSON line: a file and the group it belongs to
+#[derive(Debug, Serialize)]
+struct FileRecord<'a> {
+    path: &'a str,
+    group_hash: u64,
+    /// 1-based position of the group in the result (most common first)
+    group_rank: usize,
+}
+
+/// Write one JSON record per file, streaming line by line
+pub fn write_files(result: &ProcessingResult, output_path: &Path) -> Result<()> {
+    info!("Writing NDJSON results to: {}", output_path.display());
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    for (i, group) in result.groups.iter().enumerate() {
+        for path in &group.files {
+            let record = FileRecord {
+                path,
+                group_hash: group.skeleton.hash,
+                group_rank: i + 1,
+            };
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+        }
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{StructureGroup, XmlStructure};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_one_line_per_file() {
+        let mut group = StructureGroup::new(XmlStructure::new("book".to_string()).to_skeleton());
+        group.add_file("a.xml".to_string(), 7);
+        group.add_file("b.xml".to_string(), 7);
+        let hash = group.skeleton.hash;
+        let result = ProcessingResult {
+            total_files: 2,
+            unique_structures: 1,
+            groups: vec![group],
+            root: None,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.ndjson");
+        write_files(&result, &path).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["path"], "b.xml");
+        assert_eq!(lines[1]["group_hash"], hash);
+        assert_eq!(lines[1]["group_rank"], 1);
+    }
+}
@@ -0,0 +1,229 @@
+use super::html::escape_html;
+use crate::processor::{SkeletonPath, SkeletonSignature};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Attributes that are usually written with the `xml:` prefix; skeletons
+/// written before namespaces were recorded only keep local names, so the
+/// schema accepts both forms
+const XML_ATTRIBUTES: [&str; 4] = ["id", "lang", "space", "base"];
+
+/// Derive a RELAX NG grammar (XML syntax) from a skeleton.
+///
+/// Every element path becomes a define named after the path
+/// (`TEI.text.body`), with a numeric suffix when two paths give the same
+/// name (`a/b` and `a.b`). Elements allow their skeleton children in any order
+/// and number, mixed with text; attributes are optional, since a merged
+/// skeleton doesn't record which instances had them. Elements and attributes
+/// are in the namespaces the skeleton recorded; `ns`, or else the namespace
+/// of the root, is the grammar's default. The grammar accepts every file of
+/// the group, and rejects elements and attributes at paths the group never
+/// had.
+pub fn skeleton_to_rng(skeleton: &SkeletonSignature, ns: Option<&str>) -> String {
+    let paths = skeleton.paths();
+    let names = define_names(&paths);
+    let ns = ns.or(match paths[0].namespaces.iter().collect::<Vec<_>>()[..] {
+        [root_ns] => Some(root_ns.as_str()),
+        _ => None,
+    });
+    // Skeletons of older results have no namespaces; their elements take
+    // the grammar's
+    let recorded = !skeleton.namespaces.is_empty();
+
+    let mut rng = String::new();
+    writeln!(rng, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        rng,
+        "<!-- Derived from structure group {} (<{}>) -->",
+        skeleton.hash, skeleton.root
+    )
+    .unwrap();
+    match ns {
+        Some(ns) => writeln!(
+            rng,
+            r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0" ns="{}">"#,
+            escape_html(ns)
+        ),
+        None => writeln!(
+            rng,
+            r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0">"#
+        ),
+    }
+    .unwrap();
+    writeln!(rng, "  <start>").unwrap();
+    writeln!(
+        rng,
+        r#"    <ref name="{}"/>"#,
+        names[paths[0].path.as_str()]
+    )
+    .unwrap();
+    writeln!(rng, "  </start>").unwrap();
+
+    for path in &paths {
+        let children: Vec<&SkeletonPath> = paths
+            .iter()
+            .filter(|p| p.path.rsplit_once('/').map(|(parent, _)| parent) == Some(&path.path))
+            .collect();
+
+        let element_ns: Vec<&str> = match path.namespaces.iter().collect::<Vec<_>>()[..] {
+            [] if recorded && ns.is_some_and(|ns| !ns.is_empty()) => vec![""],
+            [] => vec![],
+            [single] if Some(single.as_str()) == ns => vec![],
+            ref several => several.iter().map(|ns| ns.as_str()).collect(),
+        };
+        writeln!(rng, r#"  <define name="{}">"#, names[path.path.as_str()]).unwrap();
+        match element_ns[..] {
+            [] => writeln!(rng, r#"    <element name="{}">"#, path.name()).unwrap(),
+            [single] => writeln!(
+                rng,
+                r#"    <element name="{}" ns="{}">"#,
+                path.name(),
+                escape_html(single)
+            )
+            .unwrap(),
+            _ => {
+                writeln!(rng, "    <element>").unwrap();
+                write_name_choice(&mut rng, "      ", path.name(), &element_ns);
+            }
+        }
+        for attr in &path.attributes {
+            let attribute_ns: Vec<&str> = match path.attribute_namespaces.get(attr) {
+                Some(set) => set.iter().map(|ns| ns.as_str()).collect(),
+                None if !recorded && XML_ATTRIBUTES.contains(&attr.as_str()) => vec!["", XML_NS],
+                None => vec![],
+            };
+            writeln!(rng, "      <optional>").unwrap();
+            match attribute_ns[..] {
+                [] => writeln!(
+                    rng,
+                    r#"        <attribute name="{}"><text/></attribute>"#,
+                    attr
+                )
+                .unwrap(),
+                [single] => writeln!(
+                    rng,
+                    r#"        <attribute name="{}" ns="{}"><text/></attribute>"#,
+                    attr,
+                    escape_html(single)
+                )
+                .unwrap(),
+                _ => {
+                    writeln!(rng, "        <attribute>").unwrap();
+                    write_name_choice(&mut rng, "          ", attr, &attribute_ns);
+                    writeln!(rng, "          <text/>").unwrap();
+                    writeln!(rng, "        </attribute>").unwrap();
+                }
+            }
+            writeln!(rng, "      </optional>").unwrap();
+        }
+        if children.is_empty() {
+            writeln!(rng, "      <text/>").unwrap();
+        } else {
+            writeln!(rng, "      <zeroOrMore>").unwrap();
+            writeln!(rng, "        <choice>").unwrap();
+            writeln!(rng, "          <text/>").unwrap();
+            for child in children {
+                writeln!(
+                    rng,
+                    r#"          <ref name="{}"/>"#,
+                    names[child.path.as_str()]
+                )
+                .unwrap();
+            }
+            writeln!(rng, "        </choice>").unwrap();
+            writeln!(rng, "      </zeroOrMore>").unwrap();
+        }
+        writeln!(rng, "    </element>").unwrap();
+        writeln!(rng, "  </define>").unwrap();
+    }
+
+    writeln!(rng, "</grammar>").unwrap();
+    rng
+}
+
+/// A name class for `name` in any of `namespaces`
+fn write_name_choice(rng: &mut String, indent: &str, name: &str, namespaces: &[&str]) {
+    writeln!(rng, "{}<choice>", indent).unwrap();
+    for ns in namespaces {
+        writeln!(
+            rng,
+            r#"{}  <name ns="{}">{}</name>"#,
+            indent,
+            escape_html(ns),
+            name
+        )
+        .unwrap();
+    }
+    writeln!(rng, "{}</choice>", indent).unwrap();
+}
+
+/// Unique define names, by path
+fn define_names(paths: &[SkeletonPath]) -> HashMap<&str, String> {
+    let mut used = HashSet::new();
+    let mut names = HashMap::new();
+    for path in paths {
+        let base = path.path.trim_start_matches('/').replace('/', ".");
+        let mut name = base.clone();
+        let mut n = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        names.insert(path.path.as_str(), name);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::parse_xml_structure;
+    use roxmltree::Document;
+
+    #[test]
+    fn test_skeleton_to_rng() {
+        let skeleton =
+            parse_xml_structure(r#"<TEI id="t"><text><div type="a"><p/></div></text></TEI>"#)
+                .unwrap()
+                .to_skeleton();
+
+        let rng = skeleton_to_rng(&skeleton, Some("http://www.tei-c.org/ns/1.0"));
+
+        let doc = Document::parse(&rng).unwrap();
+        let defines: Vec<&str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("define"))
+            .filter_map(|n| n.attribute("name"))
+            .collect();
+        assert_eq!(
+            defines,
+            vec!["TEI", "TEI.text", "TEI.text.div", "TEI.text.div.p"]
+        );
+        assert!(rng.contains(r#"<ref name="TEI.text.div"/>"#));
+        assert!(rng.contains(r#"<attribute name="type"><text/></attribute>"#));
+        assert!(rng.contains(XML_NS));
+    }
+
+    #[test]
+    fn test_define_names_are_unique() {
+        let skeleton = parse_xml_structure("<TEI><x><a.b/><a><b/></a><a.b_2/></x></TEI>")
+            .unwrap()
+            .to_skeleton();
+
+        let rng = skeleton_to_rng(&skeleton, None);
+
+        let doc = Document::parse(&rng).unwrap();
+        let mut defines: Vec<&str> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("define"))
+            .filter_map(|n| n.attribute("name"))
+            .collect();
+        defines.sort();
+        let count = defines.len();
+        defines.dedup();
+        assert_eq!(defines.len(), count);
+        assert_eq!(count, 6);
+    }
+}
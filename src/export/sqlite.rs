@@ -0,0 +1,163 @@
+use crate::processor::ProcessingResult;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+const SCHEMA: &str = r#"
+CREATE TABLE groups (
+    id        INTEGER PRIMARY KEY,  -- group rank, most common first
+    hash      TEXT    NOT NULL,     -- skeleton hash (decimal u64)
+    count     INTEGER NOT NULL,
+    root      TEXT    NOT NULL,
+    signature TEXT    NOT NULL
+);
+
+CREATE TABLE files (
+    id       INTEGER PRIMARY KEY,
+    path     TEXT    NOT NULL,
+    size     INTEGER,               -- bytes when analyzed; NULL for older results
+    group_id INTEGER NOT NULL REFERENCES groups(id)
+);
+
+-- Element paths of each group's skeleton; every file of a group has them
+CREATE TABLE paths (
+    id       INTEGER PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES groups(id),
+    path     TEXT    NOT NULL,
+    name     TEXT    NOT NULL,
+    depth    INTEGER NOT NULL,
+    is_leaf  INTEGER NOT NULL
+);
+
+CREATE TABLE path_attributes (
+    path_id INTEGER NOT NULL REFERENCES paths(id),
+    name    TEXT    NOT NULL
+);
+"#;
+
+// Created after the bulk load, which is much faster than maintaining them
+const INDEXES: &str = r#"
+CREATE UNIQUE INDEX idx_groups_hash ON groups(hash);
+CREATE UNIQUE INDEX idx_files_path ON files(path);
+CREATE INDEX idx_files_group ON files(group_id);
+CREATE INDEX idx_paths_group ON paths(group_id);
+CREATE INDEX idx_paths_path ON paths(path, group_id);
+CREATE INDEX idx_paths_name ON paths(name);
+CREATE INDEX idx_path_attributes_path ON path_attributes(path_id);
+CREATE INDEX idx_path_attributes_name ON path_attributes(name, path_id);
+"#;
+
+/// Write a processing result to a new SQLite database (replacing any
+/// existing file) with `groups`, `files`, `paths` and `path_attributes` tables
+pub fn write_database(result: &ProcessingResult, output_path: &Path) -> Result<()> {
+    info!("Writing SQLite database to: {}", output_path.display());
+
+    if output_path.exists() {
+        fs::remove_file(output_path)
+            .with_context(|| format!("Failed to replace {}", output_path.display()))?;
+    }
+
+    let mut conn = Connection::open(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    conn.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert_group = tx.prepare(
+            "INSERT INTO groups (id, hash, count, root, signature) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_file =
+            tx.prepare("INSERT INTO files (path, size, group_id) VALUES (?1, ?2, ?3)")?;
+        let mut insert_path = tx.prepare(
+            "INSERT INTO paths (group_id, path, name, depth, is_leaf) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_attr =
+            tx.prepare("INSERT INTO path_attributes (path_id, name) VALUES (?1, ?2)")?;
+
+        for (i, group) in result.groups.iter().enumerate() {
+            let group_id = (i + 1) as i64;
+            insert_group.execute(params![
+                group_id,
+                group.skeleton.hash.to_string(),
+                group.count as i64,
+                group.skeleton.root,
+                group.signature_string(),
+            ])?;
+
+            for path in group.skeleton.paths() {
+                insert_path.execute(params![
+                    group_id,
+                    path.path,
+                    path.name(),
+                    path.depth() as i64,
+                    path.is_leaf,
+                ])?;
+                let path_id = tx.last_insert_rowid();
+                for attr in &path.attributes {
+                    insert_attr.execute(params![path_id, attr])?;
+                }
+            }
+
+            for (n, path) in group.files.iter().enumerate() {
+                let size = group.sizes.get(n).map(|&size| size as i64);
+                insert_file.execute(params![path, size, group_id])?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    conn.execute_batch(INDEXES)?;
+    info!("Successfully wrote database {}", output_path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::process_xml_files;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let file1 = temp_path.join("a.xml");
+        let file2 = temp_path.join("b.xml");
+        fs::write(&file1, r#"<TEI><text><div type="x"/></text></TEI>"#).unwrap();
+        fs::write(&file2, r#"<TEI><text/></TEI>"#).unwrap();
+
+        let files = vec![
+            file1.to_string_lossy().to_string(),
+            file2.to_string_lossy().to_string(),
+        ];
+        let result = process_xml_files(files, None).unwrap();
+        // Sizes come from the analysis, not from the files at export time
+        fs::write(&file1, "<TEI/>").unwrap();
+        let db = temp_path.join("out.sqlite");
+        write_database(&result, &db).unwrap();
+        // Writing twice replaces the database
+        write_database(&result, &db).unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        let files: i64 = conn
+            .query_row("SELECT COUNT(*) FROM files", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(files, 2);
+
+        let (path, size): (String, i64) = conn
+            .query_row(
+                "SELECT f.path, f.size FROM files f
+                 JOIN paths p ON p.group_id = f.group_id
+                 JOIN path_attributes a ON a.path_id = p.id
+                 WHERE p.path = '/TEI/text/div' AND a.name = 'type'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert!(path.ends_with("a.xml"));
+        assert_eq!(size, 39);
+    }
+}
@@ -0,0 +1,274 @@
+use super::{extract_parsed, parse_document, ExtractFailure, ExtractTemplate, Record};
+use crate::processor::ProcessingResult;
+use crate::struct_processor::document_structure;
+use anyhow::{bail, Context, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, warn};
+
+/// Which template a set of records was extracted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TemplateKey {
+    /// Template for one skeleton hash
+    Group(u64),
+    /// Fallback for files without a group template
+    Default,
+}
+
+impl fmt::Display for TemplateKey {
+    /// File stem of the template and output files
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateKey::Group(hash) => write!(f, "{}", hash),
+            TemplateKey::Default => f.write_str("default"),
+        }
+    }
+}
+
+/// Extraction templates per structure group, e.g. a directory with
+/// `<hash>.toml` files and an optional `default.toml` fallback
+#[derive(Debug, Default)]
+pub struct TemplateSet {
+    pub templates: BTreeMap<TemplateKey, ExtractTemplate>,
+}
+
+impl TemplateSet {
+    /// Load every `<hash>.toml|yaml|yml` and `default.*` template in `dir`
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let mut set = Self::default();
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let is_template = path
+                .extension()
+                .is_some_and(|e| e == "toml" || e == "yaml" || e == "yml");
+            if !is_template {
+                continue;
+            }
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let key = match stem {
+                "default" => TemplateKey::Default,
+                _ => match stem.parse() {
+                    Ok(hash) => TemplateKey::Group(hash),
+                    Err(_) => {
+                        warn!(
+                            "Skipping template not named after a group hash: {}",
+                            path.display()
+                        );
+                        continue;
+                    }
+                },
+            };
+            if set.templates.contains_key(&key) {
+                bail!("More than one template for {} in {}", key, dir.display());
+            }
+            set.templates
+                .insert(key, ExtractTemplate::from_file(&path)?);
+        }
+
+        if set.templates.is_empty() {
+            bail!("No templates found in {}", dir.display());
+        }
+        Ok(set)
+    }
+
+    /// Starter templates for every group of a processing result
+    pub fn from_result(result: &ProcessingResult) -> Result<Self> {
+        let templates = result
+            .groups
+            .iter()
+            .map(|g| {
+                let template = ExtractTemplate::from_skeleton(&g.skeleton).with_context(|| {
+                    format!(
+                        "Failed to generate a template for group {}",
+                        g.skeleton.hash
+                    )
+                })?;
+                Ok((TemplateKey::Group(g.skeleton.hash), template))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { templates })
+    }
+
+    /// Template for a skeleton hash, falling back to the default template
+    pub fn template_for(&self, hash: u64) -> Option<(TemplateKey, &ExtractTemplate)> {
+        [TemplateKey::Group(hash), TemplateKey::Default]
+            .into_iter()
+            .find_map(|key| self.templates.get(&key).map(|t| (key, t)))
+    }
+
+    /// Write each template to `dir/<key>.toml`. Returns the written paths.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let mut written = Vec::new();
+        for (key, template) in &self.templates {
+            let path = dir.join(format!("{}.toml", key));
+            fs::write(&path, template.to_toml())
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// Records extracted with one template
+#[derive(Debug)]
+pub struct GroupRecords {
+    pub key: TemplateKey,
+    pub records: Vec<Record>,
+}
+
+/// Records of a per-group run, in template key order
+#[derive(Debug, Default)]
+pub struct GroupedExtractResult {
+    pub groups: Vec<GroupRecords>,
+    /// Files whose group has no template and no default was given
+    pub unmatched: Vec<String>,
+    pub failures: Vec<ExtractFailure>,
+}
+
+enum FileOutcome {
+    Extracted(TemplateKey, Vec<Record>),
+    Unmatched,
+}
+
+/// Extract every file in parallel with the template of its skeleton group
+pub fn extract_files_by_group(
+    file_paths: &[String],
+    templates: &TemplateSet,
+    progress_bar: Option<ProgressBar>,
+) -> GroupedExtractResult {
+    let per_file: Vec<Result<FileOutcome>> = file_paths
+        .par_iter()
+        .map(|file_path| {
+            let result = fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read file: {}", file_path))
+                .and_then(|xml| {
+                    let doc = parse_document(&xml)?;
+                    let hash = document_structure(&doc).to_skeleton().hash;
+                    match templates.template_for(hash) {
+                        Some((key, template)) => Ok(FileOutcome::Extracted(
+                            key,
+                            extract_parsed(&doc, file_path, template)?,
+                        )),
+                        None => Ok(FileOutcome::Unmatched),
+                    }
+                });
+            if let Some(ref pb) = progress_bar {
+                pb.inc(1);
+            }
+            result
+        })
+        .collect();
+
+    if let Some(ref pb) = progress_bar {
+        pb.finish_with_message("Extraction complete");
+    }
+
+    let mut by_key: BTreeMap<TemplateKey, Vec<Record>> = BTreeMap::new();
+    let mut result = GroupedExtractResult::default();
+    for (file_path, outcome) in file_paths.iter().zip(per_file) {
+        match outcome {
+            Ok(FileOutcome::Extracted(key, records)) => {
+                by_key.entry(key).or_default().extend(records)
+            }
+            Ok(FileOutcome::Unmatched) => result.unmatched.push(file_path.clone()),
+            Err(e) => {
+                error!("Failed to extract {}: {:#}", file_path, e);
+                result.failures.push(ExtractFailure {
+                    file: file_path.clone(),
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+    }
+    result.groups = by_key
+        .into_iter()
+        .map(|(key, records)| GroupRecords { key, records })
+        .collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::process_xml_files;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_generate_and_extract_by_group() {
+        let temp_dir = TempDir::new().unwrap();
+        let corpus = temp_dir.path();
+        let letter = corpus.join("letter.xml");
+        let poem = corpus.join("poem.xml");
+        let other = corpus.join("other.xml");
+        fs::write(
+            &letter,
+            "<TEI><opener>Dear</opener><closer>Yours</closer></TEI>",
+        )
+        .unwrap();
+        fs::write(
+            &poem,
+            r#"<TEI><lg type="sonnet"><l>a</l><l>b</l></lg></TEI>"#,
+        )
+        .unwrap();
+        let files: Vec<String> = [&letter, &poem]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        let result = process_xml_files(files.clone(), None).unwrap();
+        let templates_dir = corpus.join("templates");
+        let written = TemplateSet::from_result(&result)
+            .unwrap()
+            .write_to_dir(&templates_dir)
+            .unwrap();
+        assert_eq!(written.len(), 2);
+
+        fs::write(&other, "<TEI><front/></TEI>").unwrap();
+        let mut all_files = files.clone();
+        all_files.push(other.to_string_lossy().to_string());
+
+        let templates = TemplateSet::from_dir(&templates_dir).unwrap();
+        let extracted = extract_files_by_group(&all_files, &templates, None);
+
+        assert_eq!(extracted.groups.len(), 2);
+        assert_eq!(extracted.unmatched, vec![all_files[2].clone()]);
+        let poem_hash = result
+            .groups
+            .iter()
+            .find(|g| g.files == [files[1].clone()])
+            .unwrap()
+            .skeleton
+            .hash;
+        let poems = extracted
+            .groups
+            .iter()
+            .find(|g| g.key == TemplateKey::Group(poem_hash))
+            .unwrap();
+        assert_eq!(
+            poems.records,
+            vec![vec![
+                Some(files[1].clone()),
+                Some("sonnet".to_string()),
+                Some("a; b".to_string())
+            ]]
+        );
+
+        // A default template picks up files without a group template
+        fs::write(
+            templates_dir.join("default.yaml"),
+            "columns:\n  root: name(/*)\n",
+        )
+        .unwrap();
+        let templates = TemplateSet::from_dir(&templates_dir).unwrap();
+        let extracted = extract_files_by_group(&all_files, &templates, None);
+        assert!(extracted.unmatched.is_empty());
+        assert_eq!(extracted.groups.last().unwrap().key, TemplateKey::Default);
+    }
+}
@@ -0,0 +1,249 @@
+//! Template-based data extraction (roadmap Task 3).
+
+pub mod groups;
+pub mod template;
+pub mod writer;
+
+pub use groups::{extract_files_by_group, GroupedExtractResult, TemplateKey, TemplateSet};
+pub use template::{Column, ExtractTemplate};
+pub use writer::write_records;
+
+use crate::query::{Evaluator, Value, XNode};
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use roxmltree::{Document, ParsingOptions};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tracing::error;
+
+/// Output format for extracted records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractFormat {
+    /// Header row plus one row per record
+    #[default]
+    Csv,
+    /// One JSON array of record objects
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl ExtractFormat {
+    /// File extension for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExtractFormat::Csv => "csv",
+            ExtractFormat::Json => "json",
+            ExtractFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// Format matching a file extension, if any
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ExtractFormat::Csv),
+            "json" => Some(ExtractFormat::Json),
+            "ndjson" | "jsonl" => Some(ExtractFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// One extracted record; values line up with `ExtractTemplate::header()`.
+/// `None` marks a column whose expression selected nothing.
+pub type Record = Vec<Option<String>>;
+
+/// A file that could not be extracted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractFailure {
+    pub file: String,
+    pub message: String,
+}
+
+/// Records of all files, in input file order
+#[derive(Debug, Default)]
+pub struct ExtractResult {
+    pub records: Vec<Record>,
+    pub failures: Vec<ExtractFailure>,
+}
+
+fn parse_document(xml: &str) -> Result<Document<'_>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    Document::parse_with_options(xml, options).context("Failed to parse XML document")
+}
+
+/// Extract the records of one document
+pub fn extract_document(xml: &str, file: &str, template: &ExtractTemplate) -> Result<Vec<Record>> {
+    extract_parsed(&parse_document(xml)?, file, template)
+}
+
+fn extract_parsed(doc: &Document, file: &str, template: &ExtractTemplate) -> Result<Vec<Record>> {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_variable("file", Value::Str(file.to_string()));
+
+    let root = XNode::Node(doc.root());
+    let record_nodes = match &template.records {
+        Some(records) => match evaluator.evaluate(records, root)? {
+            Value::Nodes(nodes) => nodes,
+            _ => anyhow::bail!("Records expression must select nodes: {}", records),
+        },
+        None => vec![root],
+    };
+
+    let mut records = Vec::with_capacity(record_nodes.len());
+    for node in record_nodes {
+        let mut record = Vec::with_capacity(template.columns.len() + 1);
+        if template.include_file {
+            record.push(Some(file.to_string()));
+        }
+        for column in &template.columns {
+            let value = evaluator
+                .evaluate(&column.path, node)
+                .with_context(|| format!("Failed to evaluate column {}", column.name))?;
+            record.push(column_value(value, template));
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn column_value(value: Value, template: &ExtractTemplate) -> Option<String> {
+    let text = match value {
+        Value::Nodes(nodes) if nodes.is_empty() => return None,
+        Value::Nodes(nodes) => match &template.separator {
+            Some(separator) => nodes
+                .iter()
+                .map(|n| clean(n.string_value(), template))
+                .collect::<Vec<_>>()
+                .join(separator),
+            None => nodes[0].string_value(),
+        },
+        scalar => scalar.to_string_value(),
+    };
+    Some(clean(text, template))
+}
+
+fn clean(text: String, template: &ExtractTemplate) -> String {
+    if template.normalize_space {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        text
+    }
+}
+
+/// Extract records from every file in parallel
+pub fn extract_files(
+    file_paths: &[String],
+    template: &ExtractTemplate,
+    progress_bar: Option<ProgressBar>,
+) -> ExtractResult {
+    let per_file: Vec<Result<Vec<Record>>> = file_paths
+        .par_iter()
+        .map(|file_path| {
+            let result = fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read file: {}", file_path))
+                .and_then(|xml| extract_document(&xml, file_path, template));
+            if let Some(ref pb) = progress_bar {
+                pb.inc(1);
+            }
+            result
+        })
+        .collect();
+
+    if let Some(ref pb) = progress_bar {
+        pb.finish_with_message("Extraction complete");
+    }
+
+    let mut result = ExtractResult::default();
+    for (file_path, outcome) in file_paths.iter().zip(per_file) {
+        match outcome {
+            Ok(records) => result.records.extend(records),
+            Err(e) => {
+                error!("Failed to extract {}: {:#}", file_path, e);
+                result.failures.push(ExtractFailure {
+                    file: file_path.clone(),
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<TEI>
+  <teiHeader><title type="main">Letters</title></teiHeader>
+  <text>
+    <div type="chapter" n="1"><head>  First
+      chapter </head><p>a</p><p>b</p></div>
+    <div type="chapter" n="2"><p>c</p></div>
+  </text>
+</TEI>"#;
+
+    #[test]
+    fn test_extract_records() {
+        let template = ExtractTemplate::from_toml(
+            r#"
+            records = "//div[@type='chapter']"
+            separator = "|"
+
+            [columns]
+            title = "/TEI/teiHeader//title[@type='main']"
+            n = "@n"
+            head = "head"
+            paragraphs = "p"
+            count = "count(p)"
+            "#,
+        )
+        .unwrap();
+
+        let records = extract_document(XML, "a.xml", &template).unwrap();
+
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            records,
+            vec![
+                vec![
+                    some("a.xml"),
+                    some("Letters"),
+                    some("1"),
+                    some("First chapter"),
+                    some("a|b"),
+                    some("2")
+                ],
+                vec![
+                    some("a.xml"),
+                    some("Letters"),
+                    some("2"),
+                    None,
+                    some("c"),
+                    some("1")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_record_per_file() {
+        let template = ExtractTemplate::from_toml(
+            "include_file = false\n[columns]\ntitle = \"//title\"\nsrc = \"$file\"",
+        )
+        .unwrap();
+
+        let records = extract_document(XML, "a.xml", &template).unwrap();
+
+        assert_eq!(
+            records,
+            vec![vec![Some("Letters".to_string()), Some("a.xml".to_string())]]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use crate::processor::SkeletonSignature;
+use crate::query::XPath;
+use anyhow::{bail, Context, Result};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// An extraction template: which nodes become records and which XPath
+/// expressions fill their columns.
+///
+/// ```toml
+/// # One record per chapter; omit for one record per file
+/// records = "//div[@type='chapter']"
+///
+/// [columns]
+/// title = "/TEI/teiHeader//title[@type='main']"
+/// n = "@n"
+/// head = "head"
+/// ```
+///
+/// Column expressions are evaluated with the record node as context, so
+/// relative paths select inside the record and absolute paths select from
+/// the document root. `$file` is bound to the file path. Columns keep the
+/// order of the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractTemplate {
+    /// Expression selecting record nodes; `None` means one record per file
+    pub records: Option<XPath>,
+
+    /// Output columns in template order
+    pub columns: Vec<Column>,
+
+    /// Prepend a `file` column with the source path
+    pub include_file: bool,
+
+    /// Join every selected node with this separator instead of taking the
+    /// first one
+    pub separator: Option<String>,
+
+    /// Collapse whitespace in extracted values
+    pub normalize_space: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub path: XPath,
+}
+
+/// Template file as written by users, before expressions are parsed
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTemplate {
+    records: Option<String>,
+    #[serde(deserialize_with = "ordered_columns")]
+    columns: Vec<(String, String)>,
+    #[serde(default = "default_true")]
+    include_file: bool,
+    separator: Option<String>,
+    #[serde(default = "default_true")]
+    normalize_space: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Deserialize a map into its entries, keeping the document order
+fn ordered_columns<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct ColumnsVisitor;
+
+    impl<'de> Visitor<'de> for ColumnsVisitor {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table of column names to XPath expressions")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut columns = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                columns.push(entry);
+            }
+            Ok(columns)
+        }
+    }
+
+    deserializer.deserialize_map(ColumnsVisitor)
+}
+
+impl ExtractTemplate {
+    /// Load a TOML (`.toml`) or YAML (`.yaml`, `.yml`) template
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template: {}", path.display()))?;
+        let template = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            Some("toml") => Self::from_toml(&content),
+            _ => bail!(
+                "Unknown template format (expected .toml, .yaml or .yml): {}",
+                path.display()
+            ),
+        };
+        template.with_context(|| format!("Invalid template: {}", path.display()))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Self::from_raw(toml::from_str(content)?)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        Self::from_raw(serde_yaml::from_str(content)?)
+    }
+
+    fn from_raw(raw: RawTemplate) -> Result<Self> {
+        if raw.columns.is_empty() {
+            bail!("Template has no columns");
+        }
+        let records = raw
+            .records
+            .map(|expr| {
+                XPath::parse(&expr).with_context(|| format!("Invalid records expression: {}", expr))
+            })
+            .transpose()?;
+
+        let mut columns: Vec<Column> = Vec::with_capacity(raw.columns.len());
+        for (name, expr) in raw.columns {
+            if columns.iter().any(|c| c.name == name) || (raw.include_file && name == "file") {
+                bail!("Duplicate column: {}", name);
+            }
+            let path = XPath::parse(&expr)
+                .with_context(|| format!("Invalid expression for column {}: {}", name, expr))?;
+            columns.push(Column { name, path });
+        }
+
+        Ok(Self {
+            records,
+            columns,
+            include_file: raw.include_file,
+            separator: raw.separator,
+            normalize_space: raw.normalize_space,
+        })
+    }
+
+    /// Starter template for files of one skeleton: one record per file, one
+    /// column per leaf element and per attribute key, named after their
+    /// path below the root (`teiHeader.fileDesc.titleStmt.title`,
+    /// `text.body.div@type`). Repeated elements are joined with `"; "`.
+    /// Names that collide (`a/b` and `a.b`) get a numeric suffix.
+    pub fn from_skeleton(skeleton: &SkeletonSignature) -> Result<Self> {
+        let mut columns = Vec::new();
+        for path in skeleton.paths() {
+            let relative = path
+                .path
+                .splitn(3, '/')
+                .nth(2)
+                .map(|rest| rest.replace('/', "."))
+                .unwrap_or_default();
+
+            if path.is_leaf {
+                let name = match relative.as_str() {
+                    "" => skeleton.root.clone(),
+                    // Reserved for the source path column
+                    "file" => "file_".to_string(),
+                    _ => relative.clone(),
+                };
+                columns.push((name, path.path.clone()));
+            }
+            for attr in &path.attributes {
+                columns.push((
+                    format!("{}@{}", relative, attr),
+                    format!("{}/@{}", path.path, attr),
+                ));
+            }
+        }
+
+        let mut used: HashSet<String> = HashSet::from(["file".to_string()]);
+        let columns = columns
+            .into_iter()
+            .map(|(name, expr)| {
+                let name = (1..)
+                    .map(|i| match i {
+                        1 => name.clone(),
+                        _ => format!("{}_{}", name, i),
+                    })
+                    .find(|candidate| !used.contains(candidate))
+                    .expect("unbounded suffixes");
+                used.insert(name.clone());
+                let path = XPath::parse(&expr)
+                    .with_context(|| format!("Invalid XPath for skeleton path {}", expr))?;
+                Ok(Column { name, path })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            records: None,
+            columns,
+            include_file: true,
+            separator: Some("; ".to_string()),
+            normalize_space: true,
+        })
+    }
+
+    /// Serialize as a TOML template that `from_toml` reads back
+    pub fn to_toml(&self) -> String {
+        let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+        let mut out = String::new();
+        if let Some(records) = &self.records {
+            out.push_str(&format!("records = {}\n", quote(records.as_str())));
+        }
+        if !self.include_file {
+            out.push_str("include_file = false\n");
+        }
+        if let Some(separator) = &self.separator {
+            out.push_str(&format!("separator = {}\n", quote(separator)));
+        }
+        if !self.normalize_space {
+            out.push_str("normalize_space = false\n");
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("[columns]\n");
+        for column in &self.columns {
+            out.push_str(&format!(
+                "{} = {}\n",
+                quote(&column.name),
+                quote(column.path.as_str())
+            ));
+        }
+        out
+    }
+
+    /// Output column names, including `file` when enabled
+    pub fn header(&self) -> Vec<&str> {
+        let file = self.include_file.then_some("file");
+        file.into_iter()
+            .chain(self.columns.iter().map(|c| c.name.as_str()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_and_yaml_keep_column_order() {
+        let toml = r#"
+            records = "//div"
+            separator = "; "
+
+            [columns]
+            zeta = "@n"
+            alpha = "head"
+        "#;
+        let yaml = "records: //div\nseparator: '; '\ncolumns:\n  zeta: '@n'\n  alpha: head\n";
+
+        for template in [
+            ExtractTemplate::from_toml(toml).unwrap(),
+            ExtractTemplate::from_yaml(yaml).unwrap(),
+        ] {
+            assert_eq!(template.header(), vec!["file", "zeta", "alpha"]);
+            assert_eq!(template.records.as_ref().unwrap().as_str(), "//div");
+            assert_eq!(template.separator.as_deref(), Some("; "));
+            assert!(template.normalize_space);
+        }
+    }
+
+    #[test]
+    fn test_from_skeleton_roundtrip() {
+        let structure = crate::struct_processor::parse_xml_structure(
+            r#"<TEI id="t"><teiHeader><title/></teiHeader><text><div type="a" n="1"><p/></div></text></TEI>"#,
+        )
+        .unwrap();
+        let template = ExtractTemplate::from_skeleton(&structure.to_skeleton()).unwrap();
+
+        assert_eq!(
+            template.header(),
+            vec![
+                "file",
+                "@id",
+                "teiHeader.title",
+                "text.div@n",
+                "text.div@type",
+                "text.div.p"
+            ]
+        );
+        assert_eq!(template.columns[2].path.as_str(), "/TEI/text/div/@n");
+        assert_eq!(
+            ExtractTemplate::from_toml(&template.to_toml()).unwrap(),
+            template
+        );
+    }
+
+    #[test]
+    fn test_from_skeleton_unusual_names() {
+        let structure = crate::struct_processor::parse_xml_structure(
+            "<TEI><a\u{B7}b/><a><b/></a><a.b/><file/></TEI>",
+        )
+        .unwrap();
+        let template = ExtractTemplate::from_skeleton(&structure.to_skeleton()).unwrap();
+        let mut header = template.header();
+        header.sort_unstable();
+        assert_eq!(header, vec!["a.b", "a.b_2", "a\u{B7}b", "file", "file_"]);
+        assert_eq!(
+            ExtractTemplate::from_toml(&template.to_toml()).unwrap(),
+            template
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let bad_xpath = "[columns]\nhead = \"div[\"";
+        let err = ExtractTemplate::from_toml(bad_xpath).unwrap_err();
+        assert!(format!("{:#}", err).contains("column head"));
+
+        assert!(ExtractTemplate::from_toml("records = \"//div\"\n[columns]\n").is_err());
+        assert!(ExtractTemplate::from_toml("[columns]\nfile = \"@n\"").is_err());
+        assert!(ExtractTemplate::from_toml("rows = \"//div\"\n[columns]\nn = \"@n\"").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use super::{ExtractFormat, Record};
+use anyhow::Result;
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use std::io::Write;
+
+/// A record serialized as a JSON object with columns in header order
+struct RecordObject<'a> {
+    header: &'a [&'a str],
+    values: &'a Record,
+}
+
+impl Serialize for RecordObject<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.header.len()))?;
+        for (name, value) in self.header.iter().zip(self.values) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Write records in `format`. Empty columns are empty CSV fields and JSON nulls.
+pub fn write_records<W: Write>(
+    writer: W,
+    header: &[&str],
+    records: &[Record],
+    format: ExtractFormat,
+) -> Result<()> {
+    match format {
+        ExtractFormat::Csv => {
+            let mut csv = ::csv::Writer::from_writer(writer);
+            csv.write_record(header)?;
+            for record in records {
+                csv.write_record(record.iter().map(|v| v.as_deref().unwrap_or_default()))?;
+            }
+            csv.flush()?;
+        }
+        ExtractFormat::Json => {
+            let objects: Vec<RecordObject> = records
+                .iter()
+                .map(|values| RecordObject { header, values })
+                .collect();
+            let mut writer = writer;
+            serde_json::to_writer_pretty(&mut writer, &objects)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        ExtractFormat::Ndjson => {
+            let mut writer = writer;
+            for values in records {
+                serde_json::to_writer(&mut writer, &RecordObject { header, values })?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_formats() {
+        let header = ["zeta", "alpha"];
+        let records = vec![vec![Some("1".to_string()), None]];
+        let write = |format| {
+            let mut out = Vec::new();
+            write_records(&mut out, &header, &records, format).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(write(ExtractFormat::Csv), "zeta,alpha\n1,\n");
+        assert_eq!(
+            write(ExtractFormat::Ndjson),
+            "{\"zeta\":\"1\",\"alpha\":null}\n"
+        );
+        assert!(write(ExtractFormat::Json).starts_with("[\n  {\n    \"zeta\": \"1\""));
+    }
+}
@@ -1,5 +1,9 @@
 pub mod cli;
+pub mod commands;
+pub mod export;
+pub mod extract;
 pub mod processor;
+pub mod query;
 pub mod utils;
 pub mod validation;
 pub mod wasm;
@@ -8,7 +12,10 @@ pub mod xsconfig;
 pub use cli::Cli;
 pub use processor::{struct_processor, xml_struct};
 pub use validation::{report, validate};
-pub use xsconfig::{LoggingConfig, OutputConfig, ProcessingConfig, XsConfig};
+pub use xsconfig::{
+    ExamplePolicy, ExamplesConfig, LoggingConfig, OutputConfig, OutputFormat, ProcessingConfig,
+    XsConfig,
+};
 
 /// One-call entry point.
 pub fn validate(path: impl AsRef<std::path::Path>) -> anyhow::Result<report::Report> {
@@ -16,6 +23,17 @@ pub fn validate(path: impl AsRef<std::path::Path>) -> anyhow::Result<report::Rep
     validate::run(&xml)
 }
 
+/// One-call entry point for a directory: every XML file below it, in parallel.
+pub fn validate_dir(dir: impl AsRef<std::path::Path>) -> anyhow::Result<validation::CorpusReport> {
+    let extensions = XsConfig::default().processing.file_extensions;
+    validation::validate_corpus(
+        dir.as_ref(),
+        &extensions,
+        0,
+        &validation::Registry::builtin(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
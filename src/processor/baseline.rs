@@ -0,0 +1,187 @@
+use crate::processor::{ProcessingResult, SkeletonSignature};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Approved skeletons; files whose skeleton is not listed fail the check
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub skeletons: Vec<BaselineEntry>,
+}
+
+/// One approved skeleton, identified by its hash, its skeleton JSON, or both
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub hash: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub root: Option<String>,
+
+    /// Skeleton JSON as found in results files; its hash is approved too
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub skeleton: Option<Value>,
+
+    /// Free-form reviewer note, kept across updates
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub note: Option<String>,
+}
+
+/// Files whose skeleton isn't approved, per offending skeleton
+#[derive(Debug, Serialize)]
+pub struct BaselineViolation {
+    pub hash: u64,
+    pub root: String,
+    pub files: Vec<String>,
+}
+
+impl Baseline {
+    /// Load a baseline from a JSON file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read baseline {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse baseline {}", path.display()))
+    }
+
+    /// Write the baseline as pretty JSON
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write baseline {}", path.display()))
+    }
+
+    /// Build a baseline approving every skeleton of `result`. Notes from
+    /// `previous` are kept for skeletons that are still present.
+    pub fn from_result(result: &ProcessingResult, previous: Option<&Baseline>) -> Self {
+        let notes: HashMap<u64, &String> = previous
+            .map(|b| {
+                b.skeletons
+                    .iter()
+                    .filter_map(|e| Some((e.approved_hash()?, e.note.as_ref()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut skeletons: Vec<BaselineEntry> = result
+            .groups
+            .iter()
+            .map(|g| BaselineEntry {
+                hash: Some(g.skeleton.hash),
+                root: Some(g.skeleton.root.clone()),
+                skeleton: Some(g.skeleton.skeleton.clone()),
+                note: notes.get(&g.skeleton.hash).map(|n| n.to_string()),
+            })
+            .collect();
+        skeletons.sort_by_key(|e| e.hash);
+
+        Self { skeletons }
+    }
+
+    /// Every approved skeleton hash
+    pub fn approved_hashes(&self) -> HashSet<u64> {
+        self.skeletons
+            .iter()
+            .flat_map(|e| {
+                e.hash
+                    .into_iter()
+                    .chain(e.skeleton.as_ref().map(SkeletonSignature::hash_skeleton))
+            })
+            .collect()
+    }
+
+    /// Groups of `result` whose skeleton isn't approved
+    pub fn check(&self, result: &ProcessingResult) -> Vec<BaselineViolation> {
+        let approved = self.approved_hashes();
+        result
+            .groups
+            .iter()
+            .filter(|g| !approved.contains(&g.skeleton.hash))
+            .map(|g| {
+                let mut files = g.files.clone();
+                files.sort();
+                BaselineViolation {
+                    hash: g.skeleton.hash,
+                    root: g.skeleton.root.clone(),
+                    files,
+                }
+            })
+            .collect()
+    }
+}
+
+impl BaselineEntry {
+    /// Hash this entry approves, preferring the explicit one
+    fn approved_hash(&self) -> Option<u64> {
+        self.hash
+            .or_else(|| self.skeleton.as_ref().map(SkeletonSignature::hash_skeleton))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::StructureGroup;
+    use crate::struct_processor::parse_xml_structure;
+
+    fn result(docs: &[(&str, &str)]) -> ProcessingResult {
+        let mut groups: Vec<StructureGroup> = Vec::new();
+        for (xml, file) in docs {
+            let skeleton = parse_xml_structure(xml).unwrap().to_skeleton();
+            match groups.iter_mut().find(|g| g.skeleton.hash == skeleton.hash) {
+                Some(g) => g.add_file(file.to_string(), xml.len() as u64),
+                None => {
+                    let mut g = StructureGroup::new(skeleton);
+                    g.add_file(file.to_string(), xml.len() as u64);
+                    groups.push(g);
+                }
+            }
+        }
+        ProcessingResult {
+            total_files: docs.len(),
+            unique_structures: groups.len(),
+            groups,
+            root: None,
+        }
+    }
+
+    #[test]
+    fn test_check_reports_unapproved_files() {
+        let approved = result(&[("<TEI><text/></TEI>", "a.xml")]);
+        let baseline = Baseline::from_result(&approved, None);
+
+        let current = result(&[
+            ("<TEI><text/></TEI>", "b.xml"),
+            ("<TEI><text/><back/></TEI>", "c.xml"),
+        ]);
+        let violations = baseline.check(&current);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].files, vec!["c.xml"]);
+    }
+
+    #[test]
+    fn test_skeleton_only_entries_are_approved() {
+        let current = result(&[("<TEI><text/></TEI>", "a.xml")]);
+        let baseline: Baseline =
+            serde_json::from_str(r#"{"skeletons": [{"skeleton": {"text": {}}}]}"#).unwrap();
+
+        assert!(baseline.check(&current).is_empty());
+    }
+
+    #[test]
+    fn test_update_keeps_notes() {
+        let current = result(&[("<TEI><text/></TEI>", "a.xml")]);
+        let mut previous = Baseline::from_result(&current, None);
+        previous.skeletons[0].note = Some("approved by editors".to_string());
+
+        let updated = Baseline::from_result(&current, Some(&previous));
+
+        assert_eq!(
+            updated.skeletons[0].note.as_deref(),
+            Some("approved by editors")
+        );
+    }
+}
@@ -1,8 +1,17 @@
+pub mod baseline;
+pub mod result_diff;
 pub mod struct_processor;
+pub mod tree;
 pub mod xml_struct;
 
+pub use baseline::{Baseline, BaselineViolation};
+pub use result_diff::{diff_results, ResultDiff};
 pub use struct_processor::{
-    create_progress_bar, print_summary, process_xml_files, write_result_to_file,
+    create_progress_bar, load_result, print_summary, process_xml_files, process_xml_files_with,
+    write_result_to_file,
 };
+pub use tree::{render_skeleton, render_structure, TreeOptions};
 
-pub use xml_struct::{ProcessingResult, StructureGroup, XmlStructure};
+pub use xml_struct::{
+    GroupExample, ProcessingResult, SkeletonPath, SkeletonSignature, StructureGroup, XmlStructure,
+};
@@ -0,0 +1,254 @@
+use crate::processor::{ProcessingResult, StructureGroup};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Structural drift between two processing runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResultDiff {
+    /// Files present in both runs whose skeleton changed
+    pub moved_files: Vec<MovedFile>,
+
+    /// Files only present in the new run
+    pub added_files: Vec<FileGroup>,
+
+    /// Files only present in the old run
+    pub removed_files: Vec<FileGroup>,
+
+    /// Skeletons only present in the new run
+    pub new_groups: Vec<GroupSummary>,
+
+    /// Skeletons only present in the old run
+    pub vanished_groups: Vec<GroupSummary>,
+
+    /// Skeletons present in both runs with a different file count
+    pub count_changes: Vec<CountChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedFile {
+    pub path: String,
+    pub old_group: u64,
+    pub new_group: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileGroup {
+    pub path: String,
+    pub group: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSummary {
+    pub hash: u64,
+    pub root: String,
+    pub count: usize,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountChange {
+    pub hash: u64,
+    pub root: String,
+    pub old_count: usize,
+    pub new_count: usize,
+}
+
+impl ResultDiff {
+    /// True when both runs have the same files in the same groups
+    pub fn is_empty(&self) -> bool {
+        self.moved_files.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.new_groups.is_empty()
+            && self.vanished_groups.is_empty()
+            && self.count_changes.is_empty()
+    }
+}
+
+impl GroupSummary {
+    fn from_group(group: &StructureGroup) -> Self {
+        Self {
+            hash: group.skeleton.hash,
+            root: group.skeleton.root.clone(),
+            count: group.count,
+            signature: group.signature_string(),
+        }
+    }
+}
+
+/// Compare two runs. Files are matched by their path below the analyzed
+/// root (the full path for results without one), groups by skeleton hash.
+/// All lists are sorted (files by path, groups by hash) for stable output.
+pub fn diff_results(old: &ProcessingResult, new: &ProcessingResult) -> ResultDiff {
+    let old_groups: HashMap<u64, &StructureGroup> =
+        old.groups.iter().map(|g| (g.skeleton.hash, g)).collect();
+    let new_groups: HashMap<u64, &StructureGroup> =
+        new.groups.iter().map(|g| (g.skeleton.hash, g)).collect();
+
+    let mut diff = ResultDiff::default();
+
+    for (hash, group) in &new_groups {
+        match old_groups.get(hash) {
+            None => diff.new_groups.push(GroupSummary::from_group(group)),
+            Some(old_group) if old_group.count != group.count => {
+                diff.count_changes.push(CountChange {
+                    hash: *hash,
+                    root: group.skeleton.root.clone(),
+                    old_count: old_group.count,
+                    new_count: group.count,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (hash, group) in &old_groups {
+        if !new_groups.contains_key(hash) {
+            diff.vanished_groups.push(GroupSummary::from_group(group));
+        }
+    }
+
+    let old_files = file_groups(old);
+    let new_files = file_groups(new);
+
+    for (path, new_group) in &new_files {
+        match old_files.get(path) {
+            None => diff.added_files.push(FileGroup {
+                path: path.to_string(),
+                group: *new_group,
+            }),
+            Some(old_group) if old_group != new_group => diff.moved_files.push(MovedFile {
+                path: path.to_string(),
+                old_group: *old_group,
+                new_group: *new_group,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (path, old_group) in &old_files {
+        if !new_files.contains_key(path) {
+            diff.removed_files.push(FileGroup {
+                path: path.to_string(),
+                group: *old_group,
+            });
+        }
+    }
+
+    diff.new_groups.sort_by_key(|g| g.hash);
+    diff.vanished_groups.sort_by_key(|g| g.hash);
+    diff.count_changes.sort_by_key(|c| c.hash);
+    diff
+}
+
+/// Map of file path, relative to the root of the run, to group hash
+/// (sorted by path)
+fn file_groups(result: &ProcessingResult) -> BTreeMap<String, u64> {
+    let root = result.root.as_deref().map(Path::new);
+    result
+        .groups
+        .iter()
+        .flat_map(|g| {
+            g.files.iter().map(move |f| {
+                let relative = root.and_then(|root| Path::new(f).strip_prefix(root).ok());
+                let path = relative.map_or_else(|| f.clone(), |p| p.to_string_lossy().to_string());
+                (path, g.skeleton.hash)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::parse_xml_structure;
+
+    fn group(xml: &str, files: &[&str]) -> StructureGroup {
+        let mut group = StructureGroup::new(parse_xml_structure(xml).unwrap().to_skeleton());
+        for f in files {
+            group.add_file(f.to_string(), xml.len() as u64);
+        }
+        group
+    }
+
+    fn result(groups: Vec<StructureGroup>) -> ProcessingResult {
+        ProcessingResult {
+            total_files: groups.iter().map(|g| g.count).sum(),
+            unique_structures: groups.len(),
+            groups,
+            root: None,
+        }
+    }
+
+    #[test]
+    fn test_identical_runs() {
+        let old = result(vec![group("<a/>", &["1.xml", "2.xml"])]);
+        let new = result(vec![group("<a/>", &["2.xml", "1.xml"])]);
+
+        assert!(diff_results(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_drift() {
+        let old = result(vec![
+            group("<a><x/></a>", &["1.xml", "2.xml", "3.xml"]),
+            group("<b><y/></b>", &["4.xml"]),
+        ]);
+        let new = result(vec![
+            group("<a><x/></a>", &["1.xml", "2.xml"]),
+            group("<c><z/></c>", &["3.xml", "5.xml"]),
+        ]);
+        let a = old.groups[0].skeleton.hash;
+        let c = new.groups[1].skeleton.hash;
+
+        let diff = diff_results(&old, &new);
+
+        assert_eq!(
+            diff.moved_files,
+            vec![MovedFile {
+                path: "3.xml".to_string(),
+                old_group: a,
+                new_group: c,
+            }]
+        );
+        assert_eq!(diff.added_files[0].path, "5.xml");
+        assert_eq!(diff.removed_files[0].path, "4.xml");
+        assert_eq!(diff.new_groups[0].root, "c");
+        assert_eq!(diff.vanished_groups[0].root, "b");
+        assert_eq!(diff.count_changes[0].old_count, 3);
+        assert_eq!(diff.count_changes[0].new_count, 2);
+    }
+
+    #[test]
+    fn test_runs_from_different_roots() {
+        let mut old = result(vec![
+            group("<a><x/></a>", &["corpus/1.xml", "corpus/sub/2.xml"]),
+            group("<a><y/></a>", &["corpus/3.xml"]),
+        ]);
+        old.root = Some("corpus".to_string());
+        let mut new = result(vec![
+            group("<a><x/></a>", &["/data/v2/corpus/1.xml"]),
+            group(
+                "<a><y/></a>",
+                &["/data/v2/corpus/3.xml", "/data/v2/corpus/sub/2.xml"],
+            ),
+        ]);
+        new.root = Some("/data/v2/corpus/".to_string());
+
+        let diff = diff_results(&old, &new);
+
+        assert_eq!(
+            diff.moved_files,
+            vec![MovedFile {
+                path: "sub/2.xml".to_string(),
+                old_group: old.groups[0].skeleton.hash,
+                new_group: new.groups[1].skeleton.hash,
+            }]
+        );
+        assert!(diff.added_files.is_empty());
+        assert!(diff.removed_files.is_empty());
+
+        // Without a root, paths are compared as they are
+        new.root = None;
+        assert_eq!(diff_results(&old, &new).added_files.len(), 3);
+    }
+}
@@ -1,10 +1,13 @@
-use crate::processor::{ProcessingResult, StructureGroup, XmlStructure};
+use crate::processor::{GroupExample, ProcessingResult, StructureGroup, XmlStructure};
+use crate::xsconfig::ExamplesConfig;
 use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use roxmltree::Document;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use tracing::{debug, error, info};
@@ -13,17 +16,27 @@ use tracing::{debug, error, info};
 pub fn parse_xml_structure(xml_content: &str) -> Result<XmlStructure> {
     let doc = Document::parse(xml_content).context("Failed to parse XML document")?;
 
-    let root = doc.root_element();
-    Ok(build_structure_from_node(&root))
+    Ok(document_structure(&doc))
+}
+
+/// Structure of an already parsed document
+pub fn document_structure(doc: &Document) -> XmlStructure {
+    build_structure_from_node(&doc.root_element())
 }
 
 /// Recursively build XmlStructure from roxmltree Node
 fn build_structure_from_node(node: &roxmltree::Node) -> XmlStructure {
     let mut structure = XmlStructure::new(node.tag_name().name().to_string());
+    structure.namespace = node.tag_name().namespace().map(String::from);
 
     // Add attribute keys (ignore values)
     for attr in node.attributes() {
         structure.add_attribute(attr.name().to_string());
+        if let Some(ns) = attr.namespace() {
+            structure
+                .attribute_namespaces
+                .insert(attr.name().to_string(), ns.to_string());
+        }
     }
 
     // Process child elements (skip text nodes, comments, etc.)
@@ -41,6 +54,15 @@ fn build_structure_from_node(node: &roxmltree::Node) -> XmlStructure {
 pub fn process_xml_files(
     file_paths: Vec<String>,
     progress_bar: Option<ProgressBar>,
+) -> Result<ProcessingResult> {
+    process_xml_files_with(file_paths, progress_bar, &ExamplesConfig::default())
+}
+
+/// Process multiple XML files in parallel, choosing group examples by `examples`
+pub fn process_xml_files_with(
+    file_paths: Vec<String>,
+    progress_bar: Option<ProgressBar>,
+    examples: &ExamplesConfig,
 ) -> Result<ProcessingResult> {
     info!("Starting to process {} XML files", file_paths.len());
 
@@ -49,7 +71,7 @@ pub fn process_xml_files(
 
     // Process files in parallel
     file_paths.par_iter().for_each(|file_path| {
-        match process_single_file(file_path, &groups_map) {
+        match process_single_file(file_path, &groups_map, examples) {
             Ok(_) => {
                 debug!("Successfully processed: {}", file_path);
             }
@@ -74,13 +96,19 @@ pub fn process_xml_files(
 
     let mut groups: Vec<StructureGroup> = groups_map.into_values().collect();
 
-    // Sort by count (descending) for better readability
-    groups.sort_by(|a, b| b.count.cmp(&a.count));
+    // Sort by count (descending) for better readability; ties by hash so
+    // group ranks are stable between runs
+    groups.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(a.skeleton.hash.cmp(&b.skeleton.hash))
+    });
 
     let result = ProcessingResult {
         total_files: file_paths.len(),
         unique_structures: groups.len(),
         groups,
+        root: None,
     };
 
     info!(
@@ -95,6 +123,7 @@ pub fn process_xml_files(
 fn process_single_file(
     file_path: &str,
     groups_map: &Arc<Mutex<HashMap<u64, StructureGroup>>>,
+    examples: &ExamplesConfig,
 ) -> Result<()> {
     // Read file
     let content = fs::read_to_string(file_path)
@@ -108,13 +137,26 @@ fn process_single_file(
     let skeleton = structure.to_skeleton();
     let hash = skeleton.hash;
 
+    let size = content.len() as u64;
+    let example = GroupExample {
+        path: file_path.to_string(),
+        size,
+        structure: Some(structure),
+    };
+
     // Add to groups map
     let mut groups = groups_map.lock().unwrap();
 
-    groups
-        .entry(hash)
-        .and_modify(|group| group.add_file(file_path.to_string()))
-        .or_insert_with(|| StructureGroup::new(structure, file_path.to_string()));
+    let group = match groups.entry(hash) {
+        Entry::Occupied(entry) => {
+            let group = entry.into_mut();
+            group.skeleton.merge_namespaces(&skeleton);
+            group
+        }
+        Entry::Vacant(entry) => entry.insert(StructureGroup::new(skeleton)),
+    };
+    group.add_file(file_path.to_string(), size);
+    group.offer_example(example, examples);
 
     Ok(())
 }
@@ -141,19 +183,32 @@ pub fn write_result_to_file(
 ) -> Result<()> {
     info!("Writing results to: {}", output_path.display());
 
-    let json = if pretty {
-        serde_json::to_string_pretty(result)?
+    let file = fs::File::create(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, result)?;
     } else {
-        serde_json::to_string(result)?
-    };
+        serde_json::to_writer(&mut writer, result)?;
+    }
 
-    fs::write(output_path, json)
+    writer
+        .flush()
         .with_context(|| format!("Failed to write to {}", output_path.display()))?;
 
     info!("Successfully wrote results to {}", output_path.display());
     Ok(())
 }
 
+/// Load a processing result previously written as JSON
+pub fn load_result(path: &Path) -> Result<ProcessingResult> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to read results from {}", path.display()))
+}
+
 /// Print summary statistics
 pub fn print_summary(result: &ProcessingResult) {
     println!("\n📊 Processing Summary:");
@@ -179,7 +234,7 @@ pub fn print_summary(result: &ProcessingResult) {
     println!("\n💾 Skeleton Signature Benefits:");
     println!("  - Each skeleton stores merged structure (not duplicated)");
     println!("  - Duplicate child elements are combined");
-    println!("  - Only ONE example structure kept per group");
+    println!("  - Only a few example structures kept per group");
 }
 
 #[cfg(test)]
@@ -0,0 +1,181 @@
+use crate::processor::xml_struct::{SkeletonSignature, XmlStructure};
+use console::style;
+use serde_json::Value;
+
+/// Options for rendering structures as box-drawing trees
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Maximum depth to render below the root (0 = unlimited)
+    pub max_depth: usize,
+    /// Emit ANSI colors
+    pub color: bool,
+}
+
+/// A node as seen by the renderer, independent of the source type
+struct TreeNode<'a> {
+    name: &'a str,
+    attributes: Vec<&'a str>,
+    children: Vec<TreeNode<'a>>,
+}
+
+impl<'a> TreeNode<'a> {
+    fn from_skeleton(name: &'a str, node: &'a Value) -> Self {
+        let attributes = node
+            .get("@attributes")
+            .and_then(|a| a.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|a| a.as_str())
+            .collect();
+
+        let children = node
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(k, _)| *k != "@attributes")
+            .map(|(k, v)| Self::from_skeleton(k, v))
+            .collect();
+
+        Self {
+            name,
+            attributes,
+            children,
+        }
+    }
+
+    fn from_structure(node: &'a XmlStructure) -> Self {
+        Self {
+            name: &node.name,
+            attributes: node
+                .attributes
+                .iter()
+                .flat_map(|a| a.keys())
+                .map(|k| k.as_str())
+                .collect(),
+            children: node
+                .children
+                .iter()
+                .map(|c| Self::from_structure(c))
+                .collect(),
+        }
+    }
+}
+
+/// Render a skeleton signature as a tree
+pub fn render_skeleton(skeleton: &SkeletonSignature, options: &TreeOptions) -> String {
+    render(
+        &TreeNode::from_skeleton(&skeleton.root, &skeleton.skeleton),
+        options,
+    )
+}
+
+/// Render a full XML structure as a tree (repeated children are kept)
+pub fn render_structure(structure: &XmlStructure, options: &TreeOptions) -> String {
+    render(&TreeNode::from_structure(structure), options)
+}
+
+fn render(root: &TreeNode, options: &TreeOptions) -> String {
+    let mut out = String::new();
+    out.push_str(&label(root, options));
+    out.push('\n');
+    render_children(root, "", 1, options, &mut out);
+    out
+}
+
+fn render_children(
+    node: &TreeNode,
+    prefix: &str,
+    depth: usize,
+    options: &TreeOptions,
+    out: &mut String,
+) {
+    if node.children.is_empty() {
+        return;
+    }
+
+    if options.max_depth > 0 && depth > options.max_depth {
+        let more = format!("… {} more", node.children.len());
+        out.push_str(&format!(
+            "{}{}{}\n",
+            prefix,
+            paint("└── ", options, |s| s.dim()),
+            paint(&more, options, |s| s.dim())
+        ));
+        return;
+    }
+
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+
+        out.push_str(prefix);
+        out.push_str(&paint(branch, options, |s| s.dim()));
+        out.push_str(&label(child, options));
+        out.push('\n');
+
+        let child_prefix = format!("{}{}", prefix, paint(indent, options, |s| s.dim()));
+        render_children(child, &child_prefix, depth + 1, options, out);
+    }
+}
+
+/// `name [attr1, attr2]`
+fn label(node: &TreeNode, options: &TreeOptions) -> String {
+    let mut label = paint(node.name, options, |s| s.cyan().bold());
+    if !node.attributes.is_empty() {
+        let attrs = format!("[{}]", node.attributes.join(", "));
+        label.push(' ');
+        label.push_str(&paint(&attrs, options, |s| s.yellow()));
+    }
+    label
+}
+
+fn paint(
+    text: &str,
+    options: &TreeOptions,
+    f: impl FnOnce(console::StyledObject<&str>) -> console::StyledObject<&str>,
+) -> String {
+    if options.color {
+        f(style(text).force_styling(true)).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::struct_processor::parse_xml_structure;
+
+    #[test]
+    fn test_render_skeleton() {
+        let structure =
+            parse_xml_structure(r#"<TEI id="x"><text><p n="1"/><p/></text><back/></TEI>"#).unwrap();
+        let tree = render_skeleton(&structure.to_skeleton(), &TreeOptions::default());
+
+        assert_eq!(tree, "TEI [id]\n├── back\n└── text\n    └── p [n]\n");
+    }
+
+    #[test]
+    fn test_render_structure_keeps_repeats() {
+        let structure = parse_xml_structure(r#"<a><b/><b/></a>"#).unwrap();
+        let tree = render_structure(&structure, &TreeOptions::default());
+
+        assert_eq!(tree, "a\n├── b\n└── b\n");
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let structure = parse_xml_structure(r#"<a><b><c/><d/></b></a>"#).unwrap();
+        let options = TreeOptions {
+            max_depth: 1,
+            color: false,
+        };
+        let tree = render_structure(&structure, &options);
+
+        assert_eq!(tree, "a\n└── b\n    └── … 2 more\n");
+    }
+}
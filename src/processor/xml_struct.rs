@@ -1,6 +1,7 @@
+use crate::xsconfig::{ExamplePolicy, ExamplesConfig};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::{Hash, Hasher};
 
 /// Represents the full structural tree of an XML element
@@ -10,11 +11,19 @@ pub struct XmlStructure {
     /// Element name (e.g., "book", "TEI", "title")
     pub name: String,
 
+    /// Namespace URI of the element, if it is in one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub namespace: Option<String>,
+
     /// Attribute keys only (values ignored for structural comparison)
     /// Using BTreeMap for deterministic ordering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attributes: Option<BTreeMap<String, ()>>,
 
+    /// Namespace URIs of the attributes that are in one, by attribute key
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub attribute_namespaces: BTreeMap<String, String>,
+
     /// Child elements (recursively defined)
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub children: Vec<Box<XmlStructure>>,
@@ -33,6 +42,12 @@ pub struct SkeletonSignature {
 
     /// Hash of the skeleton for quick comparison
     pub hash: u64,
+
+    /// Namespace URIs by element path (`/TEI/text`) and attribute path
+    /// (`/TEI/@schemaLocation`). Only paths in a namespace are listed; `""`
+    /// stands for no namespace where instances differ. Not part of the hash.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub namespaces: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl SkeletonSignature {
@@ -41,14 +56,61 @@ impl SkeletonSignature {
     pub fn from_structure(structure: &XmlStructure) -> Self {
         let skeleton = Self::build_skeleton_json(structure);
         let hash = Self::hash_skeleton(&skeleton);
+        let mut namespaces = BTreeMap::new();
+        Self::collect_namespaces(&format!("/{}", structure.name), structure, &mut namespaces);
+        namespaces.retain(|_, set| set.iter().any(|ns| !ns.is_empty()));
 
         Self {
             root: structure.name.clone(),
             skeleton,
             hash,
+            namespaces,
+        }
+    }
+
+    /// Record the namespace of every element and attribute instance, `""`
+    /// for none
+    fn collect_namespaces(
+        path: &str,
+        node: &XmlStructure,
+        out: &mut BTreeMap<String, BTreeSet<String>>,
+    ) {
+        out.entry(path.to_string())
+            .or_default()
+            .insert(node.namespace.clone().unwrap_or_default());
+        for key in node.attributes.iter().flat_map(|a| a.keys()) {
+            out.entry(format!("{}/@{}", path, key)).or_default().insert(
+                node.attribute_namespaces
+                    .get(key)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        }
+        for child in &node.children {
+            Self::collect_namespaces(&format!("{}/{}", path, child.name), child, out);
         }
     }
 
+    /// Add the namespaces of another skeleton with the same hash, e.g. of
+    /// another file of the group
+    pub fn merge_namespaces(&mut self, other: &SkeletonSignature) {
+        let none = || BTreeSet::from([String::new()]);
+        let keys: BTreeSet<&String> = self
+            .namespaces
+            .keys()
+            .chain(other.namespaces.keys())
+            .collect();
+        let merged: Vec<(String, BTreeSet<String>)> = keys
+            .into_iter()
+            .map(|key| {
+                let mut set = self.namespaces.get(key).cloned().unwrap_or_else(none);
+                set.extend(other.namespaces.get(key).cloned().unwrap_or_else(none));
+                (key.clone(), set)
+            })
+            .collect();
+        self.namespaces.extend(merged);
+    }
+
     /// Build compact JSON skeleton (like tree_summary in your code)
     fn build_skeleton_json(node: &XmlStructure) -> Value {
         let mut summary_map = Map::new();
@@ -67,7 +129,7 @@ impl SkeletonSignature {
         for child in &node.children {
             children_by_name
                 .entry(child.name.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(child);
         }
 
@@ -133,18 +195,91 @@ impl SkeletonSignature {
         }
     }
 
-    /// Generate hash from skeleton JSON for comparison
-    fn hash_skeleton(skeleton: &Value) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
-        let mut hasher = DefaultHasher::new();
-        // Use canonical JSON string for consistent hashing
-        skeleton.to_string().hash(&mut hasher);
-        hasher.finish()
+    /// Generate hash from skeleton JSON for comparison: FNV-1a over the
+    /// canonical (compact, key-sorted) JSON. Baselines and results files
+    /// store it, so it must not change with the Rust release.
+    pub(crate) fn hash_skeleton(skeleton: &Value) -> u64 {
+        fnv1a(skeleton.to_string().as_bytes())
     }
 
     /// Generate a compact string representation of the skeleton
     pub fn to_compact_string(&self) -> String {
-        format!("{}:{}", self.root, self.skeleton.to_string())
+        format!("{}:{}", self.root, self.skeleton)
+    }
+
+    /// List every element path of the skeleton in document-tree order,
+    /// e.g. `/TEI`, `/TEI/text`, `/TEI/text/body`
+    pub fn paths(&self) -> Vec<SkeletonPath> {
+        let mut paths = Vec::new();
+        self.collect_paths(&format!("/{}", self.root), &self.skeleton, &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, path: &str, node: &Value, out: &mut Vec<SkeletonPath>) {
+        let attributes: Vec<String> = node
+            .get("@attributes")
+            .and_then(|a| a.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|a| a.as_str().map(String::from))
+            .collect();
+        let children: Vec<(&String, &Value)> = node
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(k, _)| *k != "@attributes")
+            .collect();
+
+        let attribute_namespaces = attributes
+            .iter()
+            .filter_map(|a| {
+                let set = self.namespaces.get(&format!("{}/@{}", path, a))?;
+                Some((a.clone(), set.clone()))
+            })
+            .collect();
+
+        out.push(SkeletonPath {
+            path: path.to_string(),
+            namespaces: self.namespaces.get(path).cloned().unwrap_or_default(),
+            attributes,
+            attribute_namespaces,
+            is_leaf: children.is_empty(),
+        });
+        for (name, child) in children {
+            self.collect_paths(&format!("{}/{}", path, name), child, out);
+        }
+    }
+}
+
+/// One element path of a skeleton with the attribute keys seen there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkeletonPath {
+    /// Slash-separated element path from the root, e.g. `/TEI/text/body`
+    pub path: String,
+
+    /// Namespace URIs of the element (see `SkeletonSignature::namespaces`),
+    /// empty when it is in none
+    pub namespaces: BTreeSet<String>,
+
+    /// Attribute keys (sorted)
+    pub attributes: Vec<String>,
+
+    /// Namespace URIs of the attributes that are in one
+    pub attribute_namespaces: BTreeMap<String, BTreeSet<String>>,
+
+    /// True when the element has no child elements
+    pub is_leaf: bool,
+}
+
+impl SkeletonPath {
+    /// Element name (last path segment)
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// Depth below the root (root = 0)
+    pub fn depth(&self) -> usize {
+        self.path.matches('/').count() - 1
     }
 }
 
@@ -153,7 +288,9 @@ impl XmlStructure {
     pub fn new(name: String) -> Self {
         Self {
             name,
+            namespace: None,
             attributes: None,
+            attribute_namespaces: BTreeMap::new(),
             children: Vec::new(),
         }
     }
@@ -249,30 +386,76 @@ pub struct StructureGroup {
     /// List of file paths with this skeleton
     pub files: Vec<String>,
 
+    /// Size in bytes of each file when it was analyzed, in the order of
+    /// `files` (empty in results written before sizes were recorded)
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub sizes: Vec<u64>,
+
     /// Number of files in this group
     pub count: usize,
 
-    /// Optional: Store ONE example of the full structure (not all 177!)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub example_structure: Option<XmlStructure>,
+    /// Representative files, ordered by the configured example policy
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub examples: Vec<GroupExample>,
 }
 
-impl StructureGroup {
-    pub fn new(structure: XmlStructure, file_path: String) -> Self {
-        let skeleton = structure.to_skeleton();
+/// One representative file of a structure group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupExample {
+    /// File path
+    pub path: String,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Full structure of the file (omitted in paths-only mode)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub structure: Option<XmlStructure>,
+}
 
+impl StructureGroup {
+    pub fn new(skeleton: SkeletonSignature) -> Self {
         Self {
             skeleton,
-            files: vec![file_path],
-            count: 1,
-            example_structure: Some(structure), // Keep first example
+            files: Vec::new(),
+            sizes: Vec::new(),
+            count: 0,
+            examples: Vec::new(),
         }
     }
 
-    pub fn add_file(&mut self, file_path: String) {
+    pub fn add_file(&mut self, file_path: String, size: u64) {
         self.files.push(file_path);
+        self.sizes.push(size);
         self.count += 1;
-        // Don't add more structures - we already have an example
+    }
+
+    /// Offer a file as an example; it is kept only if it ranks among the
+    /// best `config.count` candidates seen so far. The ranking depends only
+    /// on path, size and seed, so the outcome doesn't depend on file order.
+    pub fn offer_example(&mut self, mut example: GroupExample, config: &ExamplesConfig) {
+        if config.count == 0 {
+            return;
+        }
+        if config.paths_only {
+            example.structure = None;
+        }
+
+        let key = example_rank(&example, config);
+        let pos = self
+            .examples
+            .partition_point(|e| example_rank(e, config) < key);
+        if pos >= config.count {
+            return;
+        }
+
+        self.examples.insert(pos, example);
+        self.examples.truncate(config.count);
+    }
+
+    /// First example structure, if one was kept
+    pub fn example_structure(&self) -> Option<&XmlStructure> {
+        self.examples.iter().find_map(|e| e.structure.as_ref())
     }
 
     /// Get the hash for comparison
@@ -287,6 +470,33 @@ impl StructureGroup {
     }
 }
 
+/// Sort key of an example under the configured policy (lower ranks first)
+fn example_rank<'a>(example: &'a GroupExample, config: &ExamplesConfig) -> (u64, &'a str) {
+    let primary = match config.policy {
+        ExamplePolicy::Smallest => example.size,
+        ExamplePolicy::Largest => u64::MAX - example.size,
+        ExamplePolicy::Lexicographic => 0,
+        ExamplePolicy::Random => seeded_path_hash(config.seed, &example.path),
+    };
+    (primary, example.path.as_str())
+}
+
+/// Hash of the little-endian seed followed by the path bytes; a seed must
+/// pick the same examples on every toolchain
+fn seeded_path_hash(seed: u64, path: &str) -> u64 {
+    fnv1a(seed.to_le_bytes().iter().chain(path.as_bytes()))
+}
+
+/// 64-bit FNV-1a. Spelled out rather than taken from `std`, whose hasher may
+/// change between Rust releases.
+fn fnv1a<'b>(bytes: impl IntoIterator<Item = &'b u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
 /// Result of processing all XML files
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessingResult {
@@ -298,6 +508,11 @@ pub struct ProcessingResult {
 
     /// All structure groups
     pub groups: Vec<StructureGroup>,
+
+    /// Directory the files were found in, as given; `diff` matches files
+    /// by their path below it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub root: Option<String>,
 }
 
 #[cfg(test)]
@@ -397,4 +612,155 @@ mod tests {
         assert!(attrs.contains(&json!("id")));
         assert!(attrs.contains(&json!("title")));
     }
+
+    #[test]
+    fn test_skeleton_paths() {
+        let mut root = XmlStructure::new("book".to_string());
+        let mut chapter = XmlStructure::new("chapter".to_string());
+        chapter.add_attribute("n".to_string());
+        chapter.add_child(XmlStructure::new("title".to_string()));
+        root.add_child(chapter);
+
+        let paths = root.to_skeleton().paths();
+        let names: Vec<&str> = paths.iter().map(|p| p.path.as_str()).collect();
+
+        assert_eq!(names, vec!["/book", "/book/chapter", "/book/chapter/title"]);
+        assert_eq!(paths[1].attributes, vec!["n"]);
+        assert_eq!(paths[1].name(), "chapter");
+        assert_eq!(paths[2].depth(), 2);
+        assert!(!paths[1].is_leaf);
+        assert!(paths[2].is_leaf);
+    }
+
+    #[test]
+    fn test_skeleton_namespaces() {
+        const TEI: &str = "http://www.tei-c.org/ns/1.0";
+        const XLINK: &str = "http://www.w3.org/1999/xlink";
+        let mut root = XmlStructure::new("TEI".to_string());
+        root.namespace = Some(TEI.to_string());
+        let mut tei_ref = XmlStructure::new("ref".to_string());
+        tei_ref.namespace = Some(TEI.to_string());
+        tei_ref.add_attribute("href".to_string());
+        tei_ref.add_attribute("target".to_string());
+        tei_ref
+            .attribute_namespaces
+            .insert("href".to_string(), XLINK.to_string());
+        root.add_child(tei_ref);
+        root.add_child(XmlStructure::new("ref".to_string()));
+
+        let skeleton = root.to_skeleton();
+        let set = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        assert_eq!(skeleton.namespaces["/TEI"], set(&[TEI]));
+        assert_eq!(skeleton.namespaces["/TEI/ref"], set(&["", TEI]));
+        assert_eq!(skeleton.namespaces["/TEI/ref/@href"], set(&[XLINK]));
+        assert!(!skeleton.namespaces.contains_key("/TEI/ref/@target"));
+
+        let paths = skeleton.paths();
+        assert_eq!(paths[1].attribute_namespaces["href"], set(&[XLINK]));
+
+        // Namespaces don't change the hash; merging marks the difference
+        let mut plain = XmlStructure::new("TEI".to_string());
+        let mut plain_ref = XmlStructure::new("ref".to_string());
+        plain_ref.add_attribute("href".to_string());
+        plain_ref.add_attribute("target".to_string());
+        plain.add_child(plain_ref);
+        let mut merged = plain.to_skeleton();
+        assert_eq!(merged.hash, skeleton.hash);
+        assert!(merged.namespaces.is_empty());
+        merged.merge_namespaces(&skeleton);
+        assert_eq!(merged.namespaces["/TEI"], set(&["", TEI]));
+        assert_eq!(merged.namespaces["/TEI/ref/@href"], set(&["", XLINK]));
+    }
+
+    fn example(path: &str, size: u64) -> GroupExample {
+        GroupExample {
+            path: path.to_string(),
+            size,
+            structure: Some(XmlStructure::new("book".to_string())),
+        }
+    }
+
+    fn offer_all(config: &ExamplesConfig, files: &[(&str, u64)]) -> Vec<String> {
+        let mut group = StructureGroup::new(XmlStructure::new("book".to_string()).to_skeleton());
+        for (path, size) in files {
+            group.offer_example(example(path, *size), config);
+        }
+        group.examples.into_iter().map(|e| e.path).collect()
+    }
+
+    #[test]
+    fn test_examples_by_policy() {
+        let files = [("c.xml", 10), ("a.xml", 30), ("b.xml", 20), ("d.xml", 5)];
+        let mut config = ExamplesConfig {
+            count: 2,
+            ..ExamplesConfig::default()
+        };
+
+        config.policy = ExamplePolicy::Lexicographic;
+        assert_eq!(offer_all(&config, &files), vec!["a.xml", "b.xml"]);
+
+        config.policy = ExamplePolicy::Smallest;
+        assert_eq!(offer_all(&config, &files), vec!["d.xml", "c.xml"]);
+
+        config.policy = ExamplePolicy::Largest;
+        assert_eq!(offer_all(&config, &files), vec!["a.xml", "b.xml"]);
+    }
+
+    #[test]
+    fn test_random_examples_are_order_independent() {
+        let files = [("a.xml", 1), ("b.xml", 2), ("c.xml", 3), ("d.xml", 4)];
+        let mut reversed = files;
+        reversed.reverse();
+        let config = ExamplesConfig {
+            count: 2,
+            policy: ExamplePolicy::Random,
+            seed: 42,
+            paths_only: false,
+        };
+
+        assert_eq!(offer_all(&config, &files), offer_all(&config, &reversed));
+    }
+
+    #[test]
+    fn test_skeleton_hash_is_pinned() {
+        // Baselines store hashes; they must survive toolchain upgrades
+        let mut root = XmlStructure::new("TEI".to_string());
+        root.add_child(XmlStructure::new("teiHeader".to_string()));
+        let mut text = XmlStructure::new("text".to_string());
+        text.add_attribute("type".to_string());
+        root.add_child(text);
+
+        let skeleton = root.to_skeleton();
+        assert_eq!(
+            skeleton.skeleton.to_string(),
+            r#"{"teiHeader":{},"text":{"@attributes":["type"]}}"#
+        );
+        assert_eq!(skeleton.hash, 0x06d9_b2c7_bb1c_6a56);
+    }
+
+    #[test]
+    fn test_random_examples_are_pinned_per_seed() {
+        assert_eq!(seeded_path_hash(0, ""), 0xa8c7_f832_281a_39c5);
+        let files = [("a.xml", 1), ("b.xml", 2), ("c.xml", 3), ("d.xml", 4)];
+        let config = ExamplesConfig {
+            count: 2,
+            policy: ExamplePolicy::Random,
+            seed: 42,
+            paths_only: false,
+        };
+        assert_eq!(offer_all(&config, &files), vec!["c.xml", "d.xml"]);
+    }
+
+    #[test]
+    fn test_examples_paths_only() {
+        let config = ExamplesConfig {
+            paths_only: true,
+            ..ExamplesConfig::default()
+        };
+        let mut group = StructureGroup::new(XmlStructure::new("book".to_string()).to_skeleton());
+        group.offer_example(example("a.xml", 1), &config);
+
+        assert_eq!(group.examples.len(), 1);
+        assert!(group.example_structure().is_none());
+    }
 }
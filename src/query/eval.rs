@@ -0,0 +1,880 @@
+//! Evaluator for parsed XPath expressions over a `roxmltree` document.
+//!
+//! Functions: `last`, `position`, `count`, `name`, `local-name`,
+//! `namespace-uri`, `string`, `concat`, `starts-with`, `ends-with`,
+//! `contains`, `substring`, `substring-before`, `substring-after`,
+//! `string-length`, `normalize-space`, `translate`, `lower-case`,
+//! `upper-case`, `matches` (see `regex`), `not`, `true`, `false`,
+//! `boolean`, `number`, `sum`, `floor`, `ceiling`, `round`, `id`.
+
+use super::regex::XPathRegex;
+use super::xpath::{Axis, BinaryOp, Expr, LocationPath, NodeTest, Step, XPath, XPathError};
+use roxmltree::Node;
+use std::collections::HashMap;
+
+/// Names of the functions `Evaluator` implements
+pub const FUNCTIONS: &[&str] = &[
+    "last",
+    "position",
+    "count",
+    "name",
+    "local-name",
+    "namespace-uri",
+    "string",
+    "concat",
+    "starts-with",
+    "ends-with",
+    "contains",
+    "matches",
+    "substring-before",
+    "substring-after",
+    "substring",
+    "string-length",
+    "normalize-space",
+    "translate",
+    "lower-case",
+    "upper-case",
+    "not",
+    "true",
+    "false",
+    "boolean",
+    "number",
+    "sum",
+    "floor",
+    "ceiling",
+    "round",
+    "id",
+];
+
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// A node in the XPath data model: a document node or an attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XNode<'a, 'input> {
+    Node(Node<'a, 'input>),
+    /// Element and attribute index
+    Attribute(Node<'a, 'input>, usize),
+}
+
+impl<'a, 'input> XNode<'a, 'input> {
+    /// Element or other tree node this item belongs to (owner for attributes)
+    pub fn node(&self) -> Node<'a, 'input> {
+        match self {
+            XNode::Node(n) | XNode::Attribute(n, _) => *n,
+        }
+    }
+
+    pub fn attribute(&self) -> Option<roxmltree::Attribute<'a, 'input>> {
+        match self {
+            XNode::Attribute(n, i) => n.attributes().nth(*i),
+            XNode::Node(_) => None,
+        }
+    }
+
+    /// Byte range in the source document
+    pub fn range(&self) -> std::ops::Range<usize> {
+        match self.attribute() {
+            Some(attr) => attr.range(),
+            None => self.node().range(),
+        }
+    }
+
+    /// 1-based line and column of the start of this item
+    pub fn position(&self) -> (usize, usize) {
+        let pos = self.node().document().text_pos_at(self.range().start);
+        (pos.row as usize, pos.col as usize)
+    }
+
+    /// XPath string-value
+    pub fn string_value(&self) -> String {
+        match self {
+            XNode::Attribute(..) => self
+                .attribute()
+                .map(|a| a.value().to_string())
+                .unwrap_or_default(),
+            XNode::Node(n) if n.is_element() || n.is_root() => n
+                .descendants()
+                .filter(|d| d.is_text())
+                .filter_map(|d| d.text())
+                .collect(),
+            XNode::Node(n) => n.text().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Local name (empty for text, comment and root nodes)
+    pub fn local_name(&self) -> &'a str {
+        match self {
+            XNode::Attribute(..) => self.attribute().map(|a| a.name()).unwrap_or_default(),
+            XNode::Node(n) if n.is_element() => n.tag_name().name(),
+            XNode::Node(n) if n.is_pi() => n.pi().map(|p| p.target).unwrap_or_default(),
+            XNode::Node(_) => "",
+        }
+    }
+
+    pub fn namespace(&self) -> Option<&'a str> {
+        match self {
+            XNode::Attribute(..) => self.attribute().and_then(|a| a.namespace()),
+            XNode::Node(n) => n.tag_name().namespace(),
+        }
+    }
+
+    /// Qualified name; only the `xml` prefix is reconstructed
+    pub fn name(&self) -> String {
+        match self.namespace() {
+            Some(XML_NS) => format!("xml:{}", self.local_name()),
+            _ => self.local_name().to_string(),
+        }
+    }
+
+    /// Source text of this item: the element markup, `name="value"` for
+    /// attributes, the text for text nodes
+    pub fn fragment(&self) -> &'a str {
+        let input = self.node().document().input_text();
+        match self {
+            XNode::Node(n) if n.is_text() => n.text().unwrap_or_default(),
+            _ => &input[self.range()],
+        }
+    }
+
+    fn order_key(&self) -> (usize, usize) {
+        match self {
+            XNode::Node(n) => (n.id().get_usize(), 0),
+            XNode::Attribute(n, i) => (n.id().get_usize(), i + 1),
+        }
+    }
+}
+
+/// Result of evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a, 'input> {
+    Nodes(Vec<XNode<'a, 'input>>),
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl<'a, 'input> Value<'a, 'input> {
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Value::Nodes(nodes) => !nodes.is_empty(),
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0 && !n.is_nan(),
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Value::Bool(b) => {
+                if *b {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Value::Number(n) => *n,
+            _ => string_to_number(&self.to_string_value()),
+        }
+    }
+
+    pub fn to_string_value(&self) -> String {
+        match self {
+            Value::Nodes(nodes) => nodes.first().map(|n| n.string_value()).unwrap_or_default(),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => number_to_string(*n),
+            Value::Str(s) => s.clone(),
+        }
+    }
+
+    fn into_nodes(self, function: &str) -> Result<Vec<XNode<'a, 'input>>, XPathError> {
+        match self {
+            Value::Nodes(nodes) => Ok(nodes),
+            _ => Err(XPathError::Type(format!("{} expects a node-set", function))),
+        }
+    }
+}
+
+fn string_to_number(s: &str) -> f64 {
+    s.trim().parse().unwrap_or(f64::NAN)
+}
+
+/// XPath number formatting: integers without a fraction
+pub fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if n == n.trunc() && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Context<'a, 'input> {
+    node: XNode<'a, 'input>,
+    position: usize,
+    size: usize,
+}
+
+/// Evaluates expressions with optional variable bindings
+#[derive(Clone, Default)]
+pub struct Evaluator<'a, 'input> {
+    variables: HashMap<String, Value<'a, 'input>>,
+}
+
+impl<'a, 'input> Evaluator<'a, 'input> {
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Bind `$name` for subsequent evaluations
+    pub fn set_variable(&mut self, name: impl Into<String>, value: Value<'a, 'input>) {
+        self.variables.insert(name.into(), value);
+    }
+
+    /// Evaluate `xpath` with `node` as the context node
+    pub fn evaluate(
+        &self,
+        xpath: &XPath,
+        node: XNode<'a, 'input>,
+    ) -> Result<Value<'a, 'input>, XPathError> {
+        let ctx = Context {
+            node,
+            position: 1,
+            size: 1,
+        };
+        self.eval(&xpath.expr, &ctx)
+    }
+
+    fn eval(
+        &self,
+        expr: &Expr,
+        ctx: &Context<'a, 'input>,
+    ) -> Result<Value<'a, 'input>, XPathError> {
+        Ok(match expr {
+            Expr::Or(l, r) => {
+                Value::Bool(self.eval(l, ctx)?.to_bool() || self.eval(r, ctx)?.to_bool())
+            }
+            Expr::And(l, r) => {
+                Value::Bool(self.eval(l, ctx)?.to_bool() && self.eval(r, ctx)?.to_bool())
+            }
+            Expr::Binary(op, l, r) => {
+                let left = self.eval(l, ctx)?;
+                let right = self.eval(r, ctx)?;
+                binary(*op, &left, &right)
+            }
+            Expr::Negate(e) => Value::Number(-self.eval(e, ctx)?.to_number()),
+            Expr::Union(l, r) => {
+                let mut nodes = self.eval(l, ctx)?.into_nodes("|")?;
+                nodes.extend(self.eval(r, ctx)?.into_nodes("|")?);
+                Value::Nodes(document_order(nodes))
+            }
+            Expr::Path(start, path) => {
+                let initial = match start {
+                    Some(filter) => self.eval(filter, ctx)?.into_nodes("a path step")?,
+                    None if path.absolute => {
+                        vec![XNode::Node(ctx.node.node().document().root())]
+                    }
+                    None => vec![ctx.node],
+                };
+                Value::Nodes(self.eval_steps(path, initial)?)
+            }
+            Expr::Filter(primary, predicates) => {
+                let mut nodes = self.eval(primary, ctx)?.into_nodes("a predicate")?;
+                for predicate in predicates {
+                    nodes = self.filter(nodes, predicate)?;
+                }
+                Value::Nodes(nodes)
+            }
+            Expr::Literal(s) => Value::Str(s.clone()),
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Variable(name) => self
+                .variables
+                .get(name)
+                .cloned()
+                .ok_or_else(|| XPathError::UnboundVariable(name.clone()))?,
+            Expr::Function(name, args) => self.call(name, args, ctx)?,
+            Expr::Matches(input, regex) => {
+                Value::Bool(regex.is_match(&self.eval(input, ctx)?.to_string_value()))
+            }
+        })
+    }
+
+    fn eval_steps(
+        &self,
+        path: &LocationPath,
+        mut nodes: Vec<XNode<'a, 'input>>,
+    ) -> Result<Vec<XNode<'a, 'input>>, XPathError> {
+        for step in &path.steps {
+            let mut next = Vec::new();
+            for node in &nodes {
+                next.extend(self.eval_step(step, *node)?);
+            }
+            nodes = document_order(next);
+        }
+        Ok(nodes)
+    }
+
+    fn eval_step(
+        &self,
+        step: &Step,
+        node: XNode<'a, 'input>,
+    ) -> Result<Vec<XNode<'a, 'input>>, XPathError> {
+        let mut nodes: Vec<XNode> = axis_nodes(step.axis, node)
+            .into_iter()
+            .filter(|n| matches_test(&step.test, step.axis, n))
+            .collect();
+        // Reverse axes come nearest first, so positions are proximity positions
+        for predicate in &step.predicates {
+            nodes = self.filter(nodes, predicate)?;
+        }
+        Ok(nodes)
+    }
+
+    fn filter(
+        &self,
+        nodes: Vec<XNode<'a, 'input>>,
+        predicate: &Expr,
+    ) -> Result<Vec<XNode<'a, 'input>>, XPathError> {
+        let size = nodes.len();
+        let mut kept = Vec::new();
+        for (i, node) in nodes.into_iter().enumerate() {
+            let ctx = Context {
+                node,
+                position: i + 1,
+                size,
+            };
+            let keep = match self.eval(predicate, &ctx)? {
+                Value::Number(n) => n == (i + 1) as f64,
+                value => value.to_bool(),
+            };
+            if keep {
+                kept.push(node);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        args: &[Expr],
+        ctx: &Context<'a, 'input>,
+    ) -> Result<Value<'a, 'input>, XPathError> {
+        let arity = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(XPathError::Arity(name.to_string()))
+            } else {
+                Ok(())
+            }
+        };
+        let arg = |i: usize| self.eval(&args[i], ctx);
+        let string_arg = |i: usize| -> Result<String, XPathError> {
+            match args.get(i) {
+                Some(e) => Ok(self.eval(e, ctx)?.to_string_value()),
+                None => Ok(ctx.node.string_value()),
+            }
+        };
+        let node_arg = |function: &str| -> Result<Option<XNode<'a, 'input>>, XPathError> {
+            match args.first() {
+                Some(e) => Ok(self.eval(e, ctx)?.into_nodes(function)?.first().copied()),
+                None => Ok(Some(ctx.node)),
+            }
+        };
+
+        Ok(match name {
+            "last" => {
+                arity(0, 0)?;
+                Value::Number(ctx.size as f64)
+            }
+            "position" => {
+                arity(0, 0)?;
+                Value::Number(ctx.position as f64)
+            }
+            "count" => {
+                arity(1, 1)?;
+                Value::Number(arg(0)?.into_nodes(name)?.len() as f64)
+            }
+            "name" => {
+                arity(0, 1)?;
+                Value::Str(node_arg(name)?.map(|n| n.name()).unwrap_or_default())
+            }
+            "local-name" => {
+                arity(0, 1)?;
+                Value::Str(
+                    node_arg(name)?
+                        .map(|n| n.local_name().to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "namespace-uri" => {
+                arity(0, 1)?;
+                Value::Str(
+                    node_arg(name)?
+                        .and_then(|n| n.namespace())
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            }
+            "string" => {
+                arity(0, 1)?;
+                Value::Str(string_arg(0)?)
+            }
+            "concat" => {
+                if args.len() < 2 {
+                    return Err(XPathError::Arity(name.to_string()));
+                }
+                let mut out = String::new();
+                for i in 0..args.len() {
+                    out.push_str(&string_arg(i)?);
+                }
+                Value::Str(out)
+            }
+            "starts-with" => {
+                arity(2, 2)?;
+                Value::Bool(string_arg(0)?.starts_with(&string_arg(1)?))
+            }
+            "ends-with" => {
+                arity(2, 2)?;
+                Value::Bool(string_arg(0)?.ends_with(&string_arg(1)?))
+            }
+            "contains" => {
+                arity(2, 2)?;
+                Value::Bool(string_arg(0)?.contains(&string_arg(1)?))
+            }
+            // Literal patterns are compiled by the parser (`Expr::Matches`);
+            // computed ones on every call
+            "matches" => {
+                arity(2, 3)?;
+                let flags = match args.get(2) {
+                    Some(_) => string_arg(2)?,
+                    None => String::new(),
+                };
+                let regex = XPathRegex::new(&string_arg(1)?, &flags)?;
+                Value::Bool(regex.is_match(&string_arg(0)?))
+            }
+            "substring-before" => {
+                arity(2, 2)?;
+                let s = string_arg(0)?;
+                let pat = string_arg(1)?;
+                Value::Str(s.find(&pat).map(|i| s[..i].to_string()).unwrap_or_default())
+            }
+            "substring-after" => {
+                arity(2, 2)?;
+                let s = string_arg(0)?;
+                let pat = string_arg(1)?;
+                Value::Str(
+                    s.find(&pat)
+                        .map(|i| s[i + pat.len()..].to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "substring" => {
+                arity(2, 3)?;
+                let s: Vec<char> = string_arg(0)?.chars().collect();
+                let start = arg(1)?.to_number().round();
+                let end = match args.get(2) {
+                    Some(_) => start + arg(2)?.to_number().round(),
+                    None => f64::INFINITY,
+                };
+                Value::Str(
+                    s.iter()
+                        .enumerate()
+                        .filter(|(i, _)| {
+                            let p = (*i + 1) as f64;
+                            p >= start && p < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                )
+            }
+            "string-length" => {
+                arity(0, 1)?;
+                Value::Number(string_arg(0)?.chars().count() as f64)
+            }
+            "normalize-space" => {
+                arity(0, 1)?;
+                Value::Str(
+                    string_arg(0)?
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+            }
+            "translate" => {
+                arity(3, 3)?;
+                let from: Vec<char> = string_arg(1)?.chars().collect();
+                let to: Vec<char> = string_arg(2)?.chars().collect();
+                Value::Str(
+                    string_arg(0)?
+                        .chars()
+                        .filter_map(|c| match from.iter().position(|f| *f == c) {
+                            Some(i) => to.get(i).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+            "lower-case" => {
+                arity(1, 1)?;
+                Value::Str(string_arg(0)?.to_lowercase())
+            }
+            "upper-case" => {
+                arity(1, 1)?;
+                Value::Str(string_arg(0)?.to_uppercase())
+            }
+            "not" => {
+                arity(1, 1)?;
+                Value::Bool(!arg(0)?.to_bool())
+            }
+            "true" => {
+                arity(0, 0)?;
+                Value::Bool(true)
+            }
+            "false" => {
+                arity(0, 0)?;
+                Value::Bool(false)
+            }
+            "boolean" => {
+                arity(1, 1)?;
+                Value::Bool(arg(0)?.to_bool())
+            }
+            "number" => {
+                arity(0, 1)?;
+                match args.first() {
+                    Some(_) => Value::Number(arg(0)?.to_number()),
+                    None => Value::Number(string_to_number(&ctx.node.string_value())),
+                }
+            }
+            "sum" => {
+                arity(1, 1)?;
+                Value::Number(
+                    arg(0)?
+                        .into_nodes(name)?
+                        .iter()
+                        .map(|n| string_to_number(&n.string_value()))
+                        .sum(),
+                )
+            }
+            "floor" => {
+                arity(1, 1)?;
+                Value::Number(arg(0)?.to_number().floor())
+            }
+            "ceiling" => {
+                arity(1, 1)?;
+                Value::Number(arg(0)?.to_number().ceil())
+            }
+            "round" => {
+                arity(1, 1)?;
+                Value::Number((arg(0)?.to_number() + 0.5).floor())
+            }
+            "id" => {
+                arity(1, 1)?;
+                let ids: Vec<String> = match arg(0)? {
+                    Value::Nodes(nodes) => nodes.iter().map(|n| n.string_value()).collect(),
+                    value => vec![value.to_string_value()],
+                };
+                let wanted: Vec<&str> = ids.iter().flat_map(|s| s.split_whitespace()).collect();
+                let doc = ctx.node.node().document();
+                Value::Nodes(
+                    doc.descendants()
+                        .filter(|n| {
+                            n.attribute((XML_NS, "id"))
+                                .is_some_and(|id| wanted.contains(&id))
+                        })
+                        .map(XNode::Node)
+                        .collect(),
+                )
+            }
+            _ => return Err(XPathError::UnknownFunction(name.to_string())),
+        })
+    }
+}
+
+fn binary<'a, 'input>(
+    op: BinaryOp,
+    left: &Value<'a, 'input>,
+    right: &Value<'a, 'input>,
+) -> Value<'a, 'input> {
+    match op {
+        BinaryOp::Add => Value::Number(left.to_number() + right.to_number()),
+        BinaryOp::Sub => Value::Number(left.to_number() - right.to_number()),
+        BinaryOp::Mul => Value::Number(left.to_number() * right.to_number()),
+        BinaryOp::Div => Value::Number(left.to_number() / right.to_number()),
+        BinaryOp::Mod => Value::Number(left.to_number() % right.to_number()),
+        _ => Value::Bool(compare(op, left, right)),
+    }
+}
+
+/// XPath 1.0 comparison semantics, including existential node-set rules
+fn compare(op: BinaryOp, left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Nodes(l), Value::Nodes(r)) => {
+            let rs: Vec<String> = r.iter().map(|n| n.string_value()).collect();
+            l.iter().any(|a| {
+                let a = a.string_value();
+                rs.iter()
+                    .any(|b| compare_atoms(op, &Value::Str(a.clone()), &Value::Str(b.clone())))
+            })
+        }
+        (Value::Nodes(nodes), Value::Bool(_)) | (Value::Bool(_), Value::Nodes(nodes)) => {
+            let as_bool = Value::Bool(!nodes.is_empty());
+            if matches!(left, Value::Nodes(_)) {
+                compare_atoms(op, &as_bool, right)
+            } else {
+                compare_atoms(op, left, &as_bool)
+            }
+        }
+        (Value::Nodes(nodes), other) => nodes
+            .iter()
+            .any(|n| compare_atoms(op, &atom_like(n, other), other)),
+        (other, Value::Nodes(nodes)) => nodes
+            .iter()
+            .any(|n| compare_atoms(op, other, &atom_like(n, other))),
+        _ => compare_atoms(op, left, right),
+    }
+}
+
+/// Convert a node to the type of the other comparison operand
+fn atom_like<'a, 'input>(node: &XNode<'a, 'input>, other: &Value) -> Value<'a, 'input> {
+    match other {
+        Value::Number(_) => Value::Number(string_to_number(&node.string_value())),
+        _ => Value::Str(node.string_value()),
+    }
+}
+
+fn compare_atoms(op: BinaryOp, left: &Value, right: &Value) -> bool {
+    match op {
+        BinaryOp::Eq | BinaryOp::Neq => {
+            let equal = if matches!(left, Value::Bool(_)) || matches!(right, Value::Bool(_)) {
+                left.to_bool() == right.to_bool()
+            } else if matches!(left, Value::Number(_)) || matches!(right, Value::Number(_)) {
+                left.to_number() == right.to_number()
+            } else {
+                left.to_string_value() == right.to_string_value()
+            };
+            equal == (op == BinaryOp::Eq)
+        }
+        _ => {
+            let (l, r) = (left.to_number(), right.to_number());
+            match op {
+                BinaryOp::Lt => l < r,
+                BinaryOp::Le => l <= r,
+                BinaryOp::Gt => l > r,
+                _ => l >= r,
+            }
+        }
+    }
+}
+
+/// Nodes along `axis`, nearest first for reverse axes
+fn axis_nodes<'a, 'input>(axis: Axis, node: XNode<'a, 'input>) -> Vec<XNode<'a, 'input>> {
+    let n = node.node();
+    let is_attr = matches!(node, XNode::Attribute(..));
+    match axis {
+        Axis::SelfNode => vec![node],
+        Axis::Attribute if !is_attr && n.is_element() => (0..n.attributes().len())
+            .map(|i| XNode::Attribute(n, i))
+            .collect(),
+        // Attributes have no children, descendants or siblings
+        Axis::Attribute | Axis::Child | Axis::Descendant if is_attr => Vec::new(),
+        Axis::FollowingSibling | Axis::PrecedingSibling if is_attr => Vec::new(),
+        Axis::DescendantOrSelf if is_attr => vec![node],
+        Axis::Attribute => Vec::new(),
+        Axis::Child => n.children().map(XNode::Node).collect(),
+        Axis::Descendant => n.descendants().skip(1).map(XNode::Node).collect(),
+        Axis::DescendantOrSelf => n.descendants().map(XNode::Node).collect(),
+        Axis::Parent if is_attr => vec![XNode::Node(n)],
+        Axis::Parent => n.parent().map(XNode::Node).into_iter().collect(),
+        Axis::Ancestor if is_attr => n.ancestors().map(XNode::Node).collect(),
+        Axis::Ancestor => n.ancestors().skip(1).map(XNode::Node).collect(),
+        Axis::AncestorOrSelf if is_attr => std::iter::once(node)
+            .chain(n.ancestors().map(XNode::Node))
+            .collect(),
+        Axis::AncestorOrSelf => n.ancestors().map(XNode::Node).collect(),
+        Axis::FollowingSibling => n.next_siblings().skip(1).map(XNode::Node).collect(),
+        Axis::PrecedingSibling => n.prev_siblings().skip(1).map(XNode::Node).collect(),
+    }
+}
+
+fn matches_test(test: &NodeTest, axis: Axis, node: &XNode) -> bool {
+    let principal = |node: &XNode| match node {
+        XNode::Attribute(..) => axis == Axis::Attribute,
+        XNode::Node(n) => n.is_element() && axis != Axis::Attribute,
+    };
+    match test {
+        NodeTest::Node => true,
+        NodeTest::Text => matches!(node, XNode::Node(n) if n.is_text()),
+        NodeTest::Comment => matches!(node, XNode::Node(n) if n.is_comment()),
+        NodeTest::Any => principal(node),
+        NodeTest::Name { prefix, local } => {
+            principal(node)
+                && node.local_name() == local
+                && (prefix.as_deref() != Some("xml") || node.namespace() == Some(XML_NS))
+        }
+    }
+}
+
+fn document_order<'a, 'input>(mut nodes: Vec<XNode<'a, 'input>>) -> Vec<XNode<'a, 'input>> {
+    nodes.sort_by_key(|n| n.order_key());
+    nodes.dedup();
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roxmltree::Document;
+
+    const XML: &str = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0" xml:id="t">
+  <teiHeader><title type="main">Main</title><title>Sub</title></teiHeader>
+  <text>
+    <div type="chapter" n="1"><head>One</head><p>a</p><p>b</p></div>
+    <div n="2"><p>c</p><pb n="5"/></div>
+  </text>
+</TEI>"#;
+
+    fn eval<'a, 'i>(doc: &'a Document<'i>, expr: &str) -> Value<'a, 'i> {
+        let xpath = XPath::parse(expr).unwrap();
+        Evaluator::new()
+            .evaluate(&xpath, XNode::Node(doc.root()))
+            .unwrap()
+    }
+
+    fn strings(doc: &Document, expr: &str) -> Vec<String> {
+        match eval(doc, expr) {
+            Value::Nodes(nodes) => nodes.iter().map(|n| n.string_value()).collect(),
+            other => vec![other.to_string_value()],
+        }
+    }
+
+    #[test]
+    fn test_paths_and_predicates() {
+        let doc = Document::parse(XML).unwrap();
+
+        assert_eq!(
+            strings(&doc, "/TEI/teiHeader//title[@type='main']"),
+            vec!["Main"]
+        );
+        assert_eq!(strings(&doc, "//div[@type]/head"), vec!["One"]);
+        assert_eq!(strings(&doc, "//div[2]/p"), vec!["c"]);
+        assert_eq!(strings(&doc, "//p[last()]"), vec!["b", "c"]);
+        assert_eq!(strings(&doc, "(//p)[last()]"), vec!["c"]);
+        assert_eq!(strings(&doc, "//div[@n > 1]/@n"), vec!["2"]);
+        assert_eq!(
+            strings(&doc, "//p[. = 'b']/preceding-sibling::*[1]"),
+            vec!["a"]
+        );
+        assert_eq!(strings(&doc, "//pb/ancestor::div/@n"), vec!["2"]);
+        assert_eq!(strings(&doc, "/TEI/@xml:id"), vec!["t"]);
+        // Unprefixed name tests match local names in any namespace
+        assert_eq!(strings(&doc, "/TEI/@id"), vec!["t"]);
+    }
+
+    #[test]
+    fn test_functions() {
+        let doc = Document::parse(XML).unwrap();
+
+        assert_eq!(strings(&doc, "count(//p)"), vec!["3"]);
+        assert_eq!(strings(&doc, "sum(//div/@n) div 2"), vec!["1.5"]);
+        assert_eq!(
+            strings(&doc, "concat(name(/*), '-', string-length('abc'))"),
+            vec!["TEI-3"]
+        );
+        assert_eq!(strings(&doc, "normalize-space('  a   b ')"), vec!["a b"]);
+        assert_eq!(strings(&doc, "substring('12345', 2, 3)"), vec!["234"]);
+        assert_eq!(strings(&doc, "translate('abc', 'ab', 'A')"), vec!["Ac"]);
+        assert_eq!(strings(&doc, "not(//lg)"), vec!["true"]);
+        assert_eq!(
+            strings(&doc, "//p[contains(., 'b')] | //head"),
+            vec!["One", "b"]
+        );
+        assert_eq!(strings(&doc, "local-name(id('t'))"), vec!["TEI"]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let doc = Document::parse(XML).unwrap();
+
+        assert_eq!(strings(&doc, "matches('12', '^[0-9]+$')"), vec!["true"]);
+        assert_eq!(strings(&doc, "matches('12a', '^[0-9]+$')"), vec!["false"]);
+        assert_eq!(strings(&doc, "matches('a12', '[0-9]+$')"), vec!["true"]);
+        assert_eq!(strings(&doc, "matches('12a', '^\\d+')"), vec!["true"]);
+        assert_eq!(strings(&doc, "matches('x', '[a-z-[aeiou]]')"), vec!["true"]);
+        assert_eq!(
+            strings(&doc, "matches('e', '[a-z-[aeiou]]')"),
+            vec!["false"]
+        );
+        assert_eq!(strings(&doc, "matches('ABC', '^abc$', 'i')"), vec!["true"]);
+        assert_eq!(strings(&doc, "matches('a.c', 'a.c', 'q')"), vec!["true"]);
+        assert_eq!(strings(&doc, "matches('abc', 'a.c', 'q')"), vec!["false"]);
+        assert_eq!(strings(&doc, "matches('abc', 'a b c', 'x')"), vec!["true"]);
+        assert_eq!(strings(&doc, "//div[matches(@n, '^[2-9]$')]/p"), vec!["c"]);
+        // Computed patterns are compiled when evaluated
+        assert_eq!(
+            strings(&doc, "matches('chapter', concat('^', //div[1]/@type))"),
+            vec!["true"]
+        );
+
+        assert!(matches!(
+            XPath::parse("matches(@n, '[0-9')"),
+            Err(XPathError::Regex { .. })
+        ));
+        assert!(matches!(
+            XPath::parse("matches(@n, 'a', 'z')"),
+            Err(XPathError::Regex { .. })
+        ));
+        let computed = XPath::parse("matches('a', concat('(', 'a'))").unwrap();
+        assert!(matches!(
+            Evaluator::new().evaluate(&computed, XNode::Node(doc.root())),
+            Err(XPathError::Regex { .. })
+        ));
+    }
+
+    #[test]
+    fn test_function_list() {
+        let doc = Document::parse(XML).unwrap();
+        for name in FUNCTIONS {
+            let xpath = XPath::parse(&format!("{}(1, 2, 3, 4)", name)).unwrap();
+            let result = Evaluator::new().evaluate(&xpath, XNode::Node(doc.root()));
+            assert!(
+                !matches!(result, Err(XPathError::UnknownFunction(_))),
+                "{}",
+                name
+            );
+        }
+        let xpath = XPath::parse("tokenize(., ' ')").unwrap();
+        assert_eq!(xpath.functions(), vec!["tokenize"]);
+    }
+
+    #[test]
+    fn test_variables() {
+        let doc = Document::parse(XML).unwrap();
+        let xpath = XPath::parse("//div[@n = $n]/p").unwrap();
+        let mut evaluator = Evaluator::new();
+        evaluator.set_variable("n", Value::Number(2.0));
+
+        let value = evaluator.evaluate(&xpath, XNode::Node(doc.root())).unwrap();
+        assert_eq!(value.to_string_value(), "c");
+
+        let unbound = Evaluator::new().evaluate(&xpath, XNode::Node(doc.root()));
+        assert_eq!(unbound, Err(XPathError::UnboundVariable("n".to_string())));
+    }
+
+    #[test]
+    fn test_positions_and_fragments() {
+        let doc = Document::parse(XML).unwrap();
+        let nodes = match eval(&doc, "//pb/@n | //head") {
+            Value::Nodes(nodes) => nodes,
+            _ => panic!("expected nodes"),
+        };
+
+        assert_eq!(nodes[0].fragment(), "<head>One</head>");
+        assert_eq!(nodes[0].position(), (4, 31));
+        assert_eq!(nodes[1].fragment(), "n=\"5\"");
+    }
+}
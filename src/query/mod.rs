@@ -0,0 +1,181 @@
+//! Structural queries over a corpus: an XPath 1.0 subset evaluated per
+//! file, and signature-style patterns matched against skeletons.
+//!
+//! ```
+//! use xml_structer::query::{query_document, XPath};
+//!
+//! let xpath = XPath::parse("//div[@type='chapter']/head").unwrap();
+//! let matches = query_document("<TEI><div type='chapter'><head>I</head></div></TEI>", &xpath).unwrap();
+//! assert_eq!(matches[0].fragment, "<head>I</head>");
+//! ```
+
+pub mod eval;
+pub mod pattern;
+pub(crate) mod regex;
+pub mod xpath;
+
+pub use eval::{Evaluator, Value, XNode, FUNCTIONS};
+pub use pattern::{PatternError, StructurePattern};
+pub use xpath::{XPath, XPathError};
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use roxmltree::{Document, ParsingOptions};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tracing::error;
+
+/// One query hit inside a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryMatch {
+    pub file: String,
+
+    /// 1-based position of the match; 0 for scalar results
+    pub line: usize,
+    pub column: usize,
+
+    /// Serialized node (source markup), `name="value"` for attributes, or
+    /// the string value of a scalar result such as `count(//p)`
+    pub fragment: String,
+}
+
+/// A file that could not be queried
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryFailure {
+    pub file: String,
+    pub message: String,
+}
+
+/// Matches of a query over many files, in input file order
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub matches: Vec<QueryMatch>,
+    pub failures: Vec<QueryFailure>,
+}
+
+impl QueryResult {
+    /// Number of files with at least one match
+    pub fn matched_files(&self) -> usize {
+        let mut files: Vec<&str> = self.matches.iter().map(|m| m.file.as_str()).collect();
+        files.dedup();
+        files.len()
+    }
+}
+
+/// Evaluate `xpath` against one document. Matches carry an empty `file`.
+pub fn query_document(xml: &str, xpath: &XPath) -> Result<Vec<QueryMatch>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let doc = Document::parse_with_options(xml, options).context("Failed to parse XML document")?;
+    let value = Evaluator::new().evaluate(xpath, XNode::Node(doc.root()))?;
+
+    Ok(match value {
+        Value::Nodes(nodes) => nodes
+            .iter()
+            .map(|node| {
+                let (line, column) = node.position();
+                QueryMatch {
+                    file: String::new(),
+                    line,
+                    column,
+                    fragment: node.fragment().to_string(),
+                }
+            })
+            .collect(),
+        scalar => vec![QueryMatch {
+            file: String::new(),
+            line: 0,
+            column: 0,
+            fragment: scalar.to_string_value(),
+        }],
+    })
+}
+
+/// Evaluate `xpath` against every file in parallel
+pub fn query_files(
+    file_paths: &[String],
+    xpath: &XPath,
+    progress_bar: Option<ProgressBar>,
+) -> QueryResult {
+    let per_file: Vec<Result<Vec<QueryMatch>>> = file_paths
+        .par_iter()
+        .map(|file_path| {
+            let result = fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read file: {}", file_path))
+                .and_then(|xml| query_document(&xml, xpath));
+            if let Some(ref pb) = progress_bar {
+                pb.inc(1);
+            }
+            result
+        })
+        .collect();
+
+    if let Some(ref pb) = progress_bar {
+        pb.finish_with_message("Query complete");
+    }
+
+    let mut result = QueryResult::default();
+    for (file_path, outcome) in file_paths.iter().zip(per_file) {
+        match outcome {
+            Ok(matches) => result
+                .matches
+                .extend(matches.into_iter().map(|m| QueryMatch {
+                    file: file_path.clone(),
+                    ..m
+                })),
+            Err(e) => {
+                error!("Failed to query {}: {:#}", file_path, e);
+                result.failures.push(QueryFailure {
+                    file: file_path.clone(),
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_query_document_scalar() {
+        let xpath = XPath::parse("count(//p)").unwrap();
+        let matches = query_document("<a><p/><p/></a>", &xpath).unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].fragment, "2");
+        assert_eq!(matches[0].line, 0);
+    }
+
+    #[test]
+    fn test_query_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.xml");
+        let b = temp_dir.path().join("b.xml");
+        let c = temp_dir.path().join("c.xml");
+        fs::write(&a, "<TEI>\n  <pb n=\"1\"/>\n  <pb n=\"2\"/>\n</TEI>").unwrap();
+        fs::write(&b, "<TEI/>").unwrap();
+        fs::write(&c, "<TEI>").unwrap();
+        let files: Vec<String> = [&a, &b, &c]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        let result = query_files(&files, &XPath::parse("//pb/@n").unwrap(), None);
+
+        assert_eq!(result.matches.len(), 2);
+        assert_eq!(result.matched_files(), 1);
+        assert_eq!(result.matches[1].file, files[0]);
+        assert_eq!(result.matches[1].line, 3);
+        assert_eq!(result.matches[1].column, 7);
+        assert_eq!(result.matches[1].fragment, "n=\"2\"");
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].file, files[2]);
+    }
+}
@@ -0,0 +1,433 @@
+//! Structural patterns in the `XmlStructure::signature()` syntax.
+//!
+//! ```text
+//! TEI{teiHeader,text{body{div[type]{head?,*}}}}
+//! ```
+//!
+//! - `name` matches an element by local name, `*` matches any name and
+//!   `head|label` matches either name.
+//! - `[type,n]` requires the attribute keys; `[!ana]` forbids one. Other
+//!   attribute keys are allowed.
+//! - `{a,b}` lists the child elements. The list is exact: every child must
+//!   match some item and every item must match some child, except items
+//!   marked optional with a trailing `?`. A bare `*` item allows any other
+//!   children. `{}` means no child elements; without braces the children
+//!   are unconstrained.
+//!
+//! A signature string is itself a pattern that matches its structure.
+
+use crate::processor::{ProcessingResult, SkeletonSignature, StructureGroup, XmlStructure};
+use crate::struct_processor::parse_xml_structure;
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::error;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("pattern syntax error at offset {pos}: {msg}")]
+pub struct PatternError {
+    pub pos: usize,
+    pub msg: String,
+}
+
+/// A parsed structural pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructurePattern {
+    source: String,
+    root: ElementPattern,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ElementPattern {
+    /// Accepted names; empty for `*`
+    names: Vec<String>,
+    required_attributes: Vec<String>,
+    forbidden_attributes: Vec<String>,
+    children: Option<ChildList>,
+    optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChildList {
+    items: Vec<ElementPattern>,
+    /// A bare `*` item allows children not matched by any item
+    open: bool,
+}
+
+impl ElementPattern {
+    fn is_bare_wildcard(&self) -> bool {
+        self.names.is_empty()
+            && self.required_attributes.is_empty()
+            && self.forbidden_attributes.is_empty()
+            && self.children.is_none()
+    }
+
+    fn matches(&self, node: &XmlStructure) -> bool {
+        if !self.names.is_empty() && !self.names.contains(&node.name) {
+            return false;
+        }
+
+        let has = |key: &String| {
+            node.attributes
+                .as_ref()
+                .is_some_and(|a| a.contains_key(key))
+        };
+        if !self.required_attributes.iter().all(has) || self.forbidden_attributes.iter().any(has) {
+            return false;
+        }
+
+        let Some(list) = &self.children else {
+            return true;
+        };
+        let required_present = list
+            .items
+            .iter()
+            .filter(|item| !item.optional)
+            .all(|item| node.children.iter().any(|c| item.matches(c)));
+        let all_accounted = list.open
+            || node
+                .children
+                .iter()
+                .all(|c| list.items.iter().any(|item| item.matches(c)));
+        required_present && all_accounted
+    }
+}
+
+impl StructurePattern {
+    pub fn parse(source: &str) -> Result<Self, PatternError> {
+        let mut parser = Parser {
+            input: source.as_bytes(),
+            pos: 0,
+        };
+        let root = parser.parse_element()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Original pattern text
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Match a full structure (repeated children are checked one by one)
+    pub fn matches_structure(&self, structure: &XmlStructure) -> bool {
+        self.root.matches(structure)
+    }
+
+    /// Match a merged skeleton; each child name stands for all its instances
+    pub fn matches_skeleton(&self, skeleton: &SkeletonSignature) -> bool {
+        self.root
+            .matches(&skeleton_structure(&skeleton.root, &skeleton.skeleton))
+    }
+
+    /// Groups of a processing result whose skeleton matches, in result order
+    pub fn matching_groups<'a>(&self, result: &'a ProcessingResult) -> Vec<&'a StructureGroup> {
+        result
+            .groups
+            .iter()
+            .filter(|g| self.matches_skeleton(&g.skeleton))
+            .collect()
+    }
+
+    /// Files whose structure matches, in input order. Files that fail to
+    /// parse are logged and skipped.
+    pub fn matching_files(
+        &self,
+        file_paths: &[String],
+        progress_bar: Option<ProgressBar>,
+    ) -> Vec<String> {
+        let matched: Vec<bool> = file_paths
+            .par_iter()
+            .map(|file_path| {
+                let result = fs::read_to_string(file_path)
+                    .with_context(|| format!("Failed to read file: {}", file_path))
+                    .and_then(|xml| parse_xml_structure(&xml));
+                if let Some(ref pb) = progress_bar {
+                    pb.inc(1);
+                }
+                match result {
+                    Ok(structure) => self.matches_structure(&structure),
+                    Err(e) => {
+                        error!("Failed to match {}: {:#}", file_path, e);
+                        false
+                    }
+                }
+            })
+            .collect();
+
+        if let Some(ref pb) = progress_bar {
+            pb.finish_with_message("Matching complete");
+        }
+
+        file_paths
+            .iter()
+            .zip(matched)
+            .filter(|(_, m)| *m)
+            .map(|(f, _)| f.clone())
+            .collect()
+    }
+}
+
+impl FromStr for StructurePattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for StructurePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Rebuild an `XmlStructure` from skeleton JSON (one child per name)
+fn skeleton_structure(name: &str, node: &Value) -> XmlStructure {
+    let mut structure = XmlStructure::new(name.to_string());
+    let Some(map) = node.as_object() else {
+        return structure;
+    };
+    for (key, value) in map {
+        if key == "@attributes" {
+            for attr in value.as_array().into_iter().flatten() {
+                if let Some(attr) = attr.as_str() {
+                    structure.add_attribute(attr.to_string());
+                }
+            }
+        } else {
+            structure.add_child(skeleton_structure(key, value));
+        }
+    }
+    structure
+}
+
+struct Parser<'s> {
+    input: &'s [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> PatternError {
+        PatternError {
+            pos: self.pos,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), PatternError> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, PatternError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b"_-.:".contains(b) || *b >= 0x80)
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a name"));
+        }
+        // Names are made of whole UTF-8 sequences, so this can't split a char
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn parse_element(&mut self) -> Result<ElementPattern, PatternError> {
+        let mut names = Vec::new();
+        let mut any = false;
+        loop {
+            if self.eat(b'*') {
+                any = true;
+            } else {
+                names.push(self.name()?);
+            }
+            if !self.eat(b'|') {
+                break;
+            }
+        }
+        if any {
+            names.clear();
+        }
+
+        let mut required_attributes = Vec::new();
+        let mut forbidden_attributes = Vec::new();
+        if self.eat(b'[') {
+            loop {
+                if self.eat(b'!') {
+                    forbidden_attributes.push(self.name()?);
+                } else {
+                    required_attributes.push(self.name()?);
+                }
+                if !self.eat(b',') {
+                    break;
+                }
+            }
+            self.expect(b']')?;
+        }
+
+        let children = if self.eat(b'{') {
+            let mut list = ChildList {
+                items: Vec::new(),
+                open: false,
+            };
+            if !self.eat(b'}') {
+                loop {
+                    let item = self.parse_element()?;
+                    if item.is_bare_wildcard() {
+                        list.open = true;
+                    } else {
+                        list.items.push(item);
+                    }
+                    if !self.eat(b',') {
+                        break;
+                    }
+                }
+                self.expect(b'}')?;
+            }
+            Some(list)
+        } else {
+            None
+        };
+
+        let optional = self.eat(b'?');
+
+        Ok(ElementPattern {
+            names,
+            required_attributes,
+            forbidden_attributes,
+            children,
+            optional,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structure(xml: &str) -> XmlStructure {
+        parse_xml_structure(xml).unwrap()
+    }
+
+    fn matches(pattern: &str, xml: &str) -> bool {
+        StructurePattern::parse(pattern)
+            .unwrap()
+            .matches_structure(&structure(xml))
+    }
+
+    #[test]
+    fn test_signature_is_a_pattern() {
+        let s = structure(
+            r#"<TEI><teiHeader/><text><body><div type="a" n="1"><head/><p/><p/></div></body></text></TEI>"#,
+        );
+        let pattern = StructurePattern::parse(&s.signature()).unwrap();
+
+        assert!(pattern.matches_structure(&s));
+        assert!(pattern.matches_skeleton(&s.to_skeleton()));
+    }
+
+    #[test]
+    fn test_children_and_wildcards() {
+        let xml =
+            r#"<TEI><teiHeader/><text><body><div type="ch"><head/><p/></div></body></text></TEI>"#;
+
+        assert!(matches(
+            "TEI{teiHeader,text{body{div[type]{head?,*}}}}",
+            xml
+        ));
+        assert!(matches("TEI{teiHeader,text}", xml));
+        assert!(matches("*{text{body{div{head|label,p}}},*}", xml));
+        // Exact child list: <p> is not accounted for
+        assert!(!matches("TEI{teiHeader,text{body{div{head}}}}", xml));
+        // Required child missing
+        assert!(!matches("TEI{teiHeader,text,facsimile}", xml));
+        assert!(matches("TEI{teiHeader,text,facsimile?}", xml));
+        assert!(!matches("TEI{teiHeader,text{}}", xml));
+        assert!(!matches("TEI{*{*{div[!type]}}, *}", xml));
+    }
+
+    #[test]
+    fn test_repeated_children() {
+        let xml = r#"<body><div type="a"><head/></div><div/></body>"#;
+
+        // Some div has a type and a head...
+        assert!(matches("body{div[type]{head},*}", xml));
+        // ...but the untyped div isn't covered by an exact list
+        assert!(!matches("body{div[type]{head}}", xml));
+        assert!(matches("body{div[type]{head},div{}}", xml));
+    }
+
+    #[test]
+    fn test_matching_groups_and_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.xml");
+        let b = temp_dir.path().join("b.xml");
+        fs::write(&a, r#"<TEI><text><div type="x"><head/></div></text></TEI>"#).unwrap();
+        fs::write(&b, r#"<TEI><text><div/></text></TEI>"#).unwrap();
+        let files = vec![
+            a.to_string_lossy().to_string(),
+            b.to_string_lossy().to_string(),
+        ];
+        let pattern: StructurePattern = "TEI{text{div[type]{head}}}".parse().unwrap();
+
+        assert_eq!(pattern.matching_files(&files, None), vec![files[0].clone()]);
+
+        let result = crate::struct_processor::process_xml_files(files.clone(), None).unwrap();
+        let groups = pattern.matching_groups(&result);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![files[0].clone()]);
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        for (pattern, pos) in [
+            ("TEI{", 4),
+            ("TEI[]", 4),
+            ("TEI}", 3),
+            ("", 0),
+            ("a{b,}", 4),
+        ] {
+            let err = StructurePattern::parse(pattern).unwrap_err();
+            assert_eq!(err.pos, pos, "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Regular expressions of `matches(string, pattern[, flags])`.
+//!
+//! XPath patterns follow XML Schema syntax plus the `^`/`$` anchors; they
+//! are translated to `regex` syntax: the `\i`/`\c` name character escapes and
+//! class subtraction `[a-z-[aeiou]]`. The flags `s`, `m`, `i`, `x` and `q`
+//! are supported. Back-references are not, and give an error.
+
+use super::xpath::XPathError;
+use regex_automata::meta::Regex;
+use regex_automata::util::syntax;
+
+/// A compiled `matches()` pattern
+#[derive(Debug, Clone)]
+pub(crate) struct XPathRegex {
+    pattern: String,
+    flags: String,
+    regex: Regex,
+}
+
+impl PartialEq for XPathRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.flags == other.flags
+    }
+}
+
+impl XPathRegex {
+    pub(crate) fn new(pattern: &str, flags: &str) -> Result<Self, XPathError> {
+        let error = |msg: String| XPathError::Regex {
+            pattern: pattern.to_string(),
+            msg,
+        };
+        let mut config = syntax::Config::new();
+        let (mut literal, mut extended) = (false, false);
+        for flag in flags.chars() {
+            match flag {
+                's' => config = config.dot_matches_new_line(true),
+                'm' => config = config.multi_line(true),
+                'i' => config = config.case_insensitive(true),
+                'x' => extended = true,
+                'q' => literal = true,
+                other => return Err(error(format!("unknown flag '{}'", other))),
+            }
+        }
+        let translated = if literal {
+            escape(pattern)
+        } else {
+            translate(pattern, extended)
+        };
+        let regex = Regex::builder()
+            .syntax(config)
+            .build(&translated)
+            .map_err(|e| error(e.to_string()))?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            flags: flags.to_string(),
+            regex,
+        })
+    }
+
+    /// Whether the pattern matches anywhere in `s`
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        self.regex.is_match(s)
+    }
+}
+
+/// Translate an XPath pattern to `regex` syntax; with `extended` (the `x`
+/// flag), whitespace outside character classes is dropped
+fn translate(pattern: &str, extended: bool) -> String {
+    const NAME_START: &str = r"_:\p{L}";
+    const NAME_CHAR: &str = r"\-._:\p{L}\p{M}\p{N}\x{B7}";
+
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    let mut class_depth = 0usize;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('i') if class_depth > 0 => out.push_str(NAME_START),
+                Some('c') if class_depth > 0 => out.push_str(NAME_CHAR),
+                Some('i') => out.push_str(&format!("[{}]", NAME_START)),
+                Some('c') => out.push_str(&format!("[{}]", NAME_CHAR)),
+                Some('I') => out.push_str(&format!("[^{}]", NAME_START)),
+                Some('C') => out.push_str(&format!("[^{}]", NAME_CHAR)),
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push_str(r"\\"),
+            },
+            '[' => {
+                class_depth += 1;
+                out.push('[');
+            }
+            ']' if class_depth > 0 => {
+                class_depth -= 1;
+                out.push(']');
+            }
+            '-' if class_depth > 0 && chars.peek() == Some(&'[') => out.push_str("--"),
+            c if extended && class_depth == 0 && c.is_whitespace() => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `pattern` as a literal string (the `q` flag)
+fn escape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,941 @@
+//! Parser for the supported XPath 1.0 subset.
+//!
+//! Supported: absolute and relative location paths, `//`, `.`, `..`, `@`,
+//! `*`, the axes `child`, `descendant`, `descendant-or-self`, `parent`,
+//! `ancestor`, `ancestor-or-self`, `self`, `following-sibling`,
+//! `preceding-sibling` and `attribute`, the node tests `node()`, `text()`
+//! and `comment()`, predicates (boolean and positional), unions, the usual
+//! boolean/comparison/arithmetic operators, variables and a core function
+//! library (see `eval`).
+//!
+//! Name tests compare local names only, so `//div` matches TEI's
+//! namespaced `<div>` without prefix bindings. The `xml` prefix is the
+//! exception: `@xml:id` only matches attributes in the XML namespace.
+
+use super::regex::XPathRegex;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum XPathError {
+    #[error("XPath syntax error at offset {pos}: {msg}")]
+    Syntax { pos: usize, msg: String },
+
+    #[error("unknown XPath function {0}()")]
+    UnknownFunction(String),
+
+    #[error("wrong number of arguments for {0}()")]
+    Arity(String),
+
+    #[error("XPath type error: {0}")]
+    Type(String),
+
+    #[error("unbound XPath variable ${0}")]
+    UnboundVariable(String),
+
+    #[error("invalid regular expression {pattern:?}: {msg}")]
+    Regex { pattern: String, msg: String },
+}
+
+/// A parsed XPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    source: String,
+    pub(crate) expr: Expr,
+}
+
+impl XPath {
+    pub fn parse(source: &str) -> Result<Self, XPathError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_expr()?;
+        if let Some((tok, pos)) = parser.peek_with_pos() {
+            return Err(XPathError::Syntax {
+                pos,
+                msg: format!("unexpected {:?}", tok),
+            });
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// Parse an XSLT-style match pattern (as in Schematron rule contexts):
+    /// relative paths match at any depth, so `div/head` selects like
+    /// `//div/head` when evaluated from the document node
+    pub fn parse_pattern(source: &str) -> Result<Self, XPathError> {
+        let mut xpath = Self::parse(source)?;
+        xpath.expr = anchor_pattern(xpath.expr);
+        Ok(xpath)
+    }
+
+    /// Original expression text
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Names of the functions the expression calls, in source order
+    pub fn functions(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        collect_functions(&self.expr, &mut names);
+        names
+    }
+}
+
+fn collect_functions<'e>(expr: &'e Expr, names: &mut Vec<&'e str>) {
+    match expr {
+        Expr::Or(l, r) | Expr::And(l, r) | Expr::Binary(_, l, r) | Expr::Union(l, r) => {
+            collect_functions(l, names);
+            collect_functions(r, names);
+        }
+        Expr::Negate(e) => collect_functions(e, names),
+        Expr::Path(start, path) => {
+            if let Some(start) = start {
+                collect_functions(start, names);
+            }
+            for predicate in path.steps.iter().flat_map(|s| &s.predicates) {
+                collect_functions(predicate, names);
+            }
+        }
+        Expr::Filter(primary, predicates) => {
+            collect_functions(primary, names);
+            for predicate in predicates {
+                collect_functions(predicate, names);
+            }
+        }
+        Expr::Function(name, args) => {
+            names.push(name);
+            for arg in args {
+                collect_functions(arg, names);
+            }
+        }
+        Expr::Matches(input, _) => {
+            names.push("matches");
+            collect_functions(input, names);
+        }
+        Expr::Literal(_) | Expr::Number(_) | Expr::Variable(_) => {}
+    }
+}
+
+impl FromStr for XPath {
+    type Err = XPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for XPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    /// A location path, optionally starting from a filter expression
+    Path(Option<Box<Expr>>, LocationPath),
+    Filter(Box<Expr>, Vec<Expr>),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    Function(String, Vec<Expr>),
+    /// `matches()` with a literal pattern (and flags), compiled once
+    Matches(Box<Expr>, Box<XPathRegex>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinaryOp {
+    Eq,
+    Neq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LocationPath {
+    pub absolute: bool,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Step {
+    pub axis: Axis,
+    pub test: NodeTest,
+    pub predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Axis {
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Parent,
+    Ancestor,
+    AncestorOrSelf,
+    SelfNode,
+    FollowingSibling,
+    PrecedingSibling,
+    Attribute,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "child" => Axis::Child,
+            "descendant" => Axis::Descendant,
+            "descendant-or-self" => Axis::DescendantOrSelf,
+            "parent" => Axis::Parent,
+            "ancestor" => Axis::Ancestor,
+            "ancestor-or-self" => Axis::AncestorOrSelf,
+            "self" => Axis::SelfNode,
+            "following-sibling" => Axis::FollowingSibling,
+            "preceding-sibling" => Axis::PrecedingSibling,
+            "attribute" => Axis::Attribute,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum NodeTest {
+    /// `*`
+    Any,
+    /// `name` or `prefix:name`
+    Name {
+        prefix: Option<String>,
+        local: String,
+    },
+    /// `node()`
+    Node,
+    /// `text()`
+    Text,
+    /// `comment()`
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    At,
+    Comma,
+    Dot,
+    DotDot,
+    Pipe,
+    Plus,
+    Minus,
+    Star,
+    Eq,
+    Neq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    DoubleColon,
+    Dollar,
+    Literal(String),
+    Number(f64),
+    /// NCName or QName (`prefix:local`)
+    Name(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, XPathError> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let tok = match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i += 2;
+                Token::DoubleSlash
+            }
+            b'/' => {
+                i += 1;
+                Token::Slash
+            }
+            b'[' => {
+                i += 1;
+                Token::LBracket
+            }
+            b']' => {
+                i += 1;
+                Token::RBracket
+            }
+            b'(' => {
+                i += 1;
+                Token::LParen
+            }
+            b')' => {
+                i += 1;
+                Token::RParen
+            }
+            b'@' => {
+                i += 1;
+                Token::At
+            }
+            b',' => {
+                i += 1;
+                Token::Comma
+            }
+            b'|' => {
+                i += 1;
+                Token::Pipe
+            }
+            b'+' => {
+                i += 1;
+                Token::Plus
+            }
+            b'-' => {
+                i += 1;
+                Token::Minus
+            }
+            b'*' => {
+                i += 1;
+                Token::Star
+            }
+            b'$' => {
+                i += 1;
+                Token::Dollar
+            }
+            b'=' => {
+                i += 1;
+                Token::Eq
+            }
+            b'!' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::Neq
+            }
+            b'<' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::Le
+            }
+            b'<' => {
+                i += 1;
+                Token::Lt
+            }
+            b'>' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::Ge
+            }
+            b'>' => {
+                i += 1;
+                Token::Gt
+            }
+            b':' if bytes.get(i + 1) == Some(&b':') => {
+                i += 2;
+                Token::DoubleColon
+            }
+            b'"' | b'\'' => {
+                let end = input[i + 1..]
+                    .find(c as char)
+                    .ok_or_else(|| XPathError::Syntax {
+                        pos: i,
+                        msg: "unterminated string literal".to_string(),
+                    })?;
+                let literal = input[i + 1..i + 1 + end].to_string();
+                i += end + 2;
+                Token::Literal(literal)
+            }
+            b'.' if bytes.get(i + 1) == Some(&b'.') => {
+                i += 2;
+                Token::DotDot
+            }
+            b'.' if !bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit()) => {
+                i += 1;
+                Token::Dot
+            }
+            b'0'..=b'9' | b'.' => {
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                let number = input[start..i].parse().map_err(|_| XPathError::Syntax {
+                    pos: start,
+                    msg: format!("invalid number '{}'", &input[start..i]),
+                })?;
+                Token::Number(number)
+            }
+            _ if is_name_start(input[i..].chars().next().unwrap()) => {
+                i = scan_ncname(input, i);
+                // QName: prefix:local (but not an axis `name::`)
+                if bytes.get(i) == Some(&b':')
+                    && bytes.get(i + 1) != Some(&b':')
+                    && input[i + 1..].chars().next().is_some_and(is_name_start)
+                {
+                    i = scan_ncname(input, i + 1);
+                } else if bytes.get(i) == Some(&b':') && bytes.get(i + 1) == Some(&b'*') {
+                    // prefix:* is treated like *
+                    i += 2;
+                    tokens.push((Token::Star, start));
+                    continue;
+                }
+                Token::Name(input[start..i].to_string())
+            }
+            _ => {
+                return Err(XPathError::Syntax {
+                    pos: i,
+                    msg: format!(
+                        "unexpected character '{}'",
+                        input[i..].chars().next().unwrap()
+                    ),
+                })
+            }
+        };
+        tokens.push((tok, start));
+    }
+
+    Ok(tokens)
+}
+
+/// `NameStartChar` of XML 1.0 (fifth edition), without `:`
+fn is_name_start(c: char) -> bool {
+    matches!(c,
+        'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+}
+
+/// `NameChar` of XML 1.0 (fifth edition), without `:`
+fn is_name_char(c: char) -> bool {
+    is_name_start(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+
+fn scan_ncname(input: &str, start: usize) -> usize {
+    let mut end = start;
+    for (offset, c) in input[start..].char_indices() {
+        if is_name_char(c) {
+            end = start + offset + c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(t, _)| t)
+    }
+
+    fn peek_with_pos(&self) -> Option<(&Token, usize)> {
+        self.tokens.get(self.pos).map(|(t, p)| (t, *p))
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, p)| *p)
+            .unwrap_or_else(|| self.tokens.last().map(|(_, p)| p + 1).unwrap_or(0))
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        tok
+    }
+
+    fn eat(&mut self, tok: &Token) -> bool {
+        if self.peek() == Some(tok) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_name(&mut self, name: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Name(n)) if n == name) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, tok: &Token) -> Result<(), XPathError> {
+        if self.eat(tok) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {:?}", tok)))
+        }
+    }
+
+    fn error(&self, msg: String) -> XPathError {
+        let msg = match self.peek() {
+            Some(tok) => format!("{}, found {:?}", msg, tok),
+            None => format!("{}, found end of expression", msg),
+        };
+        XPathError::Syntax {
+            pos: self.offset(),
+            msg,
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_and()?;
+        while self.eat_name("or") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_equality()?;
+        while self.eat_name("and") {
+            let right = self.parse_equality()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_equality(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_relational()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Eq) => BinaryOp::Eq,
+                Some(Token::Neq) => BinaryOp::Neq,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_relational()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_relational(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_additive()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Lt) => BinaryOp::Lt,
+                Some(Token::Le) => BinaryOp::Le,
+                Some(Token::Gt) => BinaryOp::Gt,
+                Some(Token::Ge) => BinaryOp::Ge,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_additive()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            // After an operand, `*`, `div` and `mod` are operators
+            let op = match self.peek() {
+                Some(Token::Star) => BinaryOp::Mul,
+                Some(Token::Name(n)) if n == "div" => BinaryOp::Div,
+                Some(Token::Name(n)) if n == "mod" => BinaryOp::Mod,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, XPathError> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_union()
+    }
+
+    fn parse_union(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.parse_path()?;
+        while self.eat(&Token::Pipe) {
+            let right = self.parse_path()?;
+            left = Expr::Union(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn starts_filter_expr(&self) -> bool {
+        match self.peek() {
+            Some(Token::Literal(_))
+            | Some(Token::Number(_))
+            | Some(Token::LParen)
+            | Some(Token::Dollar) => true,
+            Some(Token::Name(name)) => {
+                self.peek_at(1) == Some(&Token::LParen) && !is_node_type(name)
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_path(&mut self) -> Result<Expr, XPathError> {
+        if !self.starts_filter_expr() {
+            return Ok(Expr::Path(None, self.parse_location_path()?));
+        }
+
+        let primary = self.parse_primary()?;
+        let mut predicates = Vec::new();
+        while self.peek() == Some(&Token::LBracket) {
+            predicates.push(self.parse_predicate()?);
+        }
+        let filter = if predicates.is_empty() {
+            primary
+        } else {
+            Expr::Filter(Box::new(primary), predicates)
+        };
+
+        let mut steps = Vec::new();
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+            }
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                steps.push(descendant_or_self_step());
+            }
+            _ => return Ok(filter),
+        }
+        self.parse_relative_steps(&mut steps)?;
+        Ok(Expr::Path(
+            Some(Box::new(filter)),
+            LocationPath {
+                absolute: false,
+                steps,
+            },
+        ))
+    }
+
+    fn parse_location_path(&mut self) -> Result<LocationPath, XPathError> {
+        let mut steps = Vec::new();
+        let absolute = match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+                // A lone `/` selects the root node
+                if !self.starts_step() {
+                    return Ok(LocationPath {
+                        absolute: true,
+                        steps,
+                    });
+                }
+                true
+            }
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                steps.push(descendant_or_self_step());
+                true
+            }
+            _ => false,
+        };
+        self.parse_relative_steps(&mut steps)?;
+        Ok(LocationPath { absolute, steps })
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Name(_))
+                | Some(Token::Star)
+                | Some(Token::At)
+                | Some(Token::Dot)
+                | Some(Token::DotDot)
+        )
+    }
+
+    fn parse_relative_steps(&mut self, steps: &mut Vec<Step>) -> Result<(), XPathError> {
+        steps.push(self.parse_step()?);
+        loop {
+            match self.peek() {
+                Some(Token::Slash) => {
+                    self.pos += 1;
+                }
+                Some(Token::DoubleSlash) => {
+                    self.pos += 1;
+                    steps.push(descendant_or_self_step());
+                }
+                _ => return Ok(()),
+            }
+            steps.push(self.parse_step()?);
+        }
+    }
+
+    fn parse_step(&mut self) -> Result<Step, XPathError> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step {
+                axis: Axis::SelfNode,
+                test: NodeTest::Node,
+                predicates: Vec::new(),
+            });
+        }
+        if self.eat(&Token::DotDot) {
+            return Ok(Step {
+                axis: Axis::Parent,
+                test: NodeTest::Node,
+                predicates: Vec::new(),
+            });
+        }
+
+        let axis = if self.eat(&Token::At) {
+            Axis::Attribute
+        } else if let (Some(Token::Name(name)), Some(Token::DoubleColon)) =
+            (self.peek(), self.peek_at(1))
+        {
+            let axis = Axis::from_name(name)
+                .ok_or_else(|| self.error(format!("unsupported axis '{}'", name)))?;
+            self.pos += 2;
+            axis
+        } else {
+            Axis::Child
+        };
+
+        let test = match self.advance() {
+            Some(Token::Star) => NodeTest::Any,
+            Some(Token::Name(name))
+                if is_node_type(&name) && self.peek() == Some(&Token::LParen) =>
+            {
+                self.expect(&Token::LParen)?;
+                self.expect(&Token::RParen)?;
+                match name.as_str() {
+                    "node" => NodeTest::Node,
+                    "text" => NodeTest::Text,
+                    _ => NodeTest::Comment,
+                }
+            }
+            Some(Token::Name(name)) => match name.split_once(':') {
+                Some((prefix, local)) => NodeTest::Name {
+                    prefix: Some(prefix.to_string()),
+                    local: local.to_string(),
+                },
+                None => NodeTest::Name {
+                    prefix: None,
+                    local: name,
+                },
+            },
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a node test".to_string()));
+            }
+        };
+
+        let mut predicates = Vec::new();
+        while self.peek() == Some(&Token::LBracket) {
+            predicates.push(self.parse_predicate()?);
+        }
+
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+
+    fn parse_predicate(&mut self) -> Result<Expr, XPathError> {
+        self.expect(&Token::LBracket)?;
+        let expr = self.parse_expr()?;
+        self.expect(&Token::RBracket)?;
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, XPathError> {
+        match self.advance() {
+            Some(Token::Literal(s)) => Ok(Expr::Literal(s)),
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Dollar) => match self.advance() {
+                Some(Token::Name(name)) => Ok(Expr::Variable(name)),
+                _ => {
+                    self.pos -= 1;
+                    Err(self.error("expected a variable name".to_string()))
+                }
+            },
+            Some(Token::LParen) => {
+                let expr = self.parse_expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => {
+                self.expect(&Token::LParen)?;
+                let mut args = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(&Token::Comma)?;
+                    }
+                }
+                Ok(match (name.as_str(), args.as_slice()) {
+                    ("matches", [_, Expr::Literal(pattern)]) => {
+                        let regex = XPathRegex::new(pattern, "")?;
+                        Expr::Matches(Box::new(args.swap_remove(0)), Box::new(regex))
+                    }
+                    ("matches", [_, Expr::Literal(pattern), Expr::Literal(flags)]) => {
+                        let regex = XPathRegex::new(pattern, flags)?;
+                        Expr::Matches(Box::new(args.swap_remove(0)), Box::new(regex))
+                    }
+                    _ => Expr::Function(name, args),
+                })
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected an expression".to_string()))
+            }
+        }
+    }
+}
+
+fn is_node_type(name: &str) -> bool {
+    matches!(name, "node" | "text" | "comment")
+}
+
+fn anchor_pattern(expr: Expr) -> Expr {
+    match expr {
+        Expr::Union(l, r) => {
+            Expr::Union(Box::new(anchor_pattern(*l)), Box::new(anchor_pattern(*r)))
+        }
+        Expr::Path(None, path) if !path.absolute => {
+            let mut steps = vec![descendant_or_self_step()];
+            steps.extend(path.steps);
+            Expr::Path(
+                None,
+                LocationPath {
+                    absolute: true,
+                    steps,
+                },
+            )
+        }
+        other => other,
+    }
+}
+
+fn descendant_or_self_step() -> Step {
+    Step {
+        axis: Axis::DescendantOrSelf,
+        test: NodeTest::Node,
+        predicates: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_paths() {
+        for expr in [
+            "/TEI/teiHeader//title[@type='main']",
+            "//div[@type][2]/head",
+            "count(//pb)",
+            "//p[not(@rend) and string-length(.) > 0]",
+            "ancestor::div[1]/@n",
+            "//lb | //pb",
+            "(//div)[last()]",
+            "//@xml:id",
+            "$n + 1 div 2",
+            "/",
+        ] {
+            XPath::parse(expr).unwrap_or_else(|e| panic!("{}: {}", expr, e));
+        }
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern = XPath::parse_pattern("div/head | /TEI").unwrap();
+        let Expr::Union(l, r) = &pattern.expr else {
+            panic!("expected union");
+        };
+        assert!(matches!(&**l, Expr::Path(None, p) if p.absolute && p.steps.len() == 3));
+        assert!(matches!(&**r, Expr::Path(None, p) if p.absolute && p.steps.len() == 1));
+        assert_eq!(pattern.as_str(), "div/head | /TEI");
+    }
+
+    #[test]
+    fn test_star_and_div_as_operators() {
+        let xpath = XPath::parse("div * 2").unwrap();
+        assert!(matches!(xpath.expr, Expr::Binary(BinaryOp::Mul, _, _)));
+
+        let xpath = XPath::parse("//div div 2").unwrap();
+        assert!(matches!(xpath.expr, Expr::Binary(BinaryOp::Div, _, _)));
+    }
+
+    #[test]
+    fn test_xml_name_characters() {
+        // U+00B7, a combining mark, U+203F and a non-Latin start character
+        let names = ["a\u{B7}b", "e\u{301}", "x\u{203F}y", "\u{3042}-1.2", "_a"];
+        let xpath = XPath::parse(&format!("/{}", names.join("/"))).unwrap();
+        let Expr::Path(None, path) = &xpath.expr else {
+            panic!("expected a path");
+        };
+        let locals: Vec<&str> = path
+            .steps
+            .iter()
+            .map(|s| match &s.test {
+                NodeTest::Name { local, .. } => local.as_str(),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(locals, names);
+
+        for expr in ["/\u{B7}a", "/\u{301}"] {
+            assert!(XPath::parse(expr).is_err(), "{} should fail", expr);
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        for expr in ["//div[", "foo::bar", "'open", "//", "1 +"] {
+            assert!(
+                matches!(XPath::parse(expr), Err(XPathError::Syntax { .. })),
+                "{} should fail",
+                expr
+            );
+        }
+    }
+}
@@ -7,7 +7,9 @@ use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 pub fn init_logging(level: &str, log_file: Option<&Path>) -> Result<()> {
     let filter = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(level))?;
 
+    // Logs go to stderr so commands can write data to stdout
     let fmt_layer = fmt::layer()
+        .with_writer(std::io::stderr)
         .with_target(false)
         .with_thread_ids(false)
         .with_thread_names(false);
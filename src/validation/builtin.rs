@@ -0,0 +1,265 @@
+//! The built-in TEI rules.
+
+use super::fix::{Edit, Fix};
+use super::pages::PbSequence;
+use super::pointers::{LocalPointers, XmlIdUnique};
+use super::references::CrossFileReferences;
+use super::report::Severity;
+use super::rule::{Rule, RuleContext};
+use roxmltree::Node;
+
+const TEI_NS: &str = "http://www.tei-c.org/ns/1.0";
+
+/// Id of parse errors, which the registry reports before any rule runs
+pub const WELL_FORMED: &str = "xml-well-formed";
+
+pub(crate) fn rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(WellFormed),
+        Box::new(Utf8Bom),
+        Box::new(TeiRoot),
+        Box::new(TeiNamespace),
+        Box::new(PbRequiredAttributes),
+        Box::new(PbSequence::default()),
+        Box::new(HeadInsideDiv),
+        Box::new(XmlIdUnique),
+        Box::new(LocalPointers),
+        Box::new(CrossFileReferences),
+    ]
+}
+
+/// Documents `xml-well-formed` for `explain`; parse errors are reported by
+/// the registry itself since no element can be checked without a tree
+struct WellFormed;
+
+impl Rule for WellFormed {
+    fn id(&self) -> &str {
+        WELL_FORMED
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "The document must be well-formed XML"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Fix the markup; no other rule runs on malformed XML")
+    }
+
+    fn check(&self, _node: Node, _ctx: &mut RuleContext) {}
+}
+
+struct Utf8Bom;
+
+impl Rule for Utf8Bom {
+    fn id(&self) -> &str {
+        "utf8-bom"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn description(&self) -> &str {
+        "UTF-8 files should not start with a byte order mark"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("The BOM is harmless but unnecessary; strip the first three bytes")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_none() && ctx.source().starts_with('\u{FEFF}') {
+            let fix = Fix::new(
+                "Strip the BOM",
+                vec![Edit::delete(0..'\u{FEFF}'.len_utf8())],
+            );
+            ctx.report_with_fix_at(1, 1, "UTF-8 BOM detected (harmless but unnecessary)", fix);
+        }
+    }
+}
+
+struct TeiRoot;
+
+impl Rule for TeiRoot {
+    fn id(&self) -> &str {
+        "tei-root"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn description(&self) -> &str {
+        "The root element should be a TEI element (<TEI>, <teiCorpus>)"
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_none() && !node.tag_name().name().to_lowercase().contains("tei")
+        {
+            ctx.report_at(
+                0,
+                0,
+                format!(
+                    "Root element should contain 'tei' (case-insensitive), found <{}>",
+                    node.tag_name().name()
+                ),
+            );
+        }
+    }
+}
+
+/// A root that looks like TEI but isn't in the TEI namespace fails schema
+/// validation and namespace-aware queries; reported as info since many
+/// legacy corpora are namespace-less throughout
+struct TeiNamespace;
+
+impl Rule for TeiNamespace {
+    fn id(&self) -> &str {
+        "tei-namespace"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn description(&self) -> &str {
+        "A <TEI> or <teiCorpus> root should be in the TEI namespace"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Declare the namespace on the root: <TEI xmlns=\"http://www.tei-c.org/ns/1.0\">")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        let name = node.tag_name().name();
+        if node.parent_element().is_some()
+            || !matches!(name, "TEI" | "teiCorpus")
+            || node.tag_name().namespace().is_some()
+        {
+            return;
+        }
+        let declaration = format!(" xmlns=\"{}\"", TEI_NS);
+        let fix = Fix::new(
+            format!("Add{}", declaration),
+            vec![Edit::insert(name_end(node, ctx.source()), declaration)],
+        );
+        ctx.report_with_fix(node, format!("<{}> is not in the TEI namespace", name), fix);
+    }
+}
+
+/// Byte offset just after the name in the start tag of `node`, where
+/// attributes can be inserted
+fn name_end(node: Node, source: &str) -> usize {
+    let name_start = node.range().start + 1;
+    let len = source[name_start..]
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(0);
+    name_start + len
+}
+
+struct PbRequiredAttributes;
+
+impl Rule for PbRequiredAttributes {
+    fn id(&self) -> &str {
+        "pb-required-attributes"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "Page breaks (<pb>) must carry an edition (@ed) and a page number (@n)"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Add @ed naming the edition and @n with the page number, e.g. <pb ed=\"A\" n=\"12\"/>")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.tag_name().name() != "pb" {
+            return;
+        }
+        if node.attribute("ed").is_none() {
+            ctx.report(node, "<pb> missing @ed");
+        }
+        if node.attribute("n").is_none() {
+            match infer_page_number(node) {
+                Some(n) => {
+                    let fix = Fix::new(
+                        format!("Add n=\"{}\" (from the neighbouring page breaks)", n),
+                        vec![Edit::insert(
+                            name_end(node, ctx.source()),
+                            format!(" n=\"{}\"", n),
+                        )],
+                    );
+                    ctx.report_with_fix(node, "<pb> missing @n", fix);
+                }
+                None => ctx.report(node, "<pb> missing @n"),
+            }
+        }
+    }
+}
+
+/// The number of a page break without @n, counted on from the closest
+/// numbered page break of the same edition before it and back from the
+/// closest one after it; `None` if neither is numeric or they disagree
+fn infer_page_number(pb: Node) -> Option<u64> {
+    let ed = pb.attribute("ed");
+    let pbs: Vec<Node> = pb
+        .document()
+        .root_element()
+        .descendants()
+        .filter(|n| n.tag_name() == pb.tag_name() && n.attribute("ed") == ed)
+        .collect();
+    let i = pbs.iter().position(|n| *n == pb)?;
+    let numbered = |(distance, n): (usize, &Node)| {
+        n.attribute("n")
+            .map(|v| (distance as u64 + 1, v.trim().parse::<u64>().ok()))
+    };
+
+    let from_before = match pbs[..i].iter().rev().enumerate().find_map(numbered) {
+        Some((distance, Some(n))) => Some(n + distance),
+        _ => None,
+    };
+    let from_after = match pbs[i + 1..].iter().enumerate().find_map(numbered) {
+        Some((distance, Some(n))) => n.checked_sub(distance).filter(|n| *n > 0),
+        _ => None,
+    };
+    match (from_before, from_after) {
+        (Some(a), Some(b)) if a != b => None,
+        (a, b) => a.or(b),
+    }
+}
+
+struct HeadInsideDiv;
+
+impl Rule for HeadInsideDiv {
+    fn id(&self) -> &str {
+        "head-inside-div"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn description(&self) -> &str {
+        "Headings (<head>) should be inside a <div>"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Wrap the heading and the section it introduces in a <div>")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.tag_name().name() == "head"
+            && !node.ancestors().any(|a| a.tag_name().name() == "div")
+        {
+            ctx.report(node, "<head> should be inside <div>");
+        }
+    }
+}
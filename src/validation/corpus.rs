@@ -0,0 +1,347 @@
+use super::references::{check_references, FileIndex, CROSS_FILE_REFERENCE};
+use super::report::{Report, Severity};
+use super::rule::Registry;
+use crate::utils::find_xml_files;
+use anyhow::{Context, Result};
+use console::style;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::error;
+
+/// Output format of the `validate` command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// Per-file reports and a corpus summary for the terminal
+    #[default]
+    Text,
+    /// Per-file reports, failures and the summary as one JSON document
+    Json,
+    /// SARIF 2.1.0 log for code-scanning dashboards
+    Sarif,
+    /// JUnit XML with one testcase per file
+    Junit,
+    /// GitHub Actions annotations (`::error file=…,line=…::…`)
+    Github,
+}
+
+/// Validation report of one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileReport {
+    pub file: String,
+    pub report: Report,
+}
+
+/// A file that could not be read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationFailure {
+    pub file: String,
+    pub message: String,
+}
+
+/// Reports of many files, in input file order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorpusReport {
+    pub files: Vec<FileReport>,
+    pub failures: Vec<ValidationFailure>,
+}
+
+/// How often one message text occurs across the corpus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageCount {
+    pub severity: Severity,
+    pub rule_id: String,
+    pub text: String,
+    pub count: usize,
+
+    /// Number of files the message occurs in
+    pub files: usize,
+}
+
+/// Message counts of one file, for the worst-files ranking
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCounts {
+    pub file: String,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// Aggregated view of a corpus report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusSummary {
+    pub files: usize,
+    pub files_with_errors: usize,
+    pub files_with_warnings: usize,
+    pub failures: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+
+    /// Message texts, most frequent first
+    pub messages: Vec<MessageCount>,
+
+    /// Files with the most errors (then warnings), at most `worst` of them
+    pub worst_files: Vec<FileCounts>,
+}
+
+impl CorpusReport {
+    /// True if no file has errors and every file could be read
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty() && self.files.iter().all(|f| f.report.is_valid())
+    }
+
+    /// Number of files with at least one error, unreadable files included
+    pub fn invalid_files(&self) -> usize {
+        self.failures.len() + self.files.iter().filter(|f| !f.report.is_valid()).count()
+    }
+
+    /// Totals, message counts and the `worst` files with most issues
+    pub fn summary(&self, worst: usize) -> CorpusSummary {
+        let mut counts: HashMap<(Severity, &str, &str), (usize, usize)> = HashMap::new();
+        for file in &self.files {
+            let mut seen: Vec<(Severity, &str, &str)> = Vec::new();
+            for msg in file.report.messages() {
+                let key = (msg.severity, msg.rule_id.as_str(), msg.text.as_str());
+                let entry = counts.entry(key).or_default();
+                entry.0 += 1;
+                if !seen.contains(&key) {
+                    seen.push(key);
+                    entry.1 += 1;
+                }
+            }
+        }
+        let mut messages: Vec<MessageCount> = counts
+            .into_iter()
+            .map(|((severity, rule_id, text), (count, files))| MessageCount {
+                severity,
+                rule_id: rule_id.to_string(),
+                text: text.to_string(),
+                count,
+                files,
+            })
+            .collect();
+        messages.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.severity.cmp(&b.severity))
+                .then(a.rule_id.cmp(&b.rule_id))
+                .then(a.text.cmp(&b.text))
+        });
+
+        let mut worst_files: Vec<FileCounts> = self
+            .files
+            .iter()
+            .filter(|f| !f.report.errors.is_empty() || !f.report.warnings.is_empty())
+            .map(|f| FileCounts {
+                file: f.file.clone(),
+                errors: f.report.errors.len(),
+                warnings: f.report.warnings.len(),
+            })
+            .collect();
+        worst_files.sort_by(|a, b| {
+            b.errors
+                .cmp(&a.errors)
+                .then(b.warnings.cmp(&a.warnings))
+                .then(a.file.cmp(&b.file))
+        });
+        worst_files.truncate(worst);
+
+        let reports = || self.files.iter().map(|f| &f.report);
+        CorpusSummary {
+            files: self.files.len() + self.failures.len(),
+            files_with_errors: self.invalid_files(),
+            files_with_warnings: reports().filter(|r| !r.warnings.is_empty()).count(),
+            failures: self.failures.len(),
+            errors: reports().map(|r| r.errors.len()).sum(),
+            warnings: reports().map(|r| r.warnings.len()).sum(),
+            info: reports().map(|r| r.info.len()).sum(),
+            messages,
+            worst_files,
+        }
+    }
+}
+
+impl CorpusSummary {
+    /// Pretty console output
+    pub fn print(&self) {
+        println!("\n{}", style("Corpus Summary").bold().underlined());
+        println!(
+            "Files: {} ({} with errors, {} with warnings, {} unreadable)",
+            self.files, self.files_with_errors, self.files_with_warnings, self.failures
+        );
+        println!(
+            "Total: {} errors, {} warnings, {} info",
+            self.errors, self.warnings, self.info
+        );
+
+        if !self.messages.is_empty() {
+            println!("\n{}", style("Messages").bold());
+            for msg in &self.messages {
+                println!(
+                    "{} {:>6} in {:>5} files  {}  {}",
+                    severity_marker(msg.severity),
+                    msg.count,
+                    msg.files,
+                    msg.text,
+                    style(format!("[{}]", msg.rule_id)).dim()
+                );
+            }
+        }
+
+        if !self.worst_files.is_empty() {
+            println!("\n{}", style("Worst files").bold());
+            for file in &self.worst_files {
+                println!(
+                    "  {:>5} errors {:>5} warnings  {}",
+                    file.errors, file.warnings, file.file
+                );
+            }
+        }
+    }
+}
+
+fn severity_marker(severity: Severity) -> console::StyledObject<&'static str> {
+    match severity {
+        Severity::Error => style("✗").red(),
+        Severity::Warning => style("⚠").yellow(),
+        Severity::Info => style("ℹ").blue(),
+    }
+}
+
+/// Validate every file in parallel against the rules of `registry`, then
+/// resolve the pointers between them (unless `cross-file-reference` is
+/// disabled)
+pub fn validate_files(
+    file_paths: &[String],
+    registry: &Registry,
+    progress_bar: Option<ProgressBar>,
+) -> CorpusReport {
+    let cross_references = registry.get(CROSS_FILE_REFERENCE);
+    let per_file: Vec<Result<(Report, Option<FileIndex>)>> = file_paths
+        .par_iter()
+        .map(|file_path| {
+            let result = fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read file: {}", file_path))
+                .map(|xml| {
+                    // Indexed from the tree validation parses
+                    let (report, index) =
+                        registry.validate_file_with(&xml, Path::new(file_path), |doc| {
+                            cross_references.map(|_| FileIndex::from_document(doc))
+                        });
+                    (report, index.flatten())
+                });
+            if let Some(ref pb) = progress_bar {
+                pb.inc(1);
+            }
+            result
+        })
+        .collect();
+
+    if let Some(ref pb) = progress_bar {
+        pb.finish_with_message("Validation complete");
+    }
+
+    let mut result = CorpusReport::default();
+    let mut indexes = Vec::new();
+    for (file_path, outcome) in file_paths.iter().zip(per_file) {
+        match outcome {
+            Ok((report, index)) => {
+                result.files.push(FileReport {
+                    file: file_path.clone(),
+                    report,
+                });
+                indexes.push(index);
+            }
+            Err(e) => {
+                error!("Failed to validate {}: {:#}", file_path, e);
+                result.failures.push(ValidationFailure {
+                    file: file_path.clone(),
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+    }
+    if let Some(rule) = cross_references {
+        check_references(&mut result.files, indexes, rule);
+    }
+    result
+}
+
+/// Validate the XML files below `dir` (0 = unlimited depth)
+pub fn validate_corpus(
+    dir: &Path,
+    extensions: &[String],
+    max_depth: usize,
+    registry: &Registry,
+) -> Result<CorpusReport> {
+    let files = find_xml_files(dir, extensions, max_depth)?;
+    Ok(validate_files(&files, registry, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_corpus_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(
+            dir.join("a.xml"),
+            r#"<TEI><text><body><div><head>A</head><pb ed="x" n="1"/></div></body></text></TEI>"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.xml"),
+            "<TEI><text><body><pb/><pb/><head>B</head></body></text></TEI>",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("c.xml"),
+            "<TEI><text><body><pb n='1'/></body></text></TEI>",
+        )
+        .unwrap();
+
+        let extensions = ["xml".to_string()];
+        let mut registry = Registry::builtin();
+        registry.disable("tei-namespace").unwrap();
+        let report = validate_corpus(dir, &extensions, 0, &registry).unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert!(!report.is_valid());
+        assert_eq!(report.invalid_files(), 2);
+
+        let summary = report.summary(1);
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.files_with_errors, 2);
+        assert_eq!(summary.files_with_warnings, 1);
+        assert_eq!(summary.errors, 5);
+        assert_eq!(summary.warnings, 1);
+
+        assert_eq!(summary.messages[0].text, "<pb> missing @ed");
+        assert_eq!(summary.messages[0].count, 3);
+        assert_eq!(summary.messages[0].files, 2);
+        assert_eq!(summary.messages[1].text, "<pb> missing @n");
+        assert_eq!(summary.messages[1].count, 2);
+
+        assert_eq!(summary.worst_files.len(), 1);
+        assert!(summary.worst_files[0].file.ends_with("b.xml"));
+        assert_eq!(summary.worst_files[0].errors, 4);
+    }
+
+    #[test]
+    fn test_unreadable_file_is_invalid() {
+        let report = validate_files(
+            &["/nonexistent/file.xml".to_string()],
+            &Registry::builtin(),
+            None,
+        );
+        assert!(report.files.is_empty());
+        assert_eq!(report.failures.len(), 1);
+        assert!(!report.is_valid());
+        assert_eq!(report.summary(10).files_with_errors, 1);
+    }
+}
@@ -0,0 +1,246 @@
+//! Validation against the schemas a document declares itself: the DTD of its
+//! DOCTYPE and the XML Schemas of `xsi:schemaLocation` and
+//! `xsi:noNamespaceSchemaLocation`, resolved relative to the document.
+//! Remote schemas are not fetched; they are reported at info level and
+//! skipped.
+
+use super::dtd::{Doctype, Dtd};
+use super::local_path;
+use super::report::{Message, Severity};
+use super::rule::{node_position, Rule, RuleContext};
+use super::xsd::{XmlSchema, XSI_NS};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Compiled schemas (or why they failed to load), shared by all documents
+/// referencing the same files
+type Cache<K, V> = Mutex<HashMap<K, Arc<Result<V, String>>>>;
+
+/// Validates each document against the DTD and XML Schemas it declares
+#[derive(Default)]
+pub struct DeclaredSchemas {
+    dtds: Cache<PathBuf, Dtd>,
+    schemas: Cache<Vec<PathBuf>, XmlSchema>,
+}
+
+impl DeclaredSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn dtd(&self, doctype: &Doctype, base: &Path) -> Arc<Result<Dtd, String>> {
+        let load = || Arc::new(Dtd::from_doctype(doctype, base).map_err(|e| format!("{:#}", e)));
+        // An internal subset belongs to this one document
+        let (Some(system_id), None) = (&doctype.system_id, &doctype.internal_subset) else {
+            return load();
+        };
+        match local_path(base, system_id) {
+            Ok(path) => cached(&self.dtds, path, load),
+            Err(_) => load(),
+        }
+    }
+
+    fn schema(&self, paths: Vec<PathBuf>) -> Arc<Result<XmlSchema, String>> {
+        let load = || {
+            let name = paths[0]
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "schema".to_string());
+            let schema = XmlSchema::from_files(&paths, format!("xsd:{}", name));
+            Arc::new(schema.map_err(|e| format!("{:#}", e)))
+        };
+        cached(&self.schemas, paths.clone(), load)
+    }
+}
+
+/// The cached value of `key`, compiled outside the lock on a miss
+fn cached<K, V>(
+    cache: &Cache<K, V>,
+    key: K,
+    load: impl FnOnce() -> Arc<Result<V, String>>,
+) -> Arc<Result<V, String>>
+where
+    K: std::hash::Hash + Eq,
+{
+    if let Some(value) = cache.lock().expect("schema cache").get(&key) {
+        return value.clone();
+    }
+    let value = load();
+    cache
+        .lock()
+        .expect("schema cache")
+        .entry(key)
+        .or_insert(value)
+        .clone()
+}
+
+impl Rule for DeclaredSchemas {
+    fn id(&self) -> &str {
+        "declared-schema"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "Content model of the DTD or XML Schema the document declares"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Change the markup to follow the declared schema, or fix the schema reference")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_some() {
+            return;
+        }
+        let base = ctx
+            .path()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        let doc = node.document();
+
+        let prolog = &ctx.source()[..node.range().start];
+        if let Some(doctype) = Doctype::find(prolog) {
+            let remote = doctype
+                .system_id
+                .as_deref()
+                .filter(|system_id| local_path(&base, system_id).is_err());
+            if let Some(system_id) = remote {
+                skip_remote(node, ctx, "DTD", system_id);
+            } else if doctype.system_id.is_some() || doctype.internal_subset.is_some() {
+                match self.dtd(&doctype, &base).as_ref() {
+                    Ok(dtd) => {
+                        for msg in dtd.validate_document(doc) {
+                            ctx.report_at(msg.line, msg.column, msg.text);
+                        }
+                    }
+                    Err(e) => ctx.report(node, format!("Cannot load the DTD: {}", e)),
+                }
+            }
+        }
+
+        // Every second token of xsi:schemaLocation is a location
+        let mut locations: Vec<&str> = node
+            .attribute((XSI_NS, "schemaLocation"))
+            .unwrap_or_default()
+            .split_whitespace()
+            .skip(1)
+            .step_by(2)
+            .collect();
+        locations.extend(node.attribute((XSI_NS, "noNamespaceSchemaLocation")));
+        if locations.is_empty() {
+            return;
+        }
+        let mut paths = Vec::new();
+        for location in locations {
+            match local_path(&base, location) {
+                Ok(path) => paths.push(path),
+                Err(_) => skip_remote(node, ctx, "Schema", location),
+            }
+        }
+        if paths.is_empty() {
+            return;
+        }
+        match self.schema(paths).as_ref() {
+            Ok(schema) => {
+                for msg in schema.validate_document(doc) {
+                    ctx.report_at(msg.line, msg.column, msg.text);
+                }
+            }
+            Err(e) => ctx.report(node, format!("Cannot load the XML Schema: {}", e)),
+        }
+    }
+}
+
+/// Note a remote schema at info level: it isn't a problem of the document
+fn skip_remote(node: Node, ctx: &mut RuleContext, kind: &str, location: &str) {
+    let (line, column) = node_position(node);
+    ctx.report_message(Message::new(
+        "declared-schema",
+        Severity::Info,
+        line,
+        column,
+        format!(
+            "{} {} is not fetched; only local schemas are used",
+            kind, location
+        ),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Registry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_declared_dtd_and_schema_location() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("note.dtd"),
+            "<!ELEMENT note (to, body)>\n<!ELEMENT to (#PCDATA)>\n<!ELEMENT body (#PCDATA)>",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("note.xsd"),
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="note">
+    <xs:complexType>
+      <xs:sequence><xs:element name="body" type="xs:string"/></xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        )
+        .unwrap();
+
+        let mut registry = Registry::empty();
+        registry.register(DeclaredSchemas::new()).unwrap();
+        let texts = |xml: &str| -> Vec<String> {
+            let report = registry.validate_file(xml, &dir.path().join("a.xml"));
+            report.errors.into_iter().map(|m| m.text).collect()
+        };
+
+        assert_eq!(
+            texts("<!DOCTYPE note SYSTEM \"note.dtd\">\n<note><body/></note>"),
+            vec![
+                "Element <body> not allowed in <note>; expected <to>",
+                "Element <note> is incomplete; expected <to>",
+            ]
+        );
+        assert_eq!(
+            texts(
+                r#"<note xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+  xsi:noNamespaceSchemaLocation="note.xsd"><to/><body/></note>"#
+            ),
+            vec!["Element <to> not allowed in <note>; expected <body>"]
+        );
+        assert!(texts("<!DOCTYPE note SYSTEM \"missing.dtd\">\n<note/>")[0]
+            .starts_with("Cannot load the DTD: Invalid DTD:"));
+        assert!(texts("<note/>").is_empty());
+
+        // Remote schemas are noted and skipped, local ones still apply
+        let tei = r#"<!DOCTYPE note SYSTEM "http://www.tei-c.org/release/xml/tei/custom/schema/dtd/tei_all.dtd">
+<note xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+  xsi:schemaLocation="http://www.tei-c.org/ns/1.0 http://www.tei-c.org/release/xml/tei/custom/schema/xsd/tei_all.xsd"
+  xsi:noNamespaceSchemaLocation="note.xsd"><to/><body/></note>"#;
+        let report = registry.validate_file(tei, &dir.path().join("a.xml"));
+        let info: Vec<&str> = report.info.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(
+            info,
+            vec![
+                "DTD http://www.tei-c.org/release/xml/tei/custom/schema/dtd/tei_all.dtd is not fetched; only local schemas are used",
+                "Schema http://www.tei-c.org/release/xml/tei/custom/schema/xsd/tei_all.xsd is not fetched; only local schemas are used",
+            ]
+        );
+        assert_eq!(
+            texts(tei),
+            vec!["Element <to> not allowed in <note>; expected <body>"]
+        );
+    }
+}
@@ -0,0 +1,1041 @@
+//! DTD validation: element content models and attribute list declarations,
+//! translated to a RELAX NG grammar and checked by the same engine.
+//!
+//! Parameter entities (internal and local external ones), conditional
+//! sections and the internal subset of a DOCTYPE are supported. DTDs
+//! predate namespaces, so element names match by local name in any
+//! namespace; `xml:` attributes are in the XML namespace and `xmlns`
+//! declarations are skipped. General entities and notations are not
+//! checked, and ID uniqueness is left to the `xml:id` checks.
+
+use super::local_path;
+use super::relaxng::{Elem, RelaxNg, XML_NS, XSD_LIBRARY};
+use super::report::Severity;
+use super::rule::{Rule, RuleContext};
+use anyhow::{Context, Result};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Deepest nesting of parameter entity references
+const MAX_ENTITY_DEPTH: usize = 32;
+
+/// Most replacement text substituted for parameter entity references, in
+/// bytes, over a whole DTD
+const MAX_EXPANDED_BYTES: usize = 64 << 20;
+
+/// A compiled DTD, registered as one rule
+#[derive(Debug)]
+pub struct Dtd {
+    inner: RelaxNg,
+}
+
+impl Dtd {
+    /// Load an external DTD file; every declared element may be the root
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut decls = Declarations::default();
+        decls
+            .parse_file(path, 0)
+            .with_context(|| format!("Invalid DTD: {}", path.display()))?;
+        Self::compile(&decls, None, &format!("dtd:{}", file_stem(path)), path)
+    }
+
+    /// The DTD of a DOCTYPE: its internal subset, then the external subset
+    /// resolved relative to `base` (the document's directory)
+    pub fn from_doctype(doctype: &Doctype, base: &Path) -> Result<Self> {
+        let mut decls = Declarations::default();
+        if let Some(internal) = &doctype.internal_subset {
+            decls
+                .parse_subset(internal, base, 0)
+                .context("Invalid internal DTD subset")?;
+        }
+        let origin = match &doctype.system_id {
+            Some(system_id) => {
+                let path = local_path(base, system_id)?;
+                decls
+                    .parse_file(&path, 0)
+                    .with_context(|| format!("Invalid DTD: {}", path.display()))?;
+                path
+            }
+            None => PathBuf::from("internal subset"),
+        };
+        Self::compile(
+            &decls,
+            Some(&doctype.name),
+            &format!("dtd:{}", doctype.name),
+            &origin,
+        )
+    }
+
+    fn compile(decls: &Declarations, root: Option<&str>, id: &str, origin: &Path) -> Result<Self> {
+        if decls.elements.is_empty() {
+            anyhow::bail!("No element declarations in {}", origin.display());
+        }
+        let grammar = decls.to_grammar(root);
+        let description = format!(
+            "Content models and attribute lists of the DTD {} ({} element declarations)",
+            origin.display(),
+            decls.elements.len()
+        );
+        Ok(Self {
+            inner: RelaxNg::from_grammar(&grammar, id.to_string(), description)?,
+        })
+    }
+
+    /// Validate a parsed document; messages are in document order
+    pub fn validate_document(&self, doc: &roxmltree::Document) -> Vec<super::Message> {
+        self.inner.validate_document(doc)
+    }
+}
+
+impl Rule for Dtd {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Change the markup to follow the element and attribute declarations of the DTD")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        self.inner.check(node, ctx)
+    }
+}
+
+/// A document type declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Doctype {
+    pub name: String,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    pub internal_subset: Option<String>,
+}
+
+impl Doctype {
+    /// The DOCTYPE of a document's source, if it has one
+    pub fn find(source: &str) -> Option<Self> {
+        let start = source.find("<!DOCTYPE")?;
+        let mut cursor = Cursor::new(&source[start + "<!DOCTYPE".len()..]);
+        cursor.skip_ws();
+        let name = cursor.name()?;
+        cursor.skip_ws();
+
+        let (mut public_id, mut system_id) = (None, None);
+        if cursor.eat("PUBLIC") {
+            cursor.skip_ws();
+            public_id = Some(cursor.literal()?);
+            cursor.skip_ws();
+            system_id = cursor.literal();
+        } else if cursor.eat("SYSTEM") {
+            cursor.skip_ws();
+            system_id = Some(cursor.literal()?);
+        }
+        cursor.skip_ws();
+
+        let internal_subset = if cursor.eat("[") {
+            let rest = cursor.rest();
+            Some(rest[..markup_end(rest, b']')?].to_string())
+        } else {
+            None
+        };
+        Some(Self {
+            name,
+            public_id,
+            system_id,
+            internal_subset,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Entity {
+    Internal(String),
+    External(PathBuf),
+}
+
+#[derive(Debug)]
+enum ContentSpec {
+    Empty,
+    Any,
+    /// `(#PCDATA | a | b)*`
+    Mixed(Vec<String>),
+    Children(Particle),
+}
+
+#[derive(Debug)]
+enum Particle {
+    Name(String),
+    Seq(Vec<Particle>),
+    Choice(Vec<Particle>),
+    /// `?`, `*` or `+`
+    Repeat(Box<Particle>, u8),
+}
+
+#[derive(Debug)]
+enum AttType {
+    Cdata,
+    /// ID, IDREF(S), ENTITY, ENTITIES, NMTOKEN(S), by XML Schema type name
+    Tokenized(String),
+    Enumeration(Vec<String>),
+}
+
+#[derive(Debug)]
+enum AttDefault {
+    Required,
+    Implied,
+    Fixed(String),
+    Default,
+}
+
+#[derive(Debug)]
+struct AttDef {
+    name: String,
+    kind: AttType,
+    default: AttDefault,
+}
+
+/// Declarations in document order; the first declaration of a name wins
+#[derive(Debug, Default)]
+struct Declarations {
+    entities: HashMap<String, Entity>,
+    elements: Vec<(String, ContentSpec)>,
+    attlists: HashMap<String, Vec<AttDef>>,
+    /// Replacement text substituted so far
+    expanded: usize,
+}
+
+impl Declarations {
+    fn parse_file(&mut self, path: &Path, depth: usize) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read DTD: {}", path.display()))?;
+        let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+        // A text declaration may open an external subset
+        let text = match text.strip_prefix("<?xml") {
+            Some(rest) => rest.find("?>").map_or("", |end| &rest[end + 2..]),
+            None => text,
+        };
+        self.parse_subset(text, path.parent().unwrap_or(Path::new("")), depth)
+    }
+
+    fn parse_subset(&mut self, text: &str, base: &Path, depth: usize) -> Result<()> {
+        if depth > MAX_ENTITY_DEPTH {
+            anyhow::bail!("Parameter entities nested too deeply");
+        }
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Ok(());
+            }
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                let end = comment.find("-->").context("Unterminated comment")?;
+                rest = &comment[end + 3..];
+            } else if let Some(pi) = rest.strip_prefix("<?") {
+                let end = pi
+                    .find("?>")
+                    .context("Unterminated processing instruction")?;
+                rest = &pi[end + 2..];
+            } else if let Some(section) = rest.strip_prefix("<![") {
+                let open = section.find('[').context("Malformed conditional section")?;
+                let keyword = self.expand(&section[..open], depth)?;
+                let body = &section[open + 1..];
+                let end = conditional_end(body).context("Unterminated conditional section")?;
+                match keyword.trim() {
+                    "INCLUDE" => self.parse_subset(&body[..end], base, depth + 1)?,
+                    "IGNORE" => {}
+                    other => anyhow::bail!("Unknown conditional section keyword: {}", other),
+                }
+                rest = &body[end + 3..];
+            } else if rest.starts_with("<!") {
+                let end = markup_end(rest, b'>').context("Unterminated declaration")?;
+                self.declaration(&rest[2..end], base, depth)?;
+                rest = &rest[end + 1..];
+            } else if let Some(reference) = rest.strip_prefix('%') {
+                let end = reference
+                    .find(';')
+                    .context("Unterminated parameter entity reference")?;
+                let name = &reference[..end];
+                match self.entities.get(name) {
+                    Some(Entity::Internal(value)) => {
+                        let value = value.clone();
+                        self.parse_subset(&value, base, depth + 1)?
+                    }
+                    Some(Entity::External(path)) => {
+                        let path = path.clone();
+                        self.parse_file(&path, depth + 1)?
+                    }
+                    None => anyhow::bail!("Undeclared parameter entity: %{};", name),
+                }
+                rest = &reference[end + 1..];
+            } else {
+                let snippet: String = rest.chars().take(30).collect();
+                anyhow::bail!("Unexpected text in DTD: {}", snippet);
+            }
+        }
+    }
+
+    /// Replace parameter entity references outside of literals
+    fn expand(&mut self, text: &str, depth: usize) -> Result<String> {
+        if depth > MAX_ENTITY_DEPTH {
+            anyhow::bail!("Parameter entities nested too deeply");
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut quote = None;
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match (c, quote) {
+                ('"' | '\'', None) => quote = Some(c),
+                (q, Some(open)) if q == open => quote = None,
+                ('%', None) if chars.peek().is_some_and(|(_, n)| is_name_start(*n)) => {
+                    let end = text[i..]
+                        .find(';')
+                        .map(|e| i + e)
+                        .context("Unterminated parameter entity reference")?;
+                    let name = &text[i + 1..end];
+                    out.push(' ');
+                    let value = self.entity_text(name)?;
+                    out.push_str(&self.expand(&value, depth + 1)?);
+                    out.push(' ');
+                    while chars.peek().is_some_and(|(j, _)| *j <= end) {
+                        chars.next();
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            out.push(c);
+        }
+        Ok(out)
+    }
+
+    /// Replace all parameter entity references (in entity values)
+    fn expand_literal(&mut self, text: &str, depth: usize) -> Result<String> {
+        if depth > MAX_ENTITY_DEPTH {
+            anyhow::bail!("Parameter entities nested too deeply");
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(i) = rest.find('%') {
+            out.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            match after
+                .find(';')
+                .filter(|e| after[..*e].chars().all(is_name_char))
+            {
+                Some(end) if end > 0 => {
+                    let value = self.entity_text(&after[..end])?;
+                    out.push_str(&self.expand_literal(&value, depth + 1)?);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    out.push('%');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// The replacement text of a reference, counted towards
+    /// `MAX_EXPANDED_BYTES`
+    fn entity_text(&mut self, name: &str) -> Result<String> {
+        let text = match self.entities.get(name) {
+            Some(Entity::Internal(value)) => value.clone(),
+            Some(Entity::External(path)) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read DTD: {}", path.display()))?,
+            None => anyhow::bail!("Undeclared parameter entity: %{};", name),
+        };
+        self.expanded += text.len();
+        if self.expanded > MAX_EXPANDED_BYTES {
+            anyhow::bail!("Parameter entity expansion too large");
+        }
+        Ok(text)
+    }
+
+    fn declaration(&mut self, decl: &str, base: &Path, depth: usize) -> Result<()> {
+        if let Some(body) = decl.strip_prefix("ENTITY") {
+            return self.entity(body, base, depth);
+        }
+        let expanded = self.expand(decl, depth)?;
+        let mut cursor = Cursor::new(&expanded);
+        if cursor.eat("ELEMENT") {
+            cursor.skip_ws();
+            let name = cursor.name().context("<!ELEMENT> without a name")?;
+            cursor.skip_ws();
+            let spec = content_spec(&mut cursor)
+                .with_context(|| format!("Invalid content model of {}", name))?;
+            if !self.elements.iter().any(|(n, _)| *n == name) {
+                self.elements.push((name, spec));
+            }
+        } else if cursor.eat("ATTLIST") {
+            cursor.skip_ws();
+            let element = cursor
+                .name()
+                .context("<!ATTLIST> without an element name")?;
+            let defs = attribute_defs(&mut cursor)
+                .with_context(|| format!("Invalid attribute list of {}", element))?;
+            let list = self.attlists.entry(element).or_default();
+            for def in defs {
+                if !list.iter().any(|d| d.name == def.name) {
+                    list.push(def);
+                }
+            }
+        }
+        // NOTATION declarations don't affect validation
+        Ok(())
+    }
+
+    fn entity(&mut self, body: &str, base: &Path, depth: usize) -> Result<()> {
+        let mut cursor = Cursor::new(body);
+        cursor.skip_ws();
+        if !cursor.eat("%") {
+            // General entities are expanded by the XML parser, if internal
+            return Ok(());
+        }
+        cursor.skip_ws();
+        let name = cursor.name().context("<!ENTITY %> without a name")?;
+        cursor.skip_ws();
+        let entity = if cursor.eat("SYSTEM") {
+            cursor.skip_ws();
+            Entity::External(local_path(
+                base,
+                &cursor.literal().context("Missing system identifier")?,
+            )?)
+        } else if cursor.eat("PUBLIC") {
+            cursor.skip_ws();
+            cursor.literal().context("Missing public identifier")?;
+            cursor.skip_ws();
+            Entity::External(local_path(
+                base,
+                &cursor.literal().context("Missing system identifier")?,
+            )?)
+        } else {
+            let value = cursor.literal().context("Missing entity value")?;
+            Entity::Internal(self.expand_literal(&value, depth)?)
+        };
+        self.entities.entry(name).or_insert(entity);
+        Ok(())
+    }
+
+    /// The grammar of the declarations; `root` restricts the start
+    fn to_grammar(&self, root: Option<&str>) -> Elem {
+        let declared: Vec<&str> = self.elements.iter().map(|(n, _)| n.as_str()).collect();
+        let mut referenced = Vec::new();
+
+        let start = match root {
+            Some(root) => {
+                referenced.push(root.to_string());
+                element_ref(root)
+            }
+            None => Elem::new("choice").with_children(declared.iter().map(|n| element_ref(n))),
+        };
+        let mut grammar = Elem::new("grammar").with_child(Elem::new("start").with_child(start));
+
+        for (name, spec) in &self.elements {
+            let attributes = self
+                .attlists
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter_map(attribute_pattern);
+            let content = match spec {
+                ContentSpec::Empty => Elem::new("empty"),
+                ContentSpec::Any => mixed_of(&declared),
+                ContentSpec::Mixed(names) => {
+                    referenced.extend(names.iter().cloned());
+                    mixed_of(&names.iter().map(String::as_str).collect::<Vec<_>>())
+                }
+                ContentSpec::Children(particle) => particle_pattern(particle, &mut referenced),
+            };
+            let (_, local) = split_qname(name);
+            grammar = grammar.with_child(
+                Elem::new("define").with_attr("name", name).with_child(
+                    Elem::new("element")
+                        .with_name(None, local)
+                        .with_children(attributes)
+                        .with_child(content),
+                ),
+            );
+        }
+
+        // Referenced but undeclared elements are never valid
+        referenced.sort();
+        referenced.dedup();
+        for name in referenced
+            .iter()
+            .filter(|n| !declared.contains(&n.as_str()))
+        {
+            grammar = grammar.with_child(
+                Elem::new("define")
+                    .with_attr("name", name)
+                    .with_child(Elem::new("notAllowed")),
+            );
+        }
+        grammar
+    }
+}
+
+fn element_ref(name: &str) -> Elem {
+    Elem::new("ref").with_attr("name", name)
+}
+
+/// Text mixed with any of `names`
+fn mixed_of(names: &[&str]) -> Elem {
+    if names.is_empty() {
+        return Elem::new("text");
+    }
+    Elem::new("mixed").with_child(
+        Elem::new("zeroOrMore")
+            .with_child(Elem::new("choice").with_children(names.iter().map(|n| element_ref(n)))),
+    )
+}
+
+fn particle_pattern(particle: &Particle, referenced: &mut Vec<String>) -> Elem {
+    match particle {
+        Particle::Name(name) => {
+            referenced.push(name.clone());
+            element_ref(name)
+        }
+        Particle::Seq(items) => Elem::new("group").with_children(
+            items
+                .iter()
+                .map(|p| particle_pattern(p, referenced))
+                .collect::<Vec<_>>(),
+        ),
+        Particle::Choice(items) => Elem::new("choice").with_children(
+            items
+                .iter()
+                .map(|p| particle_pattern(p, referenced))
+                .collect::<Vec<_>>(),
+        ),
+        Particle::Repeat(item, op) => {
+            let name = match op {
+                b'?' => "optional",
+                b'*' => "zeroOrMore",
+                _ => "oneOrMore",
+            };
+            Elem::new(name).with_child(particle_pattern(item, referenced))
+        }
+    }
+}
+
+fn attribute_pattern(def: &AttDef) -> Option<Elem> {
+    if def.name == "xmlns" || def.name.starts_with("xmlns:") {
+        return None;
+    }
+    let attribute = match split_qname(&def.name) {
+        (Some("xml"), local) => Elem::new("attribute").with_name(Some(XML_NS), local),
+        (Some(_), local) => Elem::new("attribute").with_name(None, local),
+        (None, local) => Elem::new("attribute").with_name(Some(""), local),
+    };
+    let value = match (&def.default, &def.kind) {
+        (AttDefault::Fixed(value), AttType::Cdata) => Elem::new("value")
+            .with_attr("type", "string")
+            .with_text(value),
+        (AttDefault::Fixed(value), _) => Elem::new("value").with_text(value),
+        (_, AttType::Cdata) => Elem::new("text"),
+        (_, AttType::Tokenized(name)) => Elem::new("data")
+            .with_attr("type", name)
+            .with_datatype_library(XSD_LIBRARY),
+        (_, AttType::Enumeration(values)) => Elem::new("choice")
+            .with_children(values.iter().map(|v| Elem::new("value").with_text(v))),
+    };
+    let attribute = attribute.with_child(value);
+    Some(match def.default {
+        AttDefault::Required => attribute,
+        _ => Elem::new("optional").with_child(attribute),
+    })
+}
+
+fn content_spec(cursor: &mut Cursor) -> Result<ContentSpec> {
+    if cursor.eat("EMPTY") {
+        return Ok(ContentSpec::Empty);
+    }
+    if cursor.eat("ANY") {
+        return Ok(ContentSpec::Any);
+    }
+    if !cursor.eat("(") {
+        anyhow::bail!("Expected EMPTY, ANY or a content model");
+    }
+    cursor.skip_ws();
+    if cursor.eat("#PCDATA") {
+        let mut names = Vec::new();
+        loop {
+            cursor.skip_ws();
+            if cursor.eat(")") {
+                cursor.eat("*");
+                return Ok(ContentSpec::Mixed(names));
+            }
+            if !cursor.eat("|") {
+                anyhow::bail!("Expected | or ) in mixed content");
+            }
+            cursor.skip_ws();
+            names.push(cursor.name().context("Expected an element name")?);
+        }
+    }
+    let group = particle_group(cursor)?;
+    Ok(ContentSpec::Children(repeat(cursor, group)))
+}
+
+/// The rest of a parenthesized group, after its `(`
+fn particle_group(cursor: &mut Cursor) -> Result<Particle> {
+    let mut items = Vec::new();
+    let mut separator = None;
+    loop {
+        cursor.skip_ws();
+        let item = if cursor.eat("(") {
+            particle_group(cursor)?
+        } else {
+            Particle::Name(cursor.name().context("Expected an element name or (")?)
+        };
+        items.push(repeat(cursor, item));
+        cursor.skip_ws();
+        if cursor.eat(")") {
+            break;
+        }
+        let sep = if cursor.eat(",") {
+            b','
+        } else if cursor.eat("|") {
+            b'|'
+        } else {
+            anyhow::bail!("Expected , | or ) in content model");
+        };
+        if separator.is_some_and(|s| s != sep) {
+            anyhow::bail!("Mixed , and | in one group");
+        }
+        separator = Some(sep);
+    }
+    Ok(match (separator, items.len()) {
+        (_, 1) => items.pop().expect("one item"),
+        (Some(b'|'), _) => Particle::Choice(items),
+        _ => Particle::Seq(items),
+    })
+}
+
+fn repeat(cursor: &mut Cursor, particle: Particle) -> Particle {
+    for op in [b'?', b'*', b'+'] {
+        if cursor.eat(std::str::from_utf8(&[op]).expect("ASCII")) {
+            return Particle::Repeat(Box::new(particle), op);
+        }
+    }
+    particle
+}
+
+fn attribute_defs(cursor: &mut Cursor) -> Result<Vec<AttDef>> {
+    let mut defs = Vec::new();
+    loop {
+        cursor.skip_ws();
+        if cursor.rest().is_empty() {
+            return Ok(defs);
+        }
+        let name = cursor.name().context("Expected an attribute name")?;
+        cursor.skip_ws();
+        let kind = if cursor.eat("CDATA") {
+            AttType::Cdata
+        } else if cursor.eat("NOTATION") || cursor.rest().starts_with('(') {
+            cursor.skip_ws();
+            AttType::Enumeration(enumeration(cursor)?)
+        } else {
+            let keyword = cursor.name().context("Expected an attribute type")?;
+            match keyword.as_str() {
+                "ID" | "IDREF" | "IDREFS" | "ENTITY" | "ENTITIES" | "NMTOKEN" | "NMTOKENS" => {
+                    AttType::Tokenized(keyword)
+                }
+                other => anyhow::bail!("Unknown attribute type {} of {}", other, name),
+            }
+        };
+        cursor.skip_ws();
+        let default = if cursor.eat("#REQUIRED") {
+            AttDefault::Required
+        } else if cursor.eat("#IMPLIED") {
+            AttDefault::Implied
+        } else if cursor.eat("#FIXED") {
+            cursor.skip_ws();
+            AttDefault::Fixed(cursor.literal().context("Expected a fixed value")?)
+        } else {
+            cursor.literal().context("Expected a default value")?;
+            AttDefault::Default
+        };
+        defs.push(AttDef {
+            name,
+            kind,
+            default,
+        });
+    }
+}
+
+fn enumeration(cursor: &mut Cursor) -> Result<Vec<String>> {
+    if !cursor.eat("(") {
+        anyhow::bail!("Expected (");
+    }
+    let mut values = Vec::new();
+    loop {
+        cursor.skip_ws();
+        values.push(cursor.nmtoken().context("Expected an enumerated value")?);
+        cursor.skip_ws();
+        if cursor.eat(")") {
+            return Ok(values);
+        }
+        if !cursor.eat("|") {
+            anyhow::bail!("Expected | or ) in enumeration");
+        }
+    }
+}
+
+/// Position of the `end` byte closing a markup construct, skipping quoted
+/// literals, comments and (for `]`) nested declarations
+fn markup_end(text: &str, end: u8) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                let quote = bytes[i];
+                i += 1 + bytes[i + 1..].iter().position(|b| *b == quote)?;
+            }
+            b'<' if text[i..].starts_with("<!--") => {
+                i += 4 + text[i + 4..].find("-->")? + 2;
+            }
+            b'<' if end == b']' && text[i..].starts_with("<!") => {
+                i += markup_end(&text[i + 1..], b'>')? + 1;
+            }
+            b if b == end => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Position of the `]]>` closing a conditional section, with nesting
+fn conditional_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("<![") {
+            depth += 1;
+            i += 3;
+        } else if text[i..].starts_with("]]>") {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+            i += 3;
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
+}
+
+fn split_qname(name: &str) -> (Option<&str>, &str) {
+    match name.split_once(':') {
+        Some((prefix, local)) => (Some(prefix), local),
+        None => (None, name),
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "dtd".to_string())
+}
+
+fn is_name_start(c: char) -> bool {
+    c == '_' || c == ':' || c.is_alphabetic()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c == '-' || c == '.' || c.is_alphanumeric()
+}
+
+/// A position in declaration text
+struct Cursor<'t> {
+    text: &'t str,
+}
+
+impl<'t> Cursor<'t> {
+    fn new(text: &'t str) -> Self {
+        Self { text }
+    }
+
+    fn rest(&self) -> &'t str {
+        self.text
+    }
+
+    fn skip_ws(&mut self) {
+        self.text = self.text.trim_start();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        match self.text.strip_prefix(token) {
+            Some(rest) => {
+                self.text = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> Option<String> {
+        let end = self.text.find(|c: char| !f(c)).unwrap_or(self.text.len());
+        if end == 0 {
+            return None;
+        }
+        let (token, rest) = self.text.split_at(end);
+        self.text = rest;
+        Some(token.to_string())
+    }
+
+    fn name(&mut self) -> Option<String> {
+        if !self.text.starts_with(is_name_start) {
+            return None;
+        }
+        self.take_while(is_name_char)
+    }
+
+    fn nmtoken(&mut self) -> Option<String> {
+        self.take_while(is_name_char)
+    }
+
+    fn literal(&mut self) -> Option<String> {
+        let quote = self
+            .text
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')?;
+        let end = self.text[1..].find(quote)? + 1;
+        let value = self.text[1..end].to_string();
+        self.text = &self.text[end + 1..];
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roxmltree::{Document, ParsingOptions};
+    use tempfile::TempDir;
+
+    fn messages(dtd: &Dtd, xml: &str) -> Vec<(usize, String)> {
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(xml, options).unwrap();
+        dtd.validate_document(&doc)
+            .into_iter()
+            .map(|m| (m.line, m.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_external_dtd_with_parameter_entities() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("att.ent"),
+            r#"<!ENTITY % att.global "xml:id ID #IMPLIED rend CDATA #IMPLIED">"#,
+        )
+        .unwrap();
+        let path = dir.path().join("letter.dtd");
+        fs::write(
+            &path,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!ENTITY % atts SYSTEM "att.ent">
+%atts;
+<!ENTITY % inline "hi | pb">
+<!ENTITY % draft "IGNORE">
+<!-- The letter -->
+<!ELEMENT letter (opener?, p+, closer)>
+<!ATTLIST letter xmlns CDATA #FIXED "http://www.tei-c.org/ns/1.0"
+                 %att.global; type (draft|final) "final">
+<!ELEMENT opener (#PCDATA)>
+<!ELEMENT p (#PCDATA | %inline;)*>
+<![%draft;[ <!ELEMENT closer ANY> ]]>
+<![INCLUDE[ <!ELEMENT closer EMPTY> ]]>
+<!ELEMENT hi (#PCDATA)>
+<!ELEMENT pb EMPTY>
+<!ATTLIST pb n NMTOKEN #REQUIRED>"#,
+        )
+        .unwrap();
+        let dtd = Dtd::from_file(&path).unwrap();
+        assert_eq!(dtd.id(), "dtd:letter");
+
+        let ok = r#"<letter xmlns="http://www.tei-c.org/ns/1.0" xml:id="l1" type="draft">
+<p>Dear <hi>friend</hi><pb n="2"/></p><closer/></letter>"#;
+        assert_eq!(messages(&dtd, ok), vec![]);
+
+        let bad = r#"<letter type="sent">
+<p>x</p>
+<opener/>
+<p><pb n="a b"/><closer/></p>
+</letter>"#;
+        assert_eq!(
+            messages(&dtd, bad),
+            vec![
+                (
+                    1,
+                    r#"Invalid value "sent" for @type on <letter>"#.to_string()
+                ),
+                (
+                    3,
+                    "Element <opener> not allowed in <letter>; expected <closer> or <p>"
+                        .to_string()
+                ),
+                (4, r#"Invalid value "a b" for @n on <pb>"#.to_string()),
+                (
+                    4,
+                    "Element <closer> not allowed in <p>; expected <hi> or <pb>".to_string()
+                ),
+                (
+                    1,
+                    "Element <letter> is incomplete; expected <closer> or <p>".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doctype_with_internal_subset() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE note [
+  <!ELEMENT note (to, body)>
+  <!ATTLIST note id ID #REQUIRED>
+  <!ELEMENT to (#PCDATA)>
+  <!ELEMENT body (#PCDATA)>
+  <!ENTITY sig "-- A.">
+]>
+<note><body>Hi &sig;</body></note>"#;
+        let doctype = Doctype::find(xml).unwrap();
+        assert_eq!(doctype.name, "note");
+        assert_eq!(doctype.system_id, None);
+
+        let dtd = Dtd::from_doctype(&doctype, Path::new(".")).unwrap();
+        assert_eq!(
+            messages(&dtd, xml),
+            vec![
+                (
+                    9,
+                    "Element <note> is missing required attribute @id".to_string()
+                ),
+                (
+                    9,
+                    "Element <body> not allowed in <note>; expected <to>".to_string()
+                ),
+                (9, "Element <note> is incomplete; expected <to>".to_string()),
+            ]
+        );
+
+        let external =
+            Doctype::find(r#"<!DOCTYPE TEI PUBLIC "-//TEI//DTD TEI P5//EN" "tei_all.dtd"><TEI/>"#)
+                .unwrap();
+        assert_eq!(
+            external.public_id.as_deref(),
+            Some("-//TEI//DTD TEI P5//EN")
+        );
+        assert_eq!(external.system_id.as_deref(), Some("tei_all.dtd"));
+        assert!(Doctype::find("<TEI/>").is_none());
+
+        let remote = Doctype {
+            system_id: Some("http://example.org/tei.dtd".to_string()),
+            ..external
+        };
+        let err = Dtd::from_doctype(&remote, Path::new(".")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Only local schema files are supported"));
+    }
+
+    #[test]
+    fn test_conditional_sections() {
+        // The internal subset switches the sections of the external one:
+        // its declarations come first and win
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("doc.dtd"),
+            r#"<!ENTITY % strict "IGNORE">
+<!ENTITY % loose "INCLUDE">
+<![ %strict; [
+  <!ELEMENT doc (head, p+)>
+  <![ INCLUDE [ <!ATTLIST p n CDATA #REQUIRED> ]]>
+]]>
+<![IGNORE[ never parsed <!ELEMENT <![ nested [ ]]> either ]]>
+<![ %loose; [
+  <!ELEMENT doc (head?, p*)>
+  <![IGNORE[ <!ATTLIST p rend CDATA #REQUIRED> ]]>
+]]>
+<!ELEMENT head (#PCDATA)>
+<!ELEMENT p (#PCDATA)>"#,
+        )
+        .unwrap();
+        let strict = Doctype {
+            name: "doc".to_string(),
+            public_id: None,
+            system_id: Some("doc.dtd".to_string()),
+            internal_subset: Some(
+                r#"<!ENTITY % strict "INCLUDE"> <!ENTITY % loose "IGNORE">"#.to_string(),
+            ),
+        };
+        let loose = Doctype {
+            internal_subset: None,
+            ..strict.clone()
+        };
+
+        let xml = "<!DOCTYPE doc SYSTEM \"doc.dtd\"><doc><p/></doc>";
+        let dtd = Dtd::from_doctype(&loose, dir.path()).unwrap();
+        assert_eq!(messages(&dtd, xml), vec![]);
+        let dtd = Dtd::from_doctype(&strict, dir.path()).unwrap();
+        assert_eq!(
+            messages(&dtd, xml),
+            vec![
+                (
+                    1,
+                    "Element <p> not allowed in <doc>; expected <head>".to_string()
+                ),
+                (
+                    1,
+                    "Element <p> is missing required attribute @n".to_string()
+                ),
+                (
+                    1,
+                    "Element <doc> is incomplete; expected <head>".to_string()
+                ),
+            ]
+        );
+
+        let unknown = Doctype {
+            internal_subset: Some("<![ MAYBE [ ]]>".to_string()),
+            ..loose
+        };
+        let err = Dtd::from_doctype(&unknown, dir.path()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Unknown conditional section keyword: MAYBE"));
+    }
+
+    #[test]
+    fn test_entity_expansion_limit() {
+        // Each entity doubles the previous one: 2^40 bytes if expanded
+        let mut subset = String::from(r#"<!ENTITY % a0 "xxxxxxxx">"#);
+        for n in 1..=40 {
+            subset.push_str(&format!(r#"<!ENTITY % a{n} "%a{}; %a{};">"#, n - 1, n - 1));
+        }
+        subset.push_str("<!ELEMENT doc (#PCDATA)>");
+        let doctype = Doctype {
+            name: "doc".to_string(),
+            public_id: None,
+            system_id: None,
+            internal_subset: Some(subset),
+        };
+        let err = Dtd::from_doctype(&doctype, Path::new(".")).unwrap_err();
+        assert!(format!("{:#}", err).contains("Parameter entity expansion too large"));
+    }
+}
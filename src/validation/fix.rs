@@ -0,0 +1,341 @@
+//! Mechanical fixes offered by rules, applied as byte-range edits of the
+//! source so that everything outside the edited spans is kept byte for byte.
+
+use super::report::Report;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Unchanged lines shown around each change of a diff
+const CONTEXT_LINES: usize = 3;
+
+/// Replacement of a byte range of the source (empty for an insertion)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub fn insert(at: usize, text: impl Into<String>) -> Self {
+        Self::replace(at..at, text)
+    }
+
+    pub fn delete(range: Range<usize>) -> Self {
+        Self::replace(range, "")
+    }
+
+    pub fn replace(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+            replacement: text.into(),
+        }
+    }
+
+    /// Whether two edits touch the same bytes; two insertions at one
+    /// position conflict since their order would be arbitrary
+    fn overlaps(&self, other: &Edit) -> bool {
+        self.start < other.end && other.start < self.end || self.start == other.start
+    }
+}
+
+/// A fix for one message: all of its edits are applied, or none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    /// What the fix does, e.g. `Add n="13"`
+    pub description: String,
+    pub edits: Vec<Edit>,
+}
+
+impl Fix {
+    pub fn new(description: impl Into<String>, edits: Vec<Edit>) -> Self {
+        Self {
+            description: description.into(),
+            edits,
+        }
+    }
+}
+
+/// The fixes of one file that can be applied together
+#[derive(Debug, Clone, Default)]
+pub struct Patch {
+    /// Non-overlapping edits, in source order
+    edits: Vec<Edit>,
+
+    /// Number of fixes applied
+    pub applied: usize,
+
+    /// Number of fixes left out because they conflict with an earlier fix
+    /// or don't fit the source
+    pub skipped: usize,
+}
+
+impl Patch {
+    /// The fixes of `messages` in order; a fix that overlaps one already
+    /// taken is skipped (validating again after applying will offer it anew)
+    pub fn new<'a>(source: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> Self {
+        let mut patch = Self::default();
+        for fix in fixes {
+            let fits = fix.edits.iter().all(|e| {
+                e.start <= e.end
+                    && e.end <= source.len()
+                    && source.is_char_boundary(e.start)
+                    && source.is_char_boundary(e.end)
+            });
+            let conflicts = fix.edits.iter().enumerate().any(|(i, e)| {
+                patch.edits.iter().any(|taken| taken.overlaps(e))
+                    || fix.edits[..i].iter().any(|other| other.overlaps(e))
+            });
+            if fits && !conflicts {
+                patch.edits.extend(fix.edits.iter().cloned());
+                patch.applied += 1;
+            } else {
+                patch.skipped += 1;
+            }
+        }
+        patch.edits.sort_by_key(|e| (e.start, e.end));
+        patch
+    }
+
+    /// The fixes offered by the messages of a report
+    pub fn from_report(source: &str, report: &Report) -> Self {
+        Self::new(source, report.messages().filter_map(|m| m.fix.as_deref()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// The patched source
+    pub fn apply(&self, source: &str) -> String {
+        apply_edits(source, &self.edits, 0)
+    }
+
+    /// A unified diff from `source` to the patched source, with `path` in
+    /// both headers
+    pub fn unified_diff(&self, path: &str, source: &str) -> String {
+        if self.edits.is_empty() {
+            return String::new();
+        }
+        let lines = line_ranges(source);
+        let line_of = |offset: usize| match lines.binary_search_by(|l| l.start.cmp(&offset)) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+
+        // Changed line ranges, with the edits inside them; adjacent lines
+        // form one change
+        let mut changes: Vec<(Range<usize>, Vec<&Edit>)> = Vec::new();
+        for edit in &self.edits {
+            let first = line_of(edit.start);
+            let last = line_of(edit.end.saturating_sub(1).max(edit.start));
+            match changes.last_mut() {
+                Some((range, edits)) if first <= range.end => {
+                    range.end = range.end.max(last + 1);
+                    edits.push(edit);
+                }
+                _ => changes.push((first..last + 1, vec![edit])),
+            }
+        }
+
+        // Changes with overlapping context share a hunk
+        let mut hunks: Vec<Vec<(Range<usize>, Vec<&Edit>)>> = Vec::new();
+        for change in changes {
+            match hunks.last_mut() {
+                Some(hunk)
+                    if change.0.start <= hunk.last().expect("hunk").0.end + 2 * CONTEXT_LINES =>
+                {
+                    hunk.push(change)
+                }
+                _ => hunks.push(vec![change]),
+            }
+        }
+
+        let mut out = format!("--- {}\n+++ {}\n", path, path);
+        let mut shift: isize = 0;
+        for hunk in hunks {
+            let start = hunk[0].0.start.saturating_sub(CONTEXT_LINES);
+            let end = (hunk.last().expect("hunk").0.end + CONTEXT_LINES).min(lines.len());
+            let mut body = String::new();
+            let (mut old_count, mut new_count) = (0, 0);
+            let mut line = start;
+            for (range, edits) in &hunk {
+                for l in line..range.start {
+                    push_line(&mut body, ' ', &source[lines[l].clone()]);
+                }
+                let span = lines[range.start].start..lines[range.end - 1].end;
+                let old = &source[span.clone()];
+                let new = apply_edits(old, edits.iter().copied(), span.start);
+                for text in split_lines(old) {
+                    push_line(&mut body, '-', text);
+                    old_count += 1;
+                }
+                for text in split_lines(&new) {
+                    push_line(&mut body, '+', text);
+                    new_count += 1;
+                }
+                old_count += range.start - line;
+                new_count += range.start - line;
+                line = range.end;
+            }
+            for l in line..end {
+                push_line(&mut body, ' ', &source[lines[l].clone()]);
+            }
+            old_count += end - line;
+            new_count += end - line;
+
+            let new_start = start as isize + shift;
+            out.push_str(&format!(
+                "@@ -{} +{} @@\n",
+                hunk_range(start, old_count),
+                hunk_range(new_start as usize, new_count)
+            ));
+            out.push_str(&body);
+            shift += new_count as isize - old_count as isize;
+        }
+        out
+    }
+}
+
+/// Apply sorted, non-overlapping edits to `text`, which starts at byte
+/// `offset` of the source the edits refer to
+fn apply_edits<'e>(text: &str, edits: impl IntoIterator<Item = &'e Edit>, offset: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for edit in edits {
+        out.push_str(&text[pos..edit.start - offset]);
+        out.push_str(&edit.replacement);
+        pos = edit.end - offset;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// Byte ranges of the lines of `text`, each with its line break
+fn line_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices('\n') {
+        lines.push(start..i + 1);
+        start = i + 1;
+    }
+    if start < text.len() || lines.is_empty() {
+        lines.push(start..text.len());
+    }
+    lines
+}
+
+fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive('\n')
+}
+
+fn push_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// `start,count` of a hunk header, 1-based (0 for an empty range)
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Registry;
+
+    #[test]
+    fn test_apply_skips_conflicting_fixes() {
+        let source = "\u{FEFF}<TEI>\n<pb/>\n</TEI>";
+        let fixes = [
+            Fix::new("Strip the BOM", vec![Edit::delete(0..3)]),
+            Fix::new("Add n", vec![Edit::insert(12, " n=\"1\"")]),
+            Fix::new("Add n again", vec![Edit::insert(12, " n=\"2\"")]),
+            Fix::new("Out of range", vec![Edit::delete(40..41)]),
+        ];
+        let patch = Patch::new(source, &fixes);
+        assert_eq!((patch.applied, patch.skipped), (2, 2));
+        assert_eq!(patch.apply(source), "<TEI>\n<pb n=\"1\"/>\n</TEI>");
+    }
+
+    #[test]
+    fn test_builtin_fixes() {
+        let source = "\u{FEFF}<TEI>\n<pb ed=\"A\" n=\"4\"/><pb ed=\"A\"/><pb ed=\"B\"/>\n\
+                      <pb n=\"x\" ed=\"B\"/><pb ed=\"A\" n=\"6\"/>\n</TEI>";
+        let report = Registry::builtin().validate(source);
+        let fixes: Vec<&str> = report
+            .messages()
+            .filter_map(|m| m.fix.as_ref().map(|f| f.description.as_str()))
+            .collect();
+        assert_eq!(
+            fixes,
+            vec![
+                "Add n=\"5\" (from the neighbouring page breaks)",
+                "Strip the BOM",
+                "Add xmlns=\"http://www.tei-c.org/ns/1.0\"",
+            ]
+        );
+
+        let patched = Patch::from_report(source, &report).apply(source);
+        assert_eq!(
+            patched,
+            "<TEI xmlns=\"http://www.tei-c.org/ns/1.0\">\n\
+             <pb ed=\"A\" n=\"4\"/><pb n=\"5\" ed=\"A\"/><pb ed=\"B\"/>\n\
+             <pb n=\"x\" ed=\"B\"/><pb ed=\"A\" n=\"6\"/>\n</TEI>"
+        );
+        assert!(Registry::builtin()
+            .validate(&patched)
+            .messages()
+            .all(|m| m.fix.is_none()));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let source: String = (1..=12).map(|i| format!("line {}\n", i)).collect();
+        let at = |line: usize| source.match_indices('\n').nth(line - 2).unwrap().0 + 1;
+        let fixes = [
+            Fix::new("a", vec![Edit::insert(at(2), "new\n")]),
+            Fix::new("b", vec![Edit::replace(at(3)..at(3) + 6, "LINE 3")]),
+            Fix::new("c", vec![Edit::delete(at(12)..source.len())]),
+        ];
+        let patch = Patch::new(&source, &fixes);
+        let expected = "\
+--- f.xml
++++ f.xml
+@@ -1,6 +1,7 @@
+ line 1
+-line 2
+-line 3
++new
++line 2
++LINE 3
+ line 4
+ line 5
+ line 6
+@@ -9,4 +10,3 @@
+ line 9
+ line 10
+ line 11
+-line 12
+";
+        assert_eq!(patch.unified_diff("f.xml", &source), expected);
+
+        let source = "<TEI>";
+        let patch = Patch::new(
+            source,
+            &[Fix::new("ns", vec![Edit::insert(4, " xmlns=\"x\"")])],
+        );
+        assert_eq!(
+            patch.unified_diff("g.xml", source),
+            "--- g.xml\n+++ g.xml\n@@ -1 +1 @@\n-<TEI>\n\\ No newline at end of file\n+<TEI xmlns=\"x\">\n\\ No newline at end of file\n"
+        );
+    }
+}
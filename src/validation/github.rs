@@ -0,0 +1,87 @@
+//! GitHub Actions workflow commands (`::error file=…,line=…::text`), which
+//! GitHub shows as annotations on the changed files.
+
+use super::corpus::CorpusReport;
+use super::report::{Message, Severity};
+use std::fmt::Write;
+
+/// One workflow command per message and unreadable file
+pub fn github_annotations(report: &CorpusReport) -> String {
+    let mut out = String::new();
+    for file in &report.files {
+        for msg in file.report.messages() {
+            writeln!(out, "{}", annotation(&file.file, msg)).unwrap();
+        }
+    }
+    for failure in &report.failures {
+        writeln!(
+            out,
+            "::error file={}::{}",
+            escape_property(&failure.file),
+            escape_data(&failure.message)
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn annotation(file: &str, msg: &Message) -> String {
+    let command = match msg.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "notice",
+    };
+    let mut properties = format!("file={}", escape_property(file));
+    if msg.line > 0 {
+        write!(properties, ",line={},col={}", msg.line, msg.column.max(1)).unwrap();
+    }
+    write!(properties, ",title={}", escape_property(&msg.rule_id)).unwrap();
+    format!("::{} {}::{}", command, properties, escape_data(&msg.text))
+}
+
+/// Escaping of the message part, as in `@actions/core`
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escaping of `key=value` properties, which also reserve `:` and `,`
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::corpus::{FileReport, ValidationFailure};
+    use crate::validation::Registry;
+
+    #[test]
+    fn test_github_annotations() {
+        let registry = Registry::builtin();
+        let report = CorpusReport {
+            files: vec![FileReport {
+                file: "corpus/a,b.xml".to_string(),
+                report: registry.validate("<root>\n<pb n=\"1\"/></root>"),
+            }],
+            failures: vec![ValidationFailure {
+                file: "gone.xml".to_string(),
+                message: "Failed to read file: 100%\nmissing".to_string(),
+            }],
+        };
+
+        let lines: Vec<String> = github_annotations(&report)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "::error file=corpus/a%2Cb.xml,line=2,col=1,title=pb-required-attributes::<pb> missing @ed",
+                "::warning file=corpus/a%2Cb.xml,title=tei-root::Root element should contain 'tei' (case-insensitive), found <root>",
+                "::error file=gone.xml::Failed to read file: 100%25%0Amissing",
+            ]
+        );
+    }
+}
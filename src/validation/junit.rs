@@ -0,0 +1,190 @@
+//! JUnit XML reports for CI test views: one testcase per file, errors as
+//! its failure, warnings and info as its system output.
+
+use super::corpus::CorpusReport;
+use super::report::Message;
+use crate::export::html::escape_html;
+use std::fmt::Write;
+
+const SUITE_NAME: &str = "xml_structer validation";
+
+/// JUnit XML document of a corpus validation run
+pub fn to_junit_xml(report: &CorpusReport) -> String {
+    let tests = report.files.len() + report.failures.len();
+    let failures = report.files.iter().filter(|f| !f.report.is_valid()).count();
+    let errors = report.failures.len();
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<testsuites name="{}" tests="{}" failures="{}" errors="{}">"#,
+        SUITE_NAME, tests, failures, errors
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="0">"#,
+        SUITE_NAME, tests, failures, errors
+    )
+    .unwrap();
+
+    for file in &report.files {
+        let name = escape_html(&file.file);
+        let rep = &file.report;
+        let others: Vec<&Message> = rep.warnings.iter().chain(rep.info.iter()).collect();
+        if rep.errors.is_empty() && others.is_empty() {
+            writeln!(
+                xml,
+                r#"    <testcase classname="validation" name="{0}" file="{0}"/>"#,
+                name
+            )
+            .unwrap();
+            continue;
+        }
+
+        writeln!(
+            xml,
+            r#"    <testcase classname="validation" name="{0}" file="{0}">"#,
+            name
+        )
+        .unwrap();
+        if !rep.errors.is_empty() {
+            writeln!(
+                xml,
+                r#"      <failure message="{}" type="{}">{}</failure>"#,
+                escape_html(&format!(
+                    "{} validation errors, first: {}",
+                    rep.errors.len(),
+                    rep.errors[0].text
+                )),
+                escape_html(&rep.errors[0].rule_id),
+                escape_html(&message_lines(&rep.errors))
+            )
+            .unwrap();
+        }
+        if !others.is_empty() {
+            writeln!(
+                xml,
+                "      <system-out>{}</system-out>",
+                escape_html(&message_lines(others))
+            )
+            .unwrap();
+        }
+        writeln!(xml, "    </testcase>").unwrap();
+    }
+
+    for failure in &report.failures {
+        let name = escape_html(&failure.file);
+        writeln!(
+            xml,
+            r#"    <testcase classname="validation" name="{0}" file="{0}">"#,
+            name
+        )
+        .unwrap();
+        writeln!(
+            xml,
+            r#"      <error message="{0}" type="unreadable">{0}</error>"#,
+            escape_html(&failure.message)
+        )
+        .unwrap();
+        writeln!(xml, "    </testcase>").unwrap();
+    }
+
+    writeln!(xml, "  </testsuite>").unwrap();
+    writeln!(xml, "</testsuites>").unwrap();
+    xml
+}
+
+/// `line:column severity [rule] text`, one message per line
+fn message_lines<'a>(messages: impl IntoIterator<Item = &'a Message>) -> String {
+    messages
+        .into_iter()
+        .map(|m| {
+            format!(
+                "{}:{} {} [{}] {}",
+                m.line,
+                m.column,
+                m.severity.as_str(),
+                m.rule_id,
+                m.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::corpus::{FileReport, ValidationFailure};
+    use crate::validation::Registry;
+    use roxmltree::Document;
+
+    #[test]
+    fn test_junit_xml() {
+        let mut registry = Registry::builtin();
+        registry.disable("tei-namespace").unwrap();
+        let report = CorpusReport {
+            files: vec![
+                FileReport {
+                    file: "ok.xml".to_string(),
+                    report: registry.validate("<TEI><div><head/></div></TEI>"),
+                },
+                FileReport {
+                    file: "a&b.xml".to_string(),
+                    report: registry.validate("<TEI><pb/><head/></TEI>"),
+                },
+            ],
+            failures: vec![ValidationFailure {
+                file: "gone.xml".to_string(),
+                message: "Failed to read file".to_string(),
+            }],
+        };
+
+        let xml = to_junit_xml(&report);
+        let doc = Document::parse(&xml).unwrap();
+        let suite = doc
+            .descendants()
+            .find(|n| n.has_tag_name("testsuite"))
+            .unwrap();
+        assert_eq!(suite.attribute("tests"), Some("3"));
+        assert_eq!(suite.attribute("failures"), Some("1"));
+        assert_eq!(suite.attribute("errors"), Some("1"));
+
+        let cases: Vec<_> = suite
+            .children()
+            .filter(|n| n.has_tag_name("testcase"))
+            .collect();
+        assert_eq!(cases[0].attribute("name"), Some("ok.xml"));
+        assert!(!cases[0].has_children());
+
+        assert_eq!(cases[1].attribute("name"), Some("a&b.xml"));
+        let failure = cases[1]
+            .children()
+            .find(|n| n.has_tag_name("failure"))
+            .unwrap();
+        assert_eq!(failure.attribute("type"), Some("pb-required-attributes"));
+        assert_eq!(
+            failure.text(),
+            Some(
+                "1:6 error [pb-required-attributes] <pb> missing @ed\n\
+                 1:6 error [pb-required-attributes] <pb> missing @n"
+            )
+        );
+        let out = cases[1]
+            .children()
+            .find(|n| n.has_tag_name("system-out"))
+            .unwrap();
+        assert_eq!(
+            out.text(),
+            Some("1:11 warning [head-inside-div] <head> should be inside <div>")
+        );
+
+        let error = cases[2]
+            .children()
+            .find(|n| n.has_tag_name("error"))
+            .unwrap();
+        assert_eq!(error.attribute("message"), Some("Failed to read file"));
+    }
+}
@@ -1,4 +1,45 @@
+pub mod builtin;
+pub mod corpus;
+pub mod declared;
+pub mod dtd;
+pub mod fix;
+pub mod github;
+pub mod junit;
+pub mod pages;
+pub mod pointers;
+pub mod references;
+pub mod relaxng;
 pub mod report;
+pub mod rule;
+pub mod rules;
+pub mod sarif;
+pub mod schematron;
 pub mod validate;
+pub mod xsd;
 
+pub use corpus::{validate_corpus, validate_files, CorpusReport, CorpusSummary, ReportFormat};
+pub use declared::DeclaredSchemas;
+pub use dtd::Dtd;
+pub use fix::{Edit, Fix, Patch};
+pub use github::github_annotations;
+pub use junit::to_junit_xml;
+pub use pages::{NumberingScheme, PbSequence, PbSequenceConfig};
+pub use relaxng::RelaxNg;
 pub use report::{Message, Report, Severity};
+pub use rule::{Registry, Rule, RuleContext};
+pub use rules::{ConfigRule, RuleConfig};
+pub use sarif::SarifLog;
+pub use schematron::Schematron;
+pub use xsd::XmlSchema;
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// A schema or DTD reference as a path relative to `base`; `file://` URLs
+/// are local, other URLs are not fetched
+pub(crate) fn local_path(base: &Path, location: &str) -> Result<PathBuf> {
+    if location.contains("://") && !location.starts_with("file://") {
+        anyhow::bail!("Only local schema files are supported, found {}", location);
+    }
+    Ok(base.join(location.trim_start_matches("file://")))
+}
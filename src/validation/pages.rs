@@ -0,0 +1,233 @@
+//! Page-break sequences: the `<pb>`s of each edition (`@ed`), in document
+//! order, should be numbered without duplicates, reversals or gaps.
+//!
+//! ```toml
+//! [pb_sequence]
+//! scheme = "numeric"     # numeric (1, 2, 3), folio (1a, 1b, 2a) or recto-verso (1r, 1v, 2r)
+//! editions = [{ ed = "A", scheme = "folio" }]
+//! ```
+
+use super::report::Severity;
+use super::rule::{node_position, Rule, RuleContext};
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Id of the page-break sequence rule
+pub const PB_SEQUENCE: &str = "pb-sequence";
+
+/// How the `@n` of the page breaks of an edition count up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberingScheme {
+    /// Page numbers: 1, 2, 3
+    #[default]
+    Numeric,
+    /// Leaves with sides a and b: 1a, 1b, 2a
+    Folio,
+    /// Leaves with recto and verso sides: 1r, 1v, 2r
+    RectoVerso,
+}
+
+impl NumberingScheme {
+    /// Position of `n` in the sequence (page 1 and leaf 1a are 0); `None`
+    /// if `n` is not written in this scheme
+    fn position(self, n: &str) -> Option<u64> {
+        let n = n.trim();
+        let sides = match self {
+            Self::Numeric => return n.parse::<u64>().ok().and_then(|n| n.checked_sub(1)),
+            Self::Folio => ['a', 'b'],
+            Self::RectoVerso => ['r', 'v'],
+        };
+        let side = n.chars().last()?.to_ascii_lowercase();
+        let side = sides.iter().position(|s| *s == side)? as u64;
+        let leaf = n[..n.len() - 1].parse::<u64>().ok()?.checked_sub(1)?;
+        Some(leaf * 2 + side)
+    }
+}
+
+/// The `[pb_sequence]` configuration section
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PbSequenceConfig {
+    /// Scheme of the editions not listed in `editions`
+    pub scheme: NumberingScheme,
+    pub editions: Vec<EditionScheme>,
+}
+
+/// The numbering scheme of one edition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EditionScheme {
+    pub ed: String,
+    pub scheme: NumberingScheme,
+}
+
+impl PbSequenceConfig {
+    pub fn scheme(&self, ed: &str) -> NumberingScheme {
+        self.editions
+            .iter()
+            .find(|e| e.ed == ed)
+            .map_or(self.scheme, |e| e.scheme)
+    }
+}
+
+/// Checks the `@n` sequence of the page breaks of each edition; values not
+/// written in the edition's scheme (e.g. roman numerals in the front matter)
+/// are left out of the sequence
+#[derive(Debug, Default)]
+pub struct PbSequence {
+    config: PbSequenceConfig,
+}
+
+impl PbSequence {
+    pub fn new(config: PbSequenceConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// The page breaks of one edition seen so far
+#[derive(Default)]
+struct Edition<'a> {
+    /// Line of the first page break at each position
+    seen: HashMap<u64, usize>,
+
+    /// Position and `@n` of the previous numbered page break
+    last: Option<(u64, &'a str)>,
+}
+
+impl Rule for PbSequence {
+    fn id(&self) -> &str {
+        PB_SEQUENCE
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn description(&self) -> &str {
+        "The page breaks of each edition (@ed) should be numbered in sequence, without duplicates or gaps"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Check @n against the source; set the numbering scheme of an edition in the [pb_sequence] configuration")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_some() {
+            return;
+        }
+        let mut editions: HashMap<&str, Edition> = HashMap::new();
+        for pb in node.descendants().filter(|n| n.tag_name().name() == "pb") {
+            let (Some(ed), Some(n)) = (pb.attribute("ed"), pb.attribute("n")) else {
+                continue;
+            };
+            let Some(position) = self.config.scheme(ed).position(n) else {
+                continue;
+            };
+            let line = node_position(pb).0;
+            let edition = editions.entry(ed).or_default();
+            if let Some(first) = edition.seen.get(&position) {
+                ctx.report(
+                    pb,
+                    format!(
+                        "Duplicate page number n=\"{}\" in edition \"{}\" (first on line {})",
+                        n, ed, first
+                    ),
+                );
+            } else if let Some((last, last_n)) = edition.last {
+                if position < last {
+                    ctx.report(
+                        pb,
+                        format!(
+                            "Page number n=\"{}\" in edition \"{}\" is out of order after n=\"{}\"",
+                            n, ed, last_n
+                        ),
+                    );
+                } else if position > last + 1 {
+                    let skipped = position - last - 1;
+                    ctx.report(
+                        pb,
+                        format!(
+                            "Page number n=\"{}\" in edition \"{}\" skips {} page{} after n=\"{}\"",
+                            n,
+                            ed,
+                            skipped,
+                            if skipped == 1 { "" } else { "s" },
+                            last_n
+                        ),
+                    );
+                }
+            }
+            edition.seen.entry(position).or_insert(line);
+            edition.last = Some((position, n));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Registry;
+
+    #[test]
+    fn test_positions() {
+        assert_eq!(NumberingScheme::Numeric.position(" 12 "), Some(11));
+        assert_eq!(NumberingScheme::Numeric.position("0"), None);
+        assert_eq!(NumberingScheme::Numeric.position("xii"), None);
+        assert_eq!(NumberingScheme::Folio.position("1a"), Some(0));
+        assert_eq!(NumberingScheme::Folio.position("2B"), Some(3));
+        assert_eq!(NumberingScheme::Folio.position("2r"), None);
+        assert_eq!(NumberingScheme::RectoVerso.position("10v"), Some(19));
+    }
+
+    #[test]
+    fn test_sequences_per_edition() {
+        let xml = r#"<TEI>
+<pb ed="A" n="1"/><pb ed="B" n="1a"/>
+<pb ed="A" n="2"/><pb ed="B" n="1b"/>
+<pb ed="A" n="2"/><pb ed="B" n="2b"/>
+<pb ed="A" n="5"/><pb ed="B" n="2a"/>
+<pb ed="A" n="4"/><pb ed="A" n="vi"/><pb ed="A" n="5"/>
+</TEI>"#;
+        let config: PbSequenceConfig =
+            toml::from_str("editions = [{ ed = \"B\", scheme = \"folio\" }]").unwrap();
+        let mut registry = Registry::empty();
+        registry.register(PbSequence::new(config)).unwrap();
+        let report = registry.validate(xml);
+        let warnings: Vec<(usize, &str)> = report
+            .warnings
+            .iter()
+            .map(|m| (m.line, m.text.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (
+                    4,
+                    "Duplicate page number n=\"2\" in edition \"A\" (first on line 3)"
+                ),
+                (
+                    4,
+                    "Page number n=\"2b\" in edition \"B\" skips 1 page after n=\"1b\""
+                ),
+                (
+                    5,
+                    "Page number n=\"5\" in edition \"A\" skips 2 pages after n=\"2\""
+                ),
+                (
+                    5,
+                    "Page number n=\"2a\" in edition \"B\" is out of order after n=\"2b\""
+                ),
+                (
+                    6,
+                    "Page number n=\"4\" in edition \"A\" is out of order after n=\"5\""
+                ),
+                (
+                    6,
+                    "Duplicate page number n=\"5\" in edition \"A\" (first on line 5)"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! `xml:id` uniqueness and local `#id` pointers.
+//!
+//! Both rules look at the whole document from the root element, since a
+//! pointer may refer forward.
+
+use super::relaxng::XML_NS;
+use super::report::Severity;
+use super::rule::{Rule, RuleContext};
+use roxmltree::{Attribute, Node};
+use std::collections::{HashMap, HashSet};
+
+/// TEI attributes holding space-separated pointers
+pub const POINTER_ATTRIBUTES: &[&str] = &[
+    "target", "corresp", "ref", "facs", "ana", "resp", "who", "source",
+];
+
+/// 1-based line and column of an attribute
+pub(crate) fn attribute_position(node: Node, attr: &Attribute) -> (usize, usize) {
+    let pos = node.document().text_pos_at(attr.range().start);
+    (pos.row as usize, pos.col as usize)
+}
+
+/// The `xml:id`s of a document
+pub(crate) fn document_ids<'a>(root: Node<'a, '_>) -> HashSet<&'a str> {
+    root.descendants()
+        .filter_map(|n| n.attribute((XML_NS, "id")))
+        .collect()
+}
+
+pub(crate) struct XmlIdUnique;
+
+impl Rule for XmlIdUnique {
+    fn id(&self) -> &str {
+        "xml-id-unique"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "Each xml:id must be unique within its document"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Rename one of the elements, and update the pointers meant for it")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_some() {
+            return;
+        }
+        let mut first: HashMap<&str, usize> = HashMap::new();
+        for element in node.descendants().filter(|n| n.is_element()) {
+            let Some(attr) = element
+                .attributes()
+                .find(|a| a.namespace() == Some(XML_NS) && a.name() == "id")
+            else {
+                continue;
+            };
+            let (line, column) = attribute_position(element, &attr);
+            match first.get(attr.value()) {
+                Some(first_line) => ctx.report_at(
+                    line,
+                    column,
+                    format!(
+                        "Duplicate xml:id \"{}\" (first used on line {})",
+                        attr.value(),
+                        first_line
+                    ),
+                ),
+                None => {
+                    first.insert(attr.value(), line);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) struct LocalPointers;
+
+impl Rule for LocalPointers {
+    fn id(&self) -> &str {
+        "local-pointer"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "Local pointers (#id) in @target, @corresp, @ref, @facs, @ana, @resp, @who and @source must match an xml:id of the document"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Point to an existing xml:id, or add the xml:id to the intended element")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        if node.parent_element().is_some() {
+            return;
+        }
+        let ids = document_ids(node);
+        for element in node.descendants().filter(|n| n.is_element()) {
+            for attr in element.attributes() {
+                if attr.namespace().is_some() || !POINTER_ATTRIBUTES.contains(&attr.name()) {
+                    continue;
+                }
+                for id in attr
+                    .value()
+                    .split_whitespace()
+                    .filter_map(|t| t.strip_prefix('#'))
+                {
+                    if !ids.contains(id) {
+                        let (line, column) = attribute_position(element, &attr);
+                        ctx.report_at(
+                            line,
+                            column,
+                            format!(
+                                "@{} on <{}> points to missing xml:id \"{}\"",
+                                attr.name(),
+                                element.tag_name().name(),
+                                id
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validation::Registry;
+
+    #[test]
+    fn test_duplicate_ids_and_dangling_pointers() {
+        let xml = r##"<TEI xmlns="http://www.tei-c.org/ns/1.0">
+<person xml:id="p1"/><person xml:id="p2"/>
+<note xml:id="p1" resp="#p2 #ed">
+  <ref target="#p1 http://example.org/#x other.xml#p9"/>
+  <said who="#p3" corresp="#p2"/>
+</note>
+</TEI>"##;
+        let report = Registry::builtin().validate(xml);
+        let errors: Vec<(usize, usize, &str, &str)> = report
+            .errors
+            .iter()
+            .map(|m| (m.line, m.column, m.rule_id.as_str(), m.text.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    3,
+                    7,
+                    "xml-id-unique",
+                    "Duplicate xml:id \"p1\" (first used on line 2)"
+                ),
+                (
+                    3,
+                    19,
+                    "local-pointer",
+                    "@resp on <note> points to missing xml:id \"ed\""
+                ),
+                (
+                    5,
+                    9,
+                    "local-pointer",
+                    "@who on <said> points to missing xml:id \"p3\""
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Pointers between the files of a corpus, such as
+//! `@corresp="other.xml#d12"`.
+//!
+//! Unlike the other rules this is a corpus pass: while the files are
+//! validated in parallel, each one is indexed (its `xml:id`s and the pointers
+//! it holds into other files), and the pointers are resolved once every index
+//! is known. Targets outside the validated files are indexed from disk.
+
+use super::corpus::FileReport;
+use super::pointers::{attribute_position, POINTER_ATTRIBUTES};
+use super::relaxng::XML_NS;
+use super::report::{Message, Severity};
+use super::rule::{Rule, RuleContext};
+use rayon::prelude::*;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Id of the cross-file pointer checks, which `validate_files` runs after
+/// validating every file
+pub const CROSS_FILE_REFERENCE: &str = "cross-file-reference";
+
+/// Documents `cross-file-reference` for `explain` and lets `--disable` turn
+/// the corpus pass off; no single document can be checked on its own
+pub(crate) struct CrossFileReferences;
+
+impl Rule for CrossFileReferences {
+    fn id(&self) -> &str {
+        CROSS_FILE_REFERENCE
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        "Pointers into other files (other.xml#id) must name an existing file with exactly one such xml:id"
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Paths are relative to the referring file; fix the path or the id, or make the id unique in the target file")
+    }
+
+    fn check(&self, _node: Node, _ctx: &mut RuleContext) {}
+}
+
+/// A pointer into another file
+#[derive(Debug)]
+struct Reference {
+    line: usize,
+    column: usize,
+
+    /// `@attr on <element>`, the start of every message
+    origin: String,
+
+    /// The file part, as written
+    file: String,
+    id: String,
+}
+
+/// The `xml:id`s of a document and its pointers into other files
+#[derive(Debug, Default)]
+pub(crate) struct FileIndex {
+    /// Number of elements carrying each id
+    ids: HashMap<String, usize>,
+    references: Vec<Reference>,
+}
+
+impl FileIndex {
+    /// Index of a file outside the validated ones; `None` if `xml` is not
+    /// well-formed
+    pub(crate) fn build(xml: &str) -> Option<Self> {
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(xml, options).ok()?;
+        Some(Self::from_document(&doc))
+    }
+
+    /// Index of a document validation has already parsed
+    pub(crate) fn from_document(doc: &Document) -> Self {
+        let mut index = Self::default();
+        for element in doc.descendants().filter(|n| n.is_element()) {
+            if let Some(id) = element.attribute((XML_NS, "id")) {
+                *index.ids.entry(id.to_string()).or_default() += 1;
+            }
+            for attr in element.attributes() {
+                if attr.namespace().is_some() || !POINTER_ATTRIBUTES.contains(&attr.name()) {
+                    continue;
+                }
+                for (file, id) in attr.value().split_whitespace().filter_map(split_pointer) {
+                    let (line, column) = attribute_position(element, &attr);
+                    index.references.push(Reference {
+                        line,
+                        column,
+                        origin: format!("@{} on <{}>", attr.name(), element.tag_name().name()),
+                        file: file.to_string(),
+                        id: id.to_string(),
+                    });
+                }
+            }
+        }
+        index
+    }
+}
+
+/// File and fragment of a relative pointer such as `other.xml#d12`; `None`
+/// for local pointers, absolute URIs and pointers without a fragment
+fn split_pointer(token: &str) -> Option<(&str, &str)> {
+    let (file, id) = token.split_once('#')?;
+    let scheme = file.find(':').is_some_and(|colon| {
+        !file[..colon].contains('/') && file[..colon].len() > 1 // not a drive letter
+    });
+    if file.is_empty() || id.is_empty() || scheme {
+        return None;
+    }
+    Some((file, id))
+}
+
+/// The index of every target file, by canonical path
+#[derive(Default)]
+struct Targets {
+    indexes: HashMap<PathBuf, Option<FileIndex>>,
+
+    /// Canonical path of `(referring directory, file part)`; `None` if no
+    /// such file exists
+    resolved: HashMap<(PathBuf, String), Option<PathBuf>>,
+}
+
+impl Targets {
+    fn resolve(&mut self, dir: &Path, file: &str) -> Option<&PathBuf> {
+        self.resolved
+            .entry((dir.to_path_buf(), file.to_string()))
+            .or_insert_with(|| {
+                fs::canonicalize(dir.join(file))
+                    .ok()
+                    .filter(|p| p.is_file())
+            })
+            .as_ref()
+    }
+}
+
+/// Resolve the cross-file pointers of the validated files and add a message
+/// to the report of the referring file for each missing file, missing
+/// target and ambiguous target; `indexes` are in the order of `files`
+pub(crate) fn check_references(
+    files: &mut [FileReport],
+    indexes: Vec<Option<FileIndex>>,
+    rule: &dyn Rule,
+) {
+    let mut targets = Targets::default();
+    let mut sources = Vec::with_capacity(files.len());
+    for (file, index) in files.iter().zip(indexes) {
+        let path = Path::new(&file.file);
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        sources.push((dir, key.clone()));
+        targets.indexes.insert(key, index);
+    }
+
+    // Targets outside the validated files
+    let mut external = HashSet::new();
+    for (dir, key) in &sources {
+        let Some(index) = &targets.indexes[key] else {
+            continue;
+        };
+        let files: Vec<String> = index.references.iter().map(|r| r.file.clone()).collect();
+        for file in files {
+            if let Some(target) = targets.resolve(dir, &file).cloned() {
+                if !targets.indexes.contains_key(&target) {
+                    external.insert(target);
+                }
+            }
+        }
+    }
+    let external: Vec<(PathBuf, Option<FileIndex>)> = external
+        .into_par_iter()
+        .map(|path| {
+            let index = fs::read_to_string(&path)
+                .ok()
+                .and_then(|xml| FileIndex::build(&xml));
+            (path, index)
+        })
+        .collect();
+    targets.indexes.extend(external);
+
+    for (file, (dir, key)) in files.iter_mut().zip(&sources) {
+        let Some(index) = &targets.indexes[key] else {
+            continue;
+        };
+        for reference in &index.references {
+            let text = match targets.resolved[&(dir.clone(), reference.file.clone())].as_ref() {
+                None => format!(
+                    "{} points to missing file {}",
+                    reference.origin, reference.file
+                ),
+                Some(target) => match &targets.indexes[target] {
+                    // Not well-formed; its own report says so
+                    None => continue,
+                    Some(target) => match target.ids.get(&reference.id) {
+                        Some(1) => continue,
+                        None => format!(
+                            "{} points to missing xml:id \"{}\" in {}",
+                            reference.origin, reference.id, reference.file
+                        ),
+                        Some(n) => format!(
+                            "{} points to ambiguous xml:id \"{}\" in {} (defined {} times)",
+                            reference.origin, reference.id, reference.file, n
+                        ),
+                    },
+                },
+            };
+            let mut msg = Message::new(
+                rule.id(),
+                rule.default_severity(),
+                reference.line,
+                reference.column,
+                text,
+            );
+            if let Some(help) = rule.help() {
+                msg = msg.with_help(help);
+            }
+            file.report.push(msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validation::{validate_files, Registry};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cross_file_references() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join("a.xml"),
+            r##"<TEI xml:id="a">
+<div corresp="sub/b.xml#d1 sub/b.xml#d9 http://example.org/x.xml#d9"/>
+<persName ref="persons.xml#p1 ../persons.xml#p1 #a"/>
+<note resp="sub/b.xml#twice"/>
+</TEI>"##,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub/b.xml"),
+            r#"<TEI><div xml:id="d1" corresp="../a.xml#a"/><p xml:id="twice"/><p xml:id="twice"/></TEI>"#,
+        )
+        .unwrap();
+        let files = [
+            dir.join("a.xml").to_string_lossy().to_string(),
+            dir.join("sub/b.xml").to_string_lossy().to_string(),
+        ];
+
+        let mut registry = Registry::builtin();
+        registry.disable("tei-namespace").unwrap();
+        registry.disable("xml-id-unique").unwrap();
+        let report = validate_files(&files, &registry, None);
+        let errors: Vec<(usize, usize, &str)> = report.files[0]
+            .report
+            .errors
+            .iter()
+            .map(|m| (m.line, m.column, m.text.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    2,
+                    6,
+                    "@corresp on <div> points to missing xml:id \"d9\" in sub/b.xml"
+                ),
+                (
+                    3,
+                    11,
+                    "@ref on <persName> points to missing file persons.xml"
+                ),
+                (
+                    3,
+                    11,
+                    "@ref on <persName> points to missing file ../persons.xml"
+                ),
+                (
+                    4,
+                    7,
+                    "@resp on <note> points to ambiguous xml:id \"twice\" in sub/b.xml (defined 2 times)"
+                ),
+            ]
+        );
+        assert!(report.files[1].report.is_valid());
+
+        registry.disable("cross-file-reference").unwrap();
+        assert!(validate_files(&files, &registry, None).is_valid());
+    }
+}
@@ -0,0 +1,357 @@
+//! Compiling a loaded schema into simplified patterns: grammars and their
+//! defines are resolved, `combine` is applied, and every `element` gets an
+//! entry in the element table so content models can be recursive.
+
+use super::datatype::Datatype;
+use super::load::Elem;
+use super::pattern::{Arena, NameClass, PatId, Patterns, EMPTY, NOT_ALLOWED, TEXT};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+
+/// A `<value>` with the datatype it is compared in
+#[derive(Debug)]
+pub(super) struct ValueSpec {
+    pub datatype: Datatype,
+    pub value: String,
+}
+
+/// Everything a validation needs, immutable once compiled
+#[derive(Debug)]
+pub(super) struct Schema {
+    pub patterns: Patterns,
+    pub start: PatId,
+    /// Content pattern and name class of each `element`
+    pub elements: Vec<(PatId, u32)>,
+    pub name_classes: Vec<NameClass>,
+    pub datatypes: Vec<Datatype>,
+    pub values: Vec<ValueSpec>,
+}
+
+/// The defines and start of one `grammar`
+struct Scope<'t> {
+    defines: HashMap<&'t str, Vec<&'t Elem>>,
+    starts: Vec<&'t Elem>,
+    parent: Option<usize>,
+}
+
+struct Compiler<'t> {
+    arena: Arena<'static>,
+    scopes: Vec<Scope<'t>>,
+    defines: HashMap<(usize, &'t str), PatId>,
+    in_progress: HashSet<(usize, &'t str)>,
+    /// Elements whose content is compiled after the current define
+    pending: Vec<(usize, &'t [Elem], usize)>,
+    elements: Vec<(PatId, u32)>,
+    name_classes: Vec<NameClass>,
+    datatypes: Vec<Datatype>,
+    values: Vec<ValueSpec>,
+}
+
+/// Compile a loaded schema; a root pattern other than `grammar` is
+/// treated as the start of an implicit grammar
+pub(super) fn compile(root: &Elem) -> Result<Schema> {
+    let wrapped;
+    let grammar = if root.name == "grammar" {
+        root
+    } else {
+        wrapped = Elem {
+            name: "grammar".to_string(),
+            children: vec![Elem {
+                name: "start".to_string(),
+                children: vec![root.clone()],
+                location: root.location.clone(),
+                ..Elem::default()
+            }],
+            location: root.location.clone(),
+            ..Elem::default()
+        };
+        &wrapped
+    };
+
+    let mut compiler = Compiler {
+        arena: Arena::new(),
+        scopes: Vec::new(),
+        defines: HashMap::new(),
+        in_progress: HashSet::new(),
+        pending: Vec::new(),
+        elements: Vec::new(),
+        name_classes: Vec::new(),
+        datatypes: Vec::new(),
+        values: Vec::new(),
+    };
+    let scope = compiler.new_scope(grammar, None);
+    let start = compiler.start(scope, grammar)?;
+    while let Some((element, content, scope)) = compiler.pending.pop() {
+        let p = compiler.group(content, scope)?;
+        compiler.elements[element].0 = p;
+    }
+
+    Ok(Schema {
+        patterns: compiler.arena.into_patterns(),
+        start,
+        elements: compiler.elements,
+        name_classes: compiler.name_classes,
+        datatypes: compiler.datatypes,
+        values: compiler.values,
+    })
+}
+
+impl<'t> Compiler<'t> {
+    fn new_scope(&mut self, grammar: &'t Elem, parent: Option<usize>) -> usize {
+        let mut scope = Scope {
+            defines: HashMap::new(),
+            starts: Vec::new(),
+            parent,
+        };
+        collect(&grammar.children, &mut scope);
+        self.scopes.push(scope);
+        self.scopes.len() - 1
+    }
+
+    fn start(&mut self, scope: usize, grammar: &Elem) -> Result<PatId> {
+        let starts = self.scopes[scope].starts.clone();
+        if starts.is_empty() {
+            anyhow::bail!("<grammar> without <start> at {}", grammar.location);
+        }
+        self.combine(&starts, scope, "start")
+    }
+
+    fn define(&mut self, scope: usize, name: &'t str, elem: &Elem) -> Result<PatId> {
+        let key = (scope, name);
+        if let Some(p) = self.defines.get(&key) {
+            return Ok(*p);
+        }
+        let defines = self.scopes[scope]
+            .defines
+            .get(name)
+            .cloned()
+            .with_context(|| {
+                format!(
+                    "Reference to undefined pattern {} at {}",
+                    name, elem.location
+                )
+            })?;
+        if !self.in_progress.insert(key) {
+            anyhow::bail!(
+                "Pattern {} refers to itself outside of an element at {}",
+                name,
+                elem.location
+            );
+        }
+        let p = self.combine(&defines, scope, name)?;
+        self.in_progress.remove(&key);
+        self.defines.insert(key, p);
+        Ok(p)
+    }
+
+    /// Merge the bodies of several `define`s (or `start`s) of one name
+    fn combine(&mut self, parts: &[&'t Elem], scope: usize, name: &str) -> Result<PatId> {
+        if parts.iter().filter(|d| d.attr("combine").is_none()).count() > 1 {
+            anyhow::bail!(
+                "Several definitions of {} without @combine at {}",
+                name,
+                parts[1].location
+            );
+        }
+        let mut methods: Vec<&str> = parts.iter().filter_map(|d| d.attr("combine")).collect();
+        methods.sort_unstable();
+        methods.dedup();
+        let interleave = match methods.as_slice() {
+            [] | ["choice"] => false,
+            ["interleave"] => true,
+            _ => anyhow::bail!("Conflicting @combine for {} at {}", name, parts[0].location),
+        };
+
+        let mut result = None;
+        for part in parts {
+            let p = self.group(&part.children, scope)?;
+            result = Some(match result {
+                None => p,
+                Some(r) if interleave => self.arena.interleave(r, p),
+                Some(r) => self.arena.choice(r, p),
+            });
+        }
+        Ok(result.unwrap_or(NOT_ALLOWED))
+    }
+
+    /// Several child patterns form an implicit group
+    fn group(&mut self, children: &'t [Elem], scope: usize) -> Result<PatId> {
+        let mut p = EMPTY;
+        for child in children {
+            let q = self.pattern(child, scope)?;
+            p = self.arena.group(p, q);
+        }
+        Ok(p)
+    }
+
+    fn pattern(&mut self, e: &'t Elem, scope: usize) -> Result<PatId> {
+        Ok(match e.name.as_str() {
+            "element" => {
+                let (name_class, content) = self.name_class_and_content(e)?;
+                let id = self.elements.len();
+                self.elements.push((NOT_ALLOWED, name_class));
+                self.pending.push((id, content, scope));
+                self.arena.element(name_class, id as u32)
+            }
+            "attribute" => {
+                let (name_class, content) = self.name_class_and_content(e)?;
+                let p = if content.is_empty() {
+                    TEXT
+                } else {
+                    self.group(content, scope)?
+                };
+                self.arena.attribute(name_class, p)
+            }
+            "group" => self.group(&e.children, scope)?,
+            "interleave" => {
+                let mut p = EMPTY;
+                for child in &e.children {
+                    let q = self.pattern(child, scope)?;
+                    p = self.arena.interleave(p, q);
+                }
+                p
+            }
+            "mixed" => {
+                let p = self.group(&e.children, scope)?;
+                self.arena.interleave(p, TEXT)
+            }
+            "choice" => {
+                let mut p = NOT_ALLOWED;
+                for child in &e.children {
+                    let q = self.pattern(child, scope)?;
+                    p = self.arena.choice(p, q);
+                }
+                p
+            }
+            "optional" => {
+                let p = self.group(&e.children, scope)?;
+                self.arena.choice(p, EMPTY)
+            }
+            "zeroOrMore" => {
+                let p = self.group(&e.children, scope)?;
+                let p = self.arena.one_or_more(p);
+                self.arena.choice(p, EMPTY)
+            }
+            "oneOrMore" => {
+                let p = self.group(&e.children, scope)?;
+                self.arena.one_or_more(p)
+            }
+            "list" => {
+                let p = self.group(&e.children, scope)?;
+                self.arena.list(p)
+            }
+            "empty" => EMPTY,
+            "text" => TEXT,
+            "notAllowed" => NOT_ALLOWED,
+            "ref" => self.define(scope, e.required("name")?, e)?,
+            "parentRef" => {
+                let parent = self.scopes[scope].parent.with_context(|| {
+                    format!("<parentRef> outside a nested grammar at {}", e.location)
+                })?;
+                self.define(parent, e.required("name")?, e)?
+            }
+            "grammar" => {
+                let nested = self.new_scope(e, Some(scope));
+                self.start(nested, e)?
+            }
+            "data" => {
+                let params: Vec<(String, String)> = e
+                    .children
+                    .iter()
+                    .filter(|c| c.name == "param")
+                    .map(|c| Ok((c.required("name")?.to_string(), c.text.clone())))
+                    .collect::<Result<_>>()?;
+                let datatype = Datatype::new(&e.datatype_library, e.required("type")?, &params)
+                    .with_context(|| format!("Invalid <data> at {}", e.location))?;
+                let except = match e.children.iter().find(|c| c.name == "except") {
+                    Some(except) => self.choice_of(&except.children, scope)?,
+                    None => NOT_ALLOWED,
+                };
+                self.datatypes.push(datatype);
+                self.arena.data(self.datatypes.len() as u32 - 1, except)
+            }
+            "value" => {
+                let (library, name) = match e.attr("type") {
+                    Some(name) => (e.datatype_library.as_str(), name),
+                    None => ("", "token"),
+                };
+                let datatype = Datatype::new(library, name, &[])
+                    .with_context(|| format!("Invalid <value> at {}", e.location))?;
+                self.values.push(ValueSpec {
+                    datatype,
+                    value: e.text.clone(),
+                });
+                self.arena.value(self.values.len() as u32 - 1)
+            }
+            other => anyhow::bail!("Unexpected <{}> at {}", other, e.location),
+        })
+    }
+
+    fn choice_of(&mut self, children: &'t [Elem], scope: usize) -> Result<PatId> {
+        let mut p = NOT_ALLOWED;
+        for child in children {
+            let q = self.pattern(child, scope)?;
+            p = self.arena.choice(p, q);
+        }
+        Ok(p)
+    }
+
+    /// Name class of an `element` or `attribute`, from its `name` attribute
+    /// or its first child, and the remaining content children
+    fn name_class_and_content(&mut self, e: &'t Elem) -> Result<(u32, &'t [Elem])> {
+        let (name_class, content) = match &e.qname {
+            Some((ns, local)) => (NameClass::from_qname(ns, local), &e.children[..]),
+            None => {
+                let first = e
+                    .children
+                    .first()
+                    .with_context(|| format!("<{}> without a name at {}", e.name, e.location))?;
+                (name_class(first)?, &e.children[1..])
+            }
+        };
+        self.name_classes.push(name_class);
+        Ok((self.name_classes.len() as u32 - 1, content))
+    }
+}
+
+fn name_class(e: &Elem) -> Result<NameClass> {
+    let except = || -> Result<Option<Box<NameClass>>> {
+        match e.children.iter().find(|c| c.name == "except") {
+            Some(except) => {
+                let classes = except
+                    .children
+                    .iter()
+                    .map(name_class)
+                    .collect::<Result<_>>()?;
+                Ok(Some(Box::new(NameClass::Choice(classes))))
+            }
+            None => Ok(None),
+        }
+    };
+    Ok(match e.name.as_str() {
+        "name" => {
+            let (ns, local) = e.qname.as_ref().expect("names are resolved when loading");
+            NameClass::from_qname(ns, local)
+        }
+        "anyName" => NameClass::AnyName(except()?),
+        "nsName" => NameClass::NsName(e.ns.clone(), except()?),
+        "choice" => NameClass::Choice(e.children.iter().map(name_class).collect::<Result<_>>()?),
+        other => anyhow::bail!("Expected a name class, found <{}> at {}", other, e.location),
+    })
+}
+
+/// Gather the components of a grammar, looking into `div`s
+fn collect<'t>(children: &'t [Elem], scope: &mut Scope<'t>) {
+    for child in children {
+        match child.name.as_str() {
+            "start" => scope.starts.push(child),
+            "define" => {
+                if let Some(name) = child.attr("name") {
+                    scope.defines.entry(name).or_default().push(child);
+                }
+            }
+            "div" => collect(&child.children, scope),
+            _ => {}
+        }
+    }
+}
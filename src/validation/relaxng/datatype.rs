@@ -0,0 +1,384 @@
+//! Datatypes of `<data>` and `<value>`: the built-in library (`string`,
+//! `token`) and the XML Schema types TEI schemas use, with their facets.
+//!
+//! Types of other libraries, and XML Schema types without a lexical check
+//! here, accept any value.
+
+use anyhow::{Context, Result};
+use regex_automata::meta::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing::warn;
+
+pub(crate) const XSD_LIBRARY: &str = "http://www.w3.org/2001/XMLSchema-datatypes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Library {
+    Builtin,
+    Xsd,
+    Unknown,
+}
+
+#[derive(Debug)]
+enum Facet {
+    Pattern(Regex),
+    Length(usize),
+    MinLength(usize),
+    MaxLength(usize),
+    MinInclusive(f64),
+    MaxInclusive(f64),
+    MinExclusive(f64),
+    MaxExclusive(f64),
+}
+
+#[derive(Debug)]
+pub(super) struct Datatype {
+    library: Library,
+    name: String,
+    facets: Vec<Facet>,
+}
+
+impl Datatype {
+    /// A type with `<param>` facets; unsupported patterns are skipped with a
+    /// warning rather than rejecting the schema
+    pub fn new(library: &str, name: &str, params: &[(String, String)]) -> Result<Self> {
+        let library = match library {
+            "" => Library::Builtin,
+            XSD_LIBRARY => Library::Xsd,
+            _ => Library::Unknown,
+        };
+        if library == Library::Builtin && !matches!(name, "string" | "token") {
+            anyhow::bail!("Unknown built-in datatype: {}", name);
+        }
+
+        let mut facets = Vec::new();
+        for (param, value) in params {
+            let number = || {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid value for param {}: {}", param, value))
+            };
+            let length = || {
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid value for param {}: {}", param, value))
+            };
+            facets.push(match param.as_str() {
+                "pattern" => match Regex::new(&format!("^(?:{})$", translate_regex(value))) {
+                    Ok(regex) => Facet::Pattern(regex),
+                    Err(e) => {
+                        warn!("Ignoring unsupported datatype pattern {:?}: {}", value, e);
+                        continue;
+                    }
+                },
+                "length" => Facet::Length(length()?),
+                "minLength" => Facet::MinLength(length()?),
+                "maxLength" => Facet::MaxLength(length()?),
+                "minInclusive" => Facet::MinInclusive(number()?),
+                "maxInclusive" => Facet::MaxInclusive(number()?),
+                "minExclusive" => Facet::MinExclusive(number()?),
+                "maxExclusive" => Facet::MaxExclusive(number()?),
+                _ => continue,
+            });
+        }
+
+        Ok(Self {
+            library,
+            name: name.to_string(),
+            facets,
+        })
+    }
+
+    /// Whether `value` is in the lexical space of the type
+    pub fn allows(&self, value: &str) -> bool {
+        if self.library != Library::Xsd {
+            return true;
+        }
+        let value = self.normalize(value);
+        lexical_ok(&self.name, &value) && self.facets.iter().all(|f| self.facet_ok(f, &value))
+    }
+
+    /// Whether two values are equal in the value space of the type
+    pub fn equal(&self, a: &str, b: &str) -> bool {
+        let (a, b) = (self.normalize(a), self.normalize(b));
+        if self.library == Library::Xsd {
+            if is_numeric(&self.name) {
+                return matches!((a.parse::<f64>(), b.parse::<f64>()), (Ok(x), Ok(y)) if x == y);
+            }
+            if self.name == "boolean" {
+                return matches!(a.as_str(), "true" | "1") == matches!(b.as_str(), "true" | "1");
+            }
+        }
+        a == b
+    }
+
+    fn normalize(&self, value: &str) -> String {
+        match (self.library, self.name.as_str()) {
+            (Library::Builtin | Library::Xsd, "string") | (Library::Unknown, _) => {
+                value.to_string()
+            }
+            (Library::Xsd, "normalizedString") => value.replace(['\t', '\n', '\r'], " "),
+            _ => value.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    fn facet_ok(&self, facet: &Facet, value: &str) -> bool {
+        let length = || {
+            if matches!(self.name.as_str(), "NMTOKENS" | "IDREFS" | "ENTITIES") {
+                value.split_whitespace().count()
+            } else {
+                value.chars().count()
+            }
+        };
+        let number = || value.parse::<f64>().unwrap_or(f64::NAN);
+        match facet {
+            Facet::Pattern(regex) => regex.is_match(value),
+            Facet::Length(n) => length() == *n,
+            Facet::MinLength(n) => length() >= *n,
+            Facet::MaxLength(n) => length() <= *n,
+            Facet::MinInclusive(n) => number() >= *n,
+            Facet::MaxInclusive(n) => number() <= *n,
+            Facet::MinExclusive(n) => number() > *n,
+            Facet::MaxExclusive(n) => number() < *n,
+        }
+    }
+}
+
+fn is_numeric(name: &str) -> bool {
+    name == "decimal" || name == "float" || name == "double" || integer_range(name).is_some()
+}
+
+/// Bounds of the integer types derived from `xsd:integer`
+fn integer_range(name: &str) -> Option<(i128, i128)> {
+    Some(match name {
+        "integer" => (i128::MIN, i128::MAX),
+        "long" => (i64::MIN as i128, i64::MAX as i128),
+        "int" => (i32::MIN as i128, i32::MAX as i128),
+        "short" => (i16::MIN as i128, i16::MAX as i128),
+        "byte" => (i8::MIN as i128, i8::MAX as i128),
+        "nonNegativeInteger" => (0, i128::MAX),
+        "positiveInteger" => (1, i128::MAX),
+        "nonPositiveInteger" => (i128::MIN, 0),
+        "negativeInteger" => (i128::MIN, -1),
+        "unsignedLong" => (0, u64::MAX as i128),
+        "unsignedInt" => (0, u32::MAX as i128),
+        "unsignedShort" => (0, u16::MAX as i128),
+        "unsignedByte" => (0, u8::MAX as i128),
+        _ => return None,
+    })
+}
+
+fn lexical_ok(name: &str, value: &str) -> bool {
+    if let Some((min, max)) = integer_range(name) {
+        let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+        // Out of i128 range only matters for unbounded integer
+        return value
+            .parse::<i128>()
+            .map_or(name == "integer", |n| n >= min && n <= max);
+    }
+    match name {
+        "string" | "normalizedString" | "token" | "anyURI" | "anySimpleType" => true,
+        "language" => {
+            let mut parts = value.split('-');
+            let first = parts.next().unwrap_or_default();
+            (1..=8).contains(&first.len())
+                && first.bytes().all(|b| b.is_ascii_alphabetic())
+                && parts.all(|p| {
+                    (1..=8).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_alphanumeric())
+                })
+        }
+        "Name" => is_name(value),
+        "NCName" | "ID" | "IDREF" | "ENTITY" => is_ncname(value),
+        "NMTOKEN" => is_nmtoken(value),
+        "NMTOKENS" => non_empty_list(value, is_nmtoken),
+        "IDREFS" | "ENTITIES" => non_empty_list(value, is_ncname),
+        "QName" => match value.split_once(':') {
+            Some((prefix, local)) => is_ncname(prefix) && is_ncname(local),
+            None => is_ncname(value),
+        },
+        "boolean" => matches!(value, "true" | "false" | "1" | "0"),
+        "decimal" => is_decimal(value),
+        "float" | "double" => {
+            matches!(value, "INF" | "-INF" | "+INF" | "NaN")
+                || (value.bytes().all(|b| b"+-.eE0123456789".contains(&b))
+                    && value.parse::<f64>().is_ok())
+        }
+        "hexBinary" => {
+            value.len().is_multiple_of(2) && value.bytes().all(|b| b.is_ascii_hexdigit())
+        }
+        "base64Binary" => value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+/= ".contains(&b)),
+        // `P` and `PT` alone match the duration regex but are not durations
+        "duration" if value.ends_with(['P', 'T']) => false,
+        _ => temporal_regexes()
+            .get(name)
+            .is_none_or(|regex| regex.is_match(value)),
+    }
+}
+
+/// Lexical forms of the date, time and duration types
+fn temporal_regexes() -> &'static HashMap<&'static str, Regex> {
+    static REGEXES: OnceLock<HashMap<&'static str, Regex>> = OnceLock::new();
+    REGEXES.get_or_init(|| {
+        let year = r"-?(?:[1-9][0-9]{4,}|[0-9]{4})";
+        let month = "(?:0[1-9]|1[0-2])";
+        let day = "(?:0[1-9]|[12][0-9]|3[01])";
+        let time = r"(?:[01][0-9]|2[0-4]):[0-5][0-9]:[0-5][0-9](?:\.[0-9]+)?";
+        let tz = "(?:Z|[+-](?:0[0-9]|1[0-4]):[0-5][0-9])?";
+        [
+            ("date", format!("{year}-{month}-{day}{tz}")),
+            ("dateTime", format!("{year}-{month}-{day}T{time}{tz}")),
+            ("time", format!("{time}{tz}")),
+            ("gYear", format!("{year}{tz}")),
+            ("gYearMonth", format!("{year}-{month}{tz}")),
+            ("gMonth", format!("--{month}{tz}")),
+            ("gMonthDay", format!("--{month}-{day}{tz}")),
+            ("gDay", format!("---{day}{tz}")),
+            (
+                "duration",
+                r"-?P(?:(?:[0-9]+Y)?(?:[0-9]+M)?(?:[0-9]+D)?)(?:T(?:[0-9]+H)?(?:[0-9]+M)?(?:[0-9]+(?:\.[0-9]+)?S)?)?"
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, pattern)| (name, Regex::new(&format!("^(?:{})$", pattern)).unwrap()))
+        .collect()
+    })
+}
+
+fn is_name_start(c: char) -> bool {
+    c == '_' || c == ':' || c.is_alphabetic()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c == '-' || c == '.' || c == '\u{B7}' || c.is_alphanumeric()
+}
+
+fn is_name(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next().is_some_and(is_name_start) && chars.all(is_name_char)
+}
+
+fn is_ncname(value: &str) -> bool {
+    is_name(value) && !value.contains(':')
+}
+
+fn is_nmtoken(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(is_name_char)
+}
+
+fn non_empty_list(value: &str, item: fn(&str) -> bool) -> bool {
+    let mut items = value.split_whitespace().peekable();
+    items.peek().is_some() && items.all(item)
+}
+
+fn is_decimal(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    !(int.is_empty() && frac.is_empty())
+        && int.bytes().all(|b| b.is_ascii_digit())
+        && frac.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Translate an XML Schema regular expression to `regex` syntax: `\i` and
+/// `\c` name character escapes, class subtraction `[a-z-[aeiou]]`, and `^`
+/// and `$`, which are ordinary characters in XML Schema
+fn translate_regex(pattern: &str) -> String {
+    const NAME_START: &str = r"_:\p{L}";
+    const NAME_CHAR: &str = r"\-._:\p{L}\p{M}\p{N}\x{B7}";
+
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    let mut class_depth = 0usize;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('i') if class_depth > 0 => out.push_str(NAME_START),
+                Some('c') if class_depth > 0 => out.push_str(NAME_CHAR),
+                Some('i') => out.push_str(&format!("[{}]", NAME_START)),
+                Some('c') => out.push_str(&format!("[{}]", NAME_CHAR)),
+                Some('I') => out.push_str(&format!("[^{}]", NAME_START)),
+                Some('C') => out.push_str(&format!("[^{}]", NAME_CHAR)),
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push_str(r"\\"),
+            },
+            '[' => {
+                class_depth += 1;
+                out.push('[');
+            }
+            ']' if class_depth > 0 => {
+                class_depth -= 1;
+                out.push(']');
+            }
+            '-' if class_depth > 0 && chars.peek() == Some(&'[') => out.push_str("--"),
+            '^' | '$' if class_depth == 0 => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xsd(name: &str, params: &[(&str, &str)]) -> Datatype {
+        let params: Vec<(String, String)> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Datatype::new(XSD_LIBRARY, name, &params).unwrap()
+    }
+
+    #[test]
+    fn test_xsd_lexical_spaces() {
+        assert!(xsd("nonNegativeInteger", &[]).allows(" 12 "));
+        assert!(!xsd("nonNegativeInteger", &[]).allows("-1"));
+        assert!(!xsd("byte", &[]).allows("128"));
+        assert!(xsd("decimal", &[]).allows("-.5"));
+        assert!(!xsd("double", &[]).allows("inf"));
+        assert!(xsd("NCName", &[]).allows("a.b-c"));
+        assert!(!xsd("NCName", &[]).allows("tei:p"));
+        assert!(xsd("IDREFS", &[]).allows("a b"));
+        assert!(xsd("language", &[]).allows("en-GB"));
+        assert!(xsd("date", &[]).allows("1850-02-28"));
+        assert!(!xsd("gYear", &[]).allows("185"));
+        assert!(xsd("duration", &[]).allows("P1Y2MT3H"));
+        assert!(!xsd("duration", &[]).allows("PT"));
+        assert!(xsd("anyURI", &[]).allows("#p1"));
+    }
+
+    #[test]
+    fn test_facets_and_equality() {
+        let pattern = xsd("token", &[("pattern", r"\i\c*(\s\i\c*)*")]);
+        assert!(pattern.allows("a b-1"));
+        assert!(!pattern.allows("1a"));
+        let subtraction = xsd("token", &[("pattern", "[a-z-[aeiou]]+")]);
+        assert!(subtraction.allows("xyz"));
+        assert!(!subtraction.allows("abc"));
+        assert!(xsd("string", &[("pattern", "$^")]).allows("$^"));
+
+        let range = xsd("integer", &[("minInclusive", "1"), ("maxExclusive", "10")]);
+        assert!(range.allows("9") && !range.allows("10") && !range.allows("0"));
+        assert!(!xsd("string", &[("maxLength", "3")]).allows("four"));
+
+        assert!(xsd("decimal", &[]).equal("1.0", "1"));
+        assert!(xsd("boolean", &[]).equal("1", "true"));
+        let token = Datatype::new("", "token", &[]).unwrap();
+        assert!(token.equal(" a  b ", "a b"));
+        assert!(!Datatype::new("", "string", &[]).unwrap().equal(" a", "a"));
+        assert!(Datatype::new("", "integer", &[]).is_err());
+    }
+}
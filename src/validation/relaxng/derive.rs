@@ -0,0 +1,417 @@
+//! Derivatives of patterns with respect to parts of a document, after
+//! James Clark's "An algorithm for RELAX NG validation".
+//!
+//! Validation walks the document: each start tag, attribute, text and end
+//! tag turns the current pattern into what remains allowed after it. A
+//! `NOT_ALLOWED` result is a violation; the walker reports it and goes on
+//! with the pattern from before the offending item.
+
+use super::compile::Schema;
+use super::pattern::{Arena, Pat, PatId, EMPTY, NOT_ALLOWED, TEXT};
+use std::collections::{HashMap, HashSet};
+
+/// Continuation applied to the `After` patterns of a derivative
+#[derive(Clone, Copy)]
+enum Then {
+    /// `interleave(x, p)`
+    InterleaveLeft(PatId),
+    /// `interleave(p, x)`
+    InterleaveRight(PatId),
+    /// `group(x, p)`
+    Group(PatId),
+    /// `after(x, p)`
+    After(PatId),
+}
+
+/// Derivative state for one document
+pub(super) struct Deriver<'s> {
+    schema: &'s Schema,
+    arena: Arena<'s>,
+    start_tag_memo: HashMap<(PatId, String, String), PatId>,
+    close_memo: HashMap<(PatId, bool), PatId>,
+    /// Text derivatives of patterns without datatypes, which don't
+    /// depend on the text
+    text_memo: HashMap<PatId, PatId>,
+}
+
+impl<'s> Deriver<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        Self {
+            schema,
+            arena: Arena::over(&schema.patterns),
+            start_tag_memo: HashMap::new(),
+            close_memo: HashMap::new(),
+            text_memo: HashMap::new(),
+        }
+    }
+
+    pub fn choice(&mut self, a: PatId, b: PatId) -> PatId {
+        self.arena.choice(a, b)
+    }
+
+    /// Alternatives of `p`, derived one by one; keeps the recursion depth
+    /// independent of the size of a choice
+    fn each_alternative(
+        &mut self,
+        p: PatId,
+        mut f: impl FnMut(&mut Self, PatId) -> PatId,
+    ) -> PatId {
+        let mut members = Vec::new();
+        self.arena.choice_members(p, &mut members);
+        let mut result = NOT_ALLOWED;
+        for m in members {
+            let d = f(self, m);
+            result = self.arena.choice(result, d);
+        }
+        result
+    }
+
+    fn apply(&mut self, then: Then, x: PatId) -> PatId {
+        match then {
+            Then::InterleaveLeft(p) => self.arena.interleave(x, p),
+            Then::InterleaveRight(p) => self.arena.interleave(p, x),
+            Then::Group(p) => self.arena.group(x, p),
+            Then::After(p) => self.arena.after(x, p),
+        }
+    }
+
+    fn apply_after(&mut self, then: Then, p: PatId) -> PatId {
+        match self.arena.get(p) {
+            Pat::After(a, b) => {
+                let b = self.apply(then, b);
+                self.arena.after(a, b)
+            }
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.apply_after(then, m)),
+            _ => NOT_ALLOWED,
+        }
+    }
+
+    /// After the start tag `<ns:local`
+    pub fn start_tag_open(&mut self, p: PatId, ns: &str, local: &str) -> PatId {
+        let key = (p, ns.to_string(), local.to_string());
+        if let Some(d) = self.start_tag_memo.get(&key) {
+            return *d;
+        }
+        let d = match self.arena.get(p) {
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.start_tag_open(m, ns, local)),
+            Pat::Element(name_class, element) => {
+                if self.schema.name_classes[name_class as usize].contains(ns, local) {
+                    let content = self.schema.elements[element as usize].0;
+                    self.arena.after(content, EMPTY)
+                } else {
+                    NOT_ALLOWED
+                }
+            }
+            Pat::Interleave(a, b) => {
+                let da = self.start_tag_open(a, ns, local);
+                let da = self.apply_after(Then::InterleaveLeft(b), da);
+                let db = self.start_tag_open(b, ns, local);
+                let db = self.apply_after(Then::InterleaveRight(a), db);
+                self.arena.choice(da, db)
+            }
+            Pat::OneOrMore(a) => {
+                let rest = self.arena.choice(p, EMPTY);
+                let da = self.start_tag_open(a, ns, local);
+                self.apply_after(Then::Group(rest), da)
+            }
+            Pat::Group(a, b) => {
+                let da = self.start_tag_open(a, ns, local);
+                let x = self.apply_after(Then::Group(b), da);
+                if self.arena.nullable(a) {
+                    let db = self.start_tag_open(b, ns, local);
+                    self.arena.choice(x, db)
+                } else {
+                    x
+                }
+            }
+            Pat::After(a, b) => {
+                let da = self.start_tag_open(a, ns, local);
+                self.apply_after(Then::After(b), da)
+            }
+            _ => NOT_ALLOWED,
+        };
+        self.start_tag_memo.insert(key, d);
+        d
+    }
+
+    /// After the attribute `ns:local="value"`
+    pub fn attribute(&mut self, p: PatId, ns: &str, local: &str, value: &str) -> PatId {
+        if !self.arena.has_attributes(p) {
+            return NOT_ALLOWED;
+        }
+        match self.arena.get(p) {
+            Pat::After(a, b) => {
+                let da = self.attribute(a, ns, local, value);
+                self.arena.after(da, b)
+            }
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.attribute(m, ns, local, value)),
+            Pat::Group(a, b) | Pat::Interleave(a, b) => {
+                let is_group = matches!(self.arena.get(p), Pat::Group(..));
+                let da = self.attribute(a, ns, local, value);
+                let db = self.attribute(b, ns, local, value);
+                let (x, y) = if is_group {
+                    (self.arena.group(da, b), self.arena.group(a, db))
+                } else {
+                    (self.arena.interleave(da, b), self.arena.interleave(a, db))
+                };
+                self.arena.choice(x, y)
+            }
+            Pat::OneOrMore(a) => {
+                let da = self.attribute(a, ns, local, value);
+                let rest = self.arena.choice(p, EMPTY);
+                self.arena.group(da, rest)
+            }
+            Pat::Attribute(name_class, a) => {
+                if self.schema.name_classes[name_class as usize].contains(ns, local)
+                    && self.value_matches(a, value)
+                {
+                    EMPTY
+                } else {
+                    NOT_ALLOWED
+                }
+            }
+            _ => NOT_ALLOWED,
+        }
+    }
+
+    /// After the `>` of a start tag: attributes still required are missing.
+    /// With `lenient`, missing attributes are ignored instead.
+    pub fn start_tag_close(&mut self, p: PatId, lenient: bool) -> PatId {
+        if !self.arena.has_attributes(p) {
+            return p;
+        }
+        if let Some(d) = self.close_memo.get(&(p, lenient)) {
+            return *d;
+        }
+        let d = match self.arena.get(p) {
+            Pat::After(a, b) => {
+                let da = self.start_tag_close(a, lenient);
+                self.arena.after(da, b)
+            }
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.start_tag_close(m, lenient)),
+            Pat::Group(a, b) => {
+                let da = self.start_tag_close(a, lenient);
+                let db = self.start_tag_close(b, lenient);
+                self.arena.group(da, db)
+            }
+            Pat::Interleave(a, b) => {
+                let da = self.start_tag_close(a, lenient);
+                let db = self.start_tag_close(b, lenient);
+                self.arena.interleave(da, db)
+            }
+            Pat::OneOrMore(a) => {
+                let da = self.start_tag_close(a, lenient);
+                self.arena.one_or_more(da)
+            }
+            Pat::Attribute(..) if lenient => EMPTY,
+            Pat::Attribute(..) => NOT_ALLOWED,
+            _ => p,
+        };
+        self.close_memo.insert((p, lenient), d);
+        d
+    }
+
+    /// After a text node
+    pub fn text(&mut self, p: PatId, s: &str) -> PatId {
+        if self.arena.has_data(p) {
+            return self.text_uncached(p, s);
+        }
+        if let Some(d) = self.text_memo.get(&p) {
+            return *d;
+        }
+        let d = self.text_uncached(p, s);
+        self.text_memo.insert(p, d);
+        d
+    }
+
+    fn text_uncached(&mut self, p: PatId, s: &str) -> PatId {
+        match self.arena.get(p) {
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.text(m, s)),
+            Pat::Interleave(a, b) => {
+                let da = self.text(a, s);
+                let x = self.arena.interleave(da, b);
+                let db = self.text(b, s);
+                let y = self.arena.interleave(a, db);
+                self.arena.choice(x, y)
+            }
+            Pat::Group(a, b) => {
+                let da = self.text(a, s);
+                let x = self.arena.group(da, b);
+                if self.arena.nullable(a) {
+                    let db = self.text(b, s);
+                    self.arena.choice(x, db)
+                } else {
+                    x
+                }
+            }
+            Pat::After(a, b) => {
+                let da = self.text(a, s);
+                self.arena.after(da, b)
+            }
+            Pat::OneOrMore(a) => {
+                let da = self.text(a, s);
+                let rest = self.arena.choice(p, EMPTY);
+                self.arena.group(da, rest)
+            }
+            Pat::Text => TEXT,
+            Pat::Value(value) => {
+                let spec = &self.schema.values[value as usize];
+                if spec.datatype.allows(s) && spec.datatype.equal(s, &spec.value) {
+                    EMPTY
+                } else {
+                    NOT_ALLOWED
+                }
+            }
+            Pat::Data(datatype, except) => {
+                if self.schema.datatypes[datatype as usize].allows(s) {
+                    let de = self.text(except, s);
+                    if self.arena.nullable(de) {
+                        NOT_ALLOWED
+                    } else {
+                        EMPTY
+                    }
+                } else {
+                    NOT_ALLOWED
+                }
+            }
+            Pat::List(a) => {
+                let mut d = a;
+                for token in s.split_whitespace() {
+                    d = self.text(d, token);
+                }
+                if self.arena.nullable(d) {
+                    EMPTY
+                } else {
+                    NOT_ALLOWED
+                }
+            }
+            _ => NOT_ALLOWED,
+        }
+    }
+
+    fn value_matches(&mut self, p: PatId, s: &str) -> bool {
+        (self.arena.nullable(p) && is_whitespace(s)) || {
+            let d = self.text(p, s);
+            self.arena.nullable(d)
+        }
+    }
+
+    /// After an end tag; with `force`, unfinished content is dropped
+    pub fn end_tag(&mut self, p: PatId, force: bool) -> PatId {
+        match self.arena.get(p) {
+            Pat::Choice(..) => self.each_alternative(p, |d, m| d.end_tag(m, force)),
+            Pat::After(a, b) if force || self.arena.nullable(a) => b,
+            _ => NOT_ALLOWED,
+        }
+    }
+
+    /// Content pattern of the first element definition for a name, to
+    /// check the content of an element that is misplaced
+    pub fn content_of(&mut self, ns: &str, local: &str) -> Option<PatId> {
+        let schema = self.schema;
+        let (content, _) = schema
+            .elements
+            .iter()
+            .filter(|(_, nc)| schema.name_classes[*nc as usize].contains(ns, local))
+            .min_by_key(|(_, nc)| !schema.name_classes[*nc as usize].is_name())?;
+        Some(self.arena.after(*content, EMPTY))
+    }
+
+    /// Element names allowed next, for messages
+    pub fn expected_elements(&self, p: PatId) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut seen = HashSet::new();
+        self.collect_expected(p, &mut names, &mut seen);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn collect_expected(&self, p: PatId, out: &mut Vec<String>, seen: &mut HashSet<PatId>) {
+        if !seen.insert(p) {
+            return;
+        }
+        match self.arena.get(p) {
+            Pat::Choice(a, b) | Pat::Interleave(a, b) => {
+                self.collect_expected(a, out, seen);
+                self.collect_expected(b, out, seen);
+            }
+            Pat::Group(a, b) => {
+                self.collect_expected(a, out, seen);
+                if self.arena.nullable(a) {
+                    self.collect_expected(b, out, seen);
+                }
+            }
+            Pat::OneOrMore(a) | Pat::After(a, _) => self.collect_expected(a, out, seen),
+            Pat::Element(name_class, _) => {
+                self.schema.name_classes[name_class as usize].describe(out)
+            }
+            _ => {}
+        }
+    }
+
+    /// Attributes that are still required, for messages
+    pub fn required_attributes(&self, p: PatId) -> Vec<String> {
+        let mut names = match self.arena.get(p) {
+            Pat::Group(a, b) | Pat::Interleave(a, b) => {
+                let mut names = self.required_attributes(a);
+                names.extend(self.required_attributes(b));
+                names
+            }
+            Pat::Choice(a, b) => {
+                let (x, y) = (self.required_attributes(a), self.required_attributes(b));
+                if x.is_empty() || y.is_empty() {
+                    Vec::new()
+                } else {
+                    x.into_iter().chain(y).collect()
+                }
+            }
+            Pat::OneOrMore(a) | Pat::After(a, _) => self.required_attributes(a),
+            Pat::Attribute(name_class, _) => {
+                let mut names = Vec::new();
+                self.schema.name_classes[name_class as usize].describe(&mut names);
+                names
+            }
+            _ => Vec::new(),
+        };
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Whether `p` declares the attribute (so a failure is about its value)
+    pub fn declares_attribute(&self, p: PatId, ns: &str, local: &str) -> bool {
+        if !self.arena.has_attributes(p) {
+            return false;
+        }
+        match self.arena.get(p) {
+            Pat::Choice(a, b) | Pat::Group(a, b) | Pat::Interleave(a, b) | Pat::After(a, b) => {
+                self.declares_attribute(a, ns, local) || self.declares_attribute(b, ns, local)
+            }
+            Pat::OneOrMore(a) => self.declares_attribute(a, ns, local),
+            Pat::Attribute(name_class, _) => {
+                self.schema.name_classes[name_class as usize].contains(ns, local)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `p` expects a typed value (rather than no text at all)
+    pub fn expects_data(&self, p: PatId) -> bool {
+        match self.arena.get(p) {
+            Pat::Choice(a, b) | Pat::Interleave(a, b) => {
+                self.expects_data(a) || self.expects_data(b)
+            }
+            Pat::Group(a, b) => {
+                self.expects_data(a) || (self.arena.nullable(a) && self.expects_data(b))
+            }
+            Pat::OneOrMore(a) | Pat::After(a, _) => self.expects_data(a),
+            Pat::Data(..) | Pat::Value(_) | Pat::List(_) => true,
+            _ => false,
+        }
+    }
+}
+
+pub(super) fn is_whitespace(s: &str) -> bool {
+    s.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+}
@@ -0,0 +1,287 @@
+//! Reading RELAX NG (XML syntax) schema files into an owned tree.
+//!
+//! Loading already performs the first simplification steps of the
+//! specification: foreign elements and attributes are dropped, `ns` and
+//! `datatypeLibrary` are inherited, QNames in names are resolved, and
+//! `externalRef` and `include` are replaced by the referenced files.
+
+use crate::validation::local_path;
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(super) const RNG_NS: &str = "http://relaxng.org/ns/structure/1.0";
+pub(crate) const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// A RELAX NG element with its inherited context. The XSD and DTD backends
+/// build these directly to describe their grammars.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Elem {
+    /// Local name in the RELAX NG namespace
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Elem>,
+
+    /// Text content (of `name`, `value` and `param`)
+    pub text: String,
+
+    /// Inherited `ns` and `datatypeLibrary`
+    pub ns: String,
+    pub datatype_library: String,
+
+    /// Namespace and local name of a `name` element, or of the `name`
+    /// attribute of `element` and `attribute`. A name without namespace
+    /// matches the local name in any namespace (for DTDs, which predate
+    /// namespaces).
+    pub qname: Option<(Option<String>, String)>,
+
+    /// `file:line`, for error messages
+    pub location: String,
+}
+
+impl Elem {
+    /// An element of a generated grammar
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn with_attr(mut self, name: &str, value: &str) -> Self {
+        self.attrs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub(crate) fn with_child(mut self, child: Elem) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub(crate) fn with_children(mut self, children: impl IntoIterator<Item = Elem>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    pub(crate) fn with_text(mut self, text: &str) -> Self {
+        self.text = text.to_string();
+        self
+    }
+
+    /// Name of an `element` or `attribute`; `None` matches any namespace
+    pub(crate) fn with_name(mut self, ns: Option<&str>, local: &str) -> Self {
+        self.qname = Some((ns.map(str::to_string), local.to_string()));
+        self
+    }
+
+    /// Namespace of an `nsName`
+    pub(crate) fn with_ns(mut self, ns: &str) -> Self {
+        self.ns = ns.to_string();
+        self
+    }
+
+    pub(crate) fn with_datatype_library(mut self, library: &str) -> Self {
+        self.datatype_library = library.to_string();
+        self
+    }
+
+    pub(crate) fn at(mut self, location: &str) -> Self {
+        self.location = location.to_string();
+        self
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+    }
+
+    /// Value of a required attribute
+    pub fn required(&self, name: &str) -> Result<&str> {
+        self.attr(name)
+            .with_context(|| format!("<{}> without @{} at {}", self.name, name, self.location))
+    }
+}
+
+/// Load a schema file with everything it references
+pub(super) fn load(path: &Path) -> Result<Elem> {
+    Loader { stack: Vec::new() }.load_file(path, "")
+}
+
+struct Loader {
+    /// Files being loaded, to reject recursive includes
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    fn load_file(&mut self, path: &Path, ns: &str) -> Result<Elem> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read RELAX NG schema: {}", path.display()))?;
+        if self.stack.contains(&canonical) {
+            anyhow::bail!("Recursive include of {}", path.display());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read RELAX NG schema: {}", path.display()))?;
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(&text, options)
+            .with_context(|| format!("Failed to parse RELAX NG schema: {}", path.display()))?;
+
+        let root = doc.root_element();
+        if root.tag_name().namespace() != Some(RNG_NS) {
+            anyhow::bail!(
+                "{} is not a RELAX NG schema (root <{}> is not in the {} namespace)",
+                path.display(),
+                root.tag_name().name(),
+                RNG_NS
+            );
+        }
+
+        self.stack.push(canonical);
+        let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let context = Inherited {
+            file: path.display().to_string(),
+            base,
+            ns: ns.to_string(),
+            datatype_library: String::new(),
+        };
+        let elem = self.convert(root, &context);
+        self.stack.pop();
+        Ok(elem?.expect("root is in the RELAX NG namespace"))
+    }
+
+    fn convert(&mut self, node: Node, parent: &Inherited) -> Result<Option<Elem>> {
+        if node.tag_name().namespace() != Some(RNG_NS) {
+            return Ok(None);
+        }
+        let name = node.tag_name().name().to_string();
+        let attrs: Vec<(String, String)> = node
+            .attributes()
+            .filter(|a| a.namespace().is_none())
+            .map(|a| (a.name().to_string(), a.value().to_string()))
+            .collect();
+        let own = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        let own_ns = own("ns");
+
+        let context = Inherited {
+            ns: own_ns.clone().unwrap_or_else(|| parent.ns.clone()),
+            datatype_library: own("datatypeLibrary")
+                .unwrap_or_else(|| parent.datatype_library.clone()),
+            ..parent.clone()
+        };
+        let pos = node.document().text_pos_at(node.range().start);
+        let location = format!("{}:{}", context.file, pos.row);
+
+        let mut elem = Elem {
+            name,
+            attrs,
+            ns: context.ns.clone(),
+            datatype_library: context.datatype_library.clone(),
+            location,
+            ..Elem::default()
+        };
+        for child in node.children() {
+            if child.is_text() {
+                elem.text.push_str(child.text().unwrap_or_default());
+            } else if let Some(child) = self.convert(child, &context)? {
+                elem.children.push(child);
+            }
+        }
+
+        match elem.name.as_str() {
+            "name" => elem.qname = Some(resolve_qname(node, elem.text.trim(), &elem.ns)?),
+            "element" | "attribute" => {
+                if let Some(qname) = elem.attr("name") {
+                    // Unprefixed attribute names are in no namespace unless
+                    // the attribute itself sets `ns`
+                    let ns = if elem.name == "attribute" {
+                        own_ns.clone().unwrap_or_default()
+                    } else {
+                        elem.ns.clone()
+                    };
+                    elem.qname = Some(resolve_qname(node, qname, &ns)?);
+                }
+            }
+            "externalRef" => {
+                let path = self.resolve_href(&elem, &context)?;
+                return self.load_file(&path, &elem.ns).map(Some);
+            }
+            "include" => {
+                let path = self.resolve_href(&elem, &context)?;
+                let mut grammar = self.load_file(&path, &elem.ns)?;
+                if grammar.name != "grammar" {
+                    anyhow::bail!("Included schema {} is not a <grammar>", path.display());
+                }
+                // Components of the include replace those of the included grammar
+                let mut overrides = Vec::new();
+                collect_components(&elem.children, &mut overrides);
+                remove_components(&mut grammar.children, &overrides);
+                grammar.children.append(&mut elem.children);
+                grammar.name = "div".to_string();
+                grammar.location = elem.location;
+                return Ok(Some(grammar));
+            }
+            _ => {}
+        }
+        Ok(Some(elem))
+    }
+
+    fn resolve_href(&self, elem: &Elem, context: &Inherited) -> Result<PathBuf> {
+        let href = elem.required("href")?;
+        local_path(&context.base, href).map_err(|e| anyhow::anyhow!("{} at {}", e, elem.location))
+    }
+}
+
+/// What an element inherits from its ancestors and its file
+#[derive(Clone)]
+struct Inherited {
+    file: String,
+    base: PathBuf,
+    ns: String,
+    datatype_library: String,
+}
+
+fn resolve_qname(node: Node, qname: &str, default_ns: &str) -> Result<(Option<String>, String)> {
+    let (ns, local) = match qname.split_once(':') {
+        // The `xml` prefix is bound without a declaration
+        Some(("xml", local)) => (XML_NS, local),
+        Some((prefix, local)) => {
+            let ns = node
+                .lookup_namespace_uri(Some(prefix))
+                .with_context(|| format!("Undeclared namespace prefix in {}", qname))?;
+            (ns, local)
+        }
+        None => (default_ns, qname),
+    };
+    Ok((Some(ns.to_string()), local.to_string()))
+}
+
+/// A `start` (as `None`) or a define name
+type Component = Option<String>;
+
+fn collect_components(children: &[Elem], out: &mut Vec<Component>) {
+    for child in children {
+        match child.name.as_str() {
+            "start" => out.push(None),
+            "define" => out.push(child.attr("name").map(str::to_string)),
+            "div" => collect_components(&child.children, out),
+            _ => {}
+        }
+    }
+}
+
+fn remove_components(children: &mut Vec<Elem>, overrides: &[Component]) {
+    children.retain(|child| match child.name.as_str() {
+        "start" => !overrides.contains(&None),
+        "define" => !overrides.contains(&child.attr("name").map(str::to_string)),
+        _ => true,
+    });
+    for child in children.iter_mut().filter(|c| c.name == "div") {
+        remove_components(&mut child.children, overrides);
+    }
+}
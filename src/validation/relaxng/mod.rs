@@ -0,0 +1,550 @@
+//! RELAX NG (XML syntax) validation against local schema files, such as a
+//! downloaded `tei_all.rng` or a project customization.
+//!
+//! A schema is loaded and compiled once (`include` and `externalRef` are
+//! followed on disk, remote URLs are rejected) and registered as one rule;
+//! the compiled patterns are immutable, so parallel runs share them.
+//!
+//! Every violation is reported at the offending element, attribute or text
+//! and validation continues: misplaced elements are checked against their
+//! own definition, unexpected attributes and text are skipped, and
+//! unfinished elements are closed.
+
+mod compile;
+mod datatype;
+mod derive;
+mod load;
+mod pattern;
+
+use super::report::{Message, Severity};
+use super::rule::{node_position, Rule, RuleContext};
+use anyhow::{Context, Result};
+use compile::Schema;
+use derive::{is_whitespace, Deriver};
+use pattern::{PatId, NOT_ALLOWED};
+use roxmltree::{Document, Node};
+use std::path::Path;
+
+pub(crate) use datatype::XSD_LIBRARY;
+pub(crate) use load::{Elem, XML_NS};
+
+/// Most names listed in "expected …" messages
+const MAX_EXPECTED: usize = 8;
+
+/// A compiled RELAX NG schema, registered as one rule
+#[derive(Debug)]
+pub struct RelaxNg {
+    id: String,
+    description: String,
+    schema: Schema,
+}
+
+impl RelaxNg {
+    /// Load and compile a schema file and the files it includes
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let root = load::load(path)?;
+        let schema = compile::compile(&root)
+            .with_context(|| format!("Invalid RELAX NG schema: {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "schema".to_string());
+        Ok(Self {
+            id: format!("relaxng:{}", name),
+            description: format!(
+                "Content model of the RELAX NG schema {} ({} element definitions)",
+                path.display(),
+                schema.elements.len()
+            ),
+            schema,
+        })
+    }
+
+    /// Compile a grammar translated from another schema language; messages
+    /// are reported under `id`
+    pub(crate) fn from_grammar(grammar: &Elem, id: String, description: String) -> Result<Self> {
+        Ok(Self {
+            id,
+            description,
+            schema: compile::compile(grammar)?,
+        })
+    }
+
+    /// Validate a parsed document; messages are in document order
+    pub fn validate_document(&self, doc: &Document) -> Vec<Message> {
+        let mut walker = Walker {
+            deriver: Deriver::new(&self.schema),
+            id: &self.id,
+            messages: Vec::new(),
+        };
+        walker.element(doc.root_element(), self.schema.start);
+        walker.messages
+    }
+}
+
+impl Rule for RelaxNg {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Change the markup to follow the content model of the schema")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        // The whole document is validated from the root element
+        if node.parent_element().is_none() {
+            for msg in self.validate_document(node.document()) {
+                ctx.report_at(msg.line, msg.column, msg.text);
+            }
+        }
+    }
+}
+
+/// Text between element children, merged across comments and PIs
+enum Child<'a, 'input> {
+    Text(String, Node<'a, 'input>),
+    Element(Node<'a, 'input>),
+}
+
+struct Walker<'s> {
+    deriver: Deriver<'s>,
+    id: &'s str,
+    messages: Vec<Message>,
+}
+
+impl Walker<'_> {
+    fn report(&mut self, (line, column): (usize, usize), text: String) {
+        self.messages
+            .push(Message::new(self.id, Severity::Error, line, column, text));
+    }
+
+    /// Validate an element in the context `p`; returns what is allowed after it
+    fn element(&mut self, node: Node, p: PatId) -> PatId {
+        let (ns, local) = (
+            node.tag_name().namespace().unwrap_or(""),
+            node.tag_name().name(),
+        );
+        let q = self.deriver.start_tag_open(p, ns, local);
+        if q != NOT_ALLOWED {
+            return self.content(node, q);
+        }
+
+        let parent = node
+            .parent_element()
+            .map(|e| format!(" in <{}>", e.tag_name().name()))
+            .unwrap_or_default();
+        let expected = self.deriver.expected_elements(p);
+        let text = if expected.is_empty() {
+            format!("Element <{}> not allowed{}", local, parent)
+        } else {
+            format!(
+                "Element <{}> not allowed{}; expected {}",
+                local,
+                parent,
+                list(&expected, "<", ">")
+            )
+        };
+        self.report(node_position(node), text);
+
+        // Check the misplaced element against its own definition
+        if let Some(content) = self.deriver.content_of(ns, local) {
+            self.content(node, content);
+        }
+        p
+    }
+
+    /// Attributes, children and end tag, after the start tag was accepted
+    fn content(&mut self, node: Node, mut p: PatId) -> PatId {
+        let name = node.tag_name().name();
+        let mut invalid = Vec::new();
+        for attr in node.attributes() {
+            let ns = attr.namespace().unwrap_or("");
+            let q = self.deriver.attribute(p, ns, attr.name(), attr.value());
+            if q != NOT_ALLOWED {
+                p = q;
+                continue;
+            }
+            let pos = node.document().text_pos_at(attr.range().start);
+            let text = if self.deriver.declares_attribute(p, ns, attr.name()) {
+                invalid.push(attr.name().to_string());
+                format!(
+                    "Invalid value {:?} for @{} on <{}>",
+                    attr.value(),
+                    attr.name(),
+                    name
+                )
+            } else {
+                format!("Attribute @{} not allowed on <{}>", attr.name(), name)
+            };
+            self.report((pos.row as usize, pos.col as usize), text);
+        }
+
+        let q = self.deriver.start_tag_close(p, false);
+        p = if q == NOT_ALLOWED {
+            // Attributes with an invalid value were already reported
+            let mut missing = self.deriver.required_attributes(p);
+            missing.retain(|m| !invalid.contains(m));
+            if !missing.is_empty() {
+                self.report(
+                    node_position(node),
+                    format!(
+                        "Element <{}> is missing required attribute {}",
+                        name,
+                        list(&missing, "@", "")
+                    ),
+                );
+            }
+            self.deriver.start_tag_close(p, true)
+        } else {
+            q
+        };
+
+        let mut children = Vec::new();
+        for child in node.children() {
+            if child.is_element() {
+                children.push(Child::Element(child));
+            } else if let Some(text) = child.text().filter(|_| child.is_text()) {
+                match children.last_mut() {
+                    Some(Child::Text(s, _)) => s.push_str(text),
+                    _ => children.push(Child::Text(text.to_string(), child)),
+                }
+            }
+        }
+
+        match children.as_slice() {
+            [] => {
+                let d = self.deriver.text(p, "");
+                p = self.deriver.choice(p, d);
+            }
+            [Child::Text(s, text_node)] => {
+                let d = self.deriver.text(p, s);
+                if is_whitespace(s) {
+                    p = self.deriver.choice(p, d);
+                } else if d == NOT_ALLOWED {
+                    // The invalid value is the whole content; don't report
+                    // the element as incomplete as well
+                    self.report_text(*text_node, name, s, p);
+                    return self.deriver.end_tag(p, true);
+                } else {
+                    p = d;
+                }
+            }
+            _ => {
+                for child in &children {
+                    match child {
+                        Child::Element(e) => p = self.element(*e, p),
+                        Child::Text(s, _) if is_whitespace(s) => {}
+                        Child::Text(s, text_node) => {
+                            let d = self.deriver.text(p, s);
+                            if d == NOT_ALLOWED {
+                                self.report_text(*text_node, name, s, p);
+                            } else {
+                                p = d;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let q = self.deriver.end_tag(p, false);
+        if q != NOT_ALLOWED {
+            return q;
+        }
+        let expected = self.deriver.expected_elements(p);
+        let text = if expected.is_empty() {
+            format!("Element <{}> is incomplete", name)
+        } else {
+            format!(
+                "Element <{}> is incomplete; expected {}",
+                name,
+                list(&expected, "<", ">")
+            )
+        };
+        self.report(node_position(node), text);
+        self.deriver.end_tag(p, true)
+    }
+
+    fn report_text(&mut self, text_node: Node, parent: &str, s: &str, p: PatId) {
+        let text = if self.deriver.expects_data(p) {
+            format!("Invalid value {:?} in <{}>", abbreviate(s.trim()), parent)
+        } else {
+            format!("Text not allowed in <{}>", parent)
+        };
+        // Report at the first non-blank character
+        let offset =
+            text_node.range().start + (s.len() - s.trim_start().len()).min(text_node.range().len());
+        let pos = text_node.document().text_pos_at(offset);
+        self.report((pos.row as usize, pos.col as usize), text);
+    }
+}
+
+/// `<a>, <b> or <c>`, shortened to the first few names
+fn list(names: &[String], prefix: &str, suffix: &str) -> String {
+    let mut items: Vec<String> = names
+        .iter()
+        .take(MAX_EXPECTED)
+        .map(|n| format!("{}{}{}", prefix, n, suffix))
+        .collect();
+    if names.len() > MAX_EXPECTED {
+        items.push(format!("{} more", names.len() - MAX_EXPECTED));
+    }
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        Some((last, _)) => last.clone(),
+        None => String::new(),
+    }
+}
+
+fn abbreviate(s: &str) -> String {
+    const MAX: usize = 40;
+    if s.chars().count() > MAX {
+        format!("{}…", s.chars().take(MAX).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::rng::skeleton_to_rng;
+    use crate::struct_processor::parse_xml_structure;
+    use crate::validation::Registry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SCHEMA: &str = r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0"
+    ns="http://www.tei-c.org/ns/1.0"
+    datatypeLibrary="http://www.w3.org/2001/XMLSchema-datatypes">
+  <start><ref name="TEI"/></start>
+  <define name="TEI"><element name="TEI"><ref name="text"/></element></define>
+  <define name="text">
+    <element name="text"><oneOrMore><ref name="div"/></oneOrMore></element>
+  </define>
+  <define name="div">
+    <element name="div">
+      <optional><attribute name="n"><data type="positiveInteger"/></attribute></optional>
+      <optional>
+        <attribute name="type"><choice><value>chapter</value><value>section</value></choice></attribute>
+      </optional>
+      <optional><element name="head"><text/></element></optional>
+      <zeroOrMore><choice><ref name="p"/><ref name="pb"/><ref name="div"/></choice></zeroOrMore>
+    </element>
+  </define>
+  <define name="p">
+    <element name="p"><mixed><zeroOrMore><ref name="pb"/></zeroOrMore></mixed></element>
+  </define>
+  <define name="pb"><element name="pb"><attribute name="n"/><empty/></element></define>
+</grammar>"#;
+
+    fn load(dir: &TempDir, name: &str, text: &str) -> RelaxNg {
+        let path = dir.path().join(name);
+        fs::write(&path, text).unwrap();
+        RelaxNg::from_file(&path).unwrap()
+    }
+
+    fn messages(schema: &RelaxNg, xml: &str) -> Vec<(usize, usize, String)> {
+        let doc = Document::parse(xml).unwrap();
+        schema
+            .validate_document(&doc)
+            .into_iter()
+            .map(|m| (m.line, m.column, m.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(&dir, "tei.rng", SCHEMA);
+        assert_eq!(schema.id(), "relaxng:tei");
+
+        let xml = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0"><text>
+  <div n="1" type="chapter"><head>One</head><p>Text <pb n="2"/> more</p>
+    <!-- nested --><div><p/></div>
+  </div>
+</text></TEI>"#;
+        assert_eq!(messages(&schema, xml), vec![]);
+    }
+
+    #[test]
+    fn test_violations_and_recovery() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(&dir, "tei.rng", SCHEMA);
+
+        let xml = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0">
+<text>
+<div n="0" type="part" rend="x">
+<p>a</p>
+<head>Late</head>
+<pb/>
+</div>
+<div>text</div>
+</text>
+</TEI>"#;
+        let expected: Vec<(usize, usize, String)> = [
+            (3, 6, r#"Invalid value "0" for @n on <div>"#),
+            (3, 12, r#"Invalid value "part" for @type on <div>"#),
+            (3, 24, "Attribute @rend not allowed on <div>"),
+            (
+                5,
+                1,
+                "Element <head> not allowed in <div>; expected <div>, <p> or <pb>",
+            ),
+            (6, 1, "Element <pb> is missing required attribute @n"),
+            (8, 6, "Text not allowed in <div>"),
+        ]
+        .into_iter()
+        .map(|(l, c, t)| (l, c, t.to_string()))
+        .collect();
+        assert_eq!(messages(&schema, xml), expected);
+
+        assert_eq!(
+            messages(
+                &schema,
+                r#"<TEI xmlns="http://www.tei-c.org/ns/1.0"><text/></TEI>"#
+            ),
+            vec![(
+                1,
+                42,
+                "Element <text> is incomplete; expected <div>".to_string()
+            )]
+        );
+        // Wrong namespace: nothing matches the start pattern
+        assert_eq!(
+            messages(&schema, "<TEI><text/></TEI>"),
+            vec![(
+                1,
+                1,
+                "Element <TEI> not allowed; expected <TEI>".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_include_override_and_external_ref() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("tei.rng"), SCHEMA).unwrap();
+        fs::write(
+            dir.path().join("note.rng"),
+            r#"<element name="note" xmlns="http://relaxng.org/ns/structure/1.0"><text/></element>"#,
+        )
+        .unwrap();
+        // Project customization: <p> may only contain notes, <pb> needs no @n
+        let custom = load(
+            &dir,
+            "project.rng",
+            r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0"
+    xmlns:tei="http://www.tei-c.org/ns/1.0">
+  <include href="tei.rng">
+    <define name="p">
+      <element name="tei:p"><zeroOrMore><externalRef href="note.rng" ns="http://www.tei-c.org/ns/1.0"/></zeroOrMore></element>
+    </define>
+  </include>
+  <define name="pb" combine="choice">
+    <element name="pb" ns="http://www.tei-c.org/ns/1.0"><empty/></element>
+  </define>
+</grammar>"#,
+        );
+
+        let ok = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0"><text><div>
+<p><note>n</note></p><pb/></div></text></TEI>"#;
+        assert_eq!(messages(&custom, ok), vec![]);
+
+        let bad = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0"><text><div>
+<p>text</p></div></text></TEI>"#;
+        assert_eq!(
+            messages(&custom, bad),
+            vec![(2, 4, "Text not allowed in <p>".to_string())]
+        );
+
+        let err = RelaxNg::from_file(&dir.path().join("missing.rng")).unwrap_err();
+        assert!(err.to_string().contains("Failed to read RELAX NG schema"));
+        fs::write(
+            dir.path().join("broken.rng"),
+            r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0"><start><ref name="x"/></start></grammar>"#,
+        )
+        .unwrap();
+        let err = RelaxNg::from_file(&dir.path().join("broken.rng")).unwrap_err();
+        assert!(format!("{:#}", err).contains("Reference to undefined pattern x"));
+    }
+
+    #[test]
+    fn test_registry_with_derived_schema() {
+        // A grammar derived from a skeleton accepts the files it came from
+        let xml = r#"<TEI xml:id="t"><text><div type="a"><p>x</p><p/></div></text></TEI>"#;
+        let skeleton = parse_xml_structure(xml).unwrap().to_skeleton();
+        let dir = TempDir::new().unwrap();
+        let schema = load(&dir, "group.rng", &skeleton_to_rng(&skeleton, None));
+
+        let mut registry = Registry::empty();
+        registry.register(schema).unwrap();
+        assert!(registry.validate(xml).is_valid());
+
+        let report = registry.validate("<TEI><text><p/></text></TEI>");
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].rule_id, "relaxng:group");
+        assert!(report.errors[0].help.is_some());
+    }
+
+    #[test]
+    fn test_derived_schema_with_dotted_names() {
+        // `a.b` and `a/b` share a dotted path; the grammar still loads and
+        // accepts its own file
+        let xml = "<TEI><x><a.b/><a><b/></a></x></TEI>";
+        let skeleton = parse_xml_structure(xml).unwrap().to_skeleton();
+        let dir = TempDir::new().unwrap();
+        let schema = load(&dir, "group.rng", &skeleton_to_rng(&skeleton, None));
+
+        assert_eq!(messages(&schema, xml), vec![]);
+        assert_eq!(
+            messages(&schema, "<TEI><x><a><a.b/></a></x></TEI>").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_derived_schema_with_namespaces() {
+        // Foreign attributes and elements keep their namespaces
+        let xml = r##"<TEI xmlns="http://www.tei-c.org/ns/1.0"
+    xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+    xmlns:xlink="http://www.w3.org/1999/xlink"
+    xsi:schemaLocation="http://www.tei-c.org/ns/1.0 tei_all.xsd">
+  <text xml:lang="en"><p xml:id="p1"><ref xlink:href="#p1" target="#p1">x</ref>
+    <formula><math xmlns="http://www.w3.org/1998/Math/MathML"><mi>x</mi></math></formula>
+    <note xmlns=""><p/></note>
+  </p></text>
+</TEI>"##;
+        let skeleton = parse_xml_structure(xml).unwrap().to_skeleton();
+        let dir = TempDir::new().unwrap();
+        let schema = load(&dir, "group.rng", &skeleton_to_rng(&skeleton, None));
+
+        assert_eq!(messages(&schema, xml), vec![]);
+        let unprefixed = xml.replace("xlink:href", "href");
+        assert_eq!(
+            messages(&schema, &unprefixed),
+            vec![(5, 43, "Attribute @href not allowed on <ref>".to_string())]
+        );
+
+        // Another file of the group, without the TEI namespace
+        let mut group = skeleton.clone();
+        let plain = r#"<TEI schemaLocation="x"><text lang="en"><p id="p1">
+<ref href="x" target="x"/><formula><math><mi/></math></formula><note><p/></note>
+</p></text></TEI>"#;
+        let other = parse_xml_structure(plain).unwrap().to_skeleton();
+        assert_eq!(other.hash, group.hash);
+        group.merge_namespaces(&other);
+        let schema = load(&dir, "merged.rng", &skeleton_to_rng(&group, None));
+        assert_eq!(messages(&schema, xml), vec![]);
+        assert_eq!(messages(&schema, plain), vec![]);
+    }
+}
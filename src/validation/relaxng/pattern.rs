@@ -0,0 +1,333 @@
+//! Simplified RELAX NG patterns, hash-consed into an arena.
+//!
+//! A compiled schema owns a frozen arena; validating a document extends it
+//! with the derivative patterns it needs in a private overlay, so one
+//! schema is shared by parallel validations without locking.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(super) struct PatId(u32);
+
+pub(super) const EMPTY: PatId = PatId(0);
+pub(super) const NOT_ALLOWED: PatId = PatId(1);
+pub(super) const TEXT: PatId = PatId(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Pat {
+    Empty,
+    NotAllowed,
+    Text,
+    Choice(PatId, PatId),
+    Interleave(PatId, PatId),
+    Group(PatId, PatId),
+    OneOrMore(PatId),
+    List(PatId),
+    /// Datatype index and `except` pattern (`NOT_ALLOWED` if none)
+    Data(u32, PatId),
+    /// Index into the schema's values
+    Value(u32),
+    /// Name class index and value pattern
+    Attribute(u32, PatId),
+    /// Name class index and element index (content is looked up lazily,
+    /// which is what allows recursive content models)
+    Element(u32, u32),
+    /// Pattern of the current element and of what follows its end tag
+    After(PatId, PatId),
+}
+
+/// Flags computed when a pattern is interned
+#[derive(Debug, Clone, Copy)]
+struct Flags {
+    nullable: bool,
+    /// Contains an attribute outside of element content
+    has_attributes: bool,
+    /// Contains a datatype outside of element content and attributes, so
+    /// its text derivative depends on the text
+    has_data: bool,
+}
+
+/// A frozen set of interned patterns
+#[derive(Debug)]
+pub(super) struct Patterns {
+    pats: Vec<Pat>,
+    flags: Vec<Flags>,
+    index: HashMap<Pat, PatId>,
+}
+
+impl Patterns {
+    pub fn len(&self) -> usize {
+        self.pats.len()
+    }
+}
+
+/// Interns patterns on top of an optional frozen base
+pub(super) struct Arena<'s> {
+    base: Option<&'s Patterns>,
+    local: Patterns,
+}
+
+impl<'s> Arena<'s> {
+    /// An arena for compiling a schema, with the constant patterns
+    pub fn new() -> Self {
+        let mut arena = Self {
+            base: None,
+            local: Patterns {
+                pats: Vec::new(),
+                flags: Vec::new(),
+                index: HashMap::new(),
+            },
+        };
+        assert_eq!(arena.intern(Pat::Empty), EMPTY);
+        assert_eq!(arena.intern(Pat::NotAllowed), NOT_ALLOWED);
+        assert_eq!(arena.intern(Pat::Text), TEXT);
+        arena
+    }
+
+    /// An overlay over a compiled schema's patterns
+    pub fn over(base: &'s Patterns) -> Self {
+        Self {
+            base: Some(base),
+            local: Patterns {
+                pats: Vec::new(),
+                flags: Vec::new(),
+                index: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn into_patterns(self) -> Patterns {
+        self.local
+    }
+
+    fn base_len(&self) -> usize {
+        self.base.map_or(0, |b| b.len())
+    }
+
+    pub fn get(&self, id: PatId) -> Pat {
+        let i = id.0 as usize;
+        match self.base {
+            Some(base) if i < base.len() => base.pats[i],
+            _ => self.local.pats[i - self.base_len()],
+        }
+    }
+
+    fn flags(&self, id: PatId) -> Flags {
+        let i = id.0 as usize;
+        match self.base {
+            Some(base) if i < base.len() => base.flags[i],
+            _ => self.local.flags[i - self.base_len()],
+        }
+    }
+
+    pub fn nullable(&self, id: PatId) -> bool {
+        self.flags(id).nullable
+    }
+
+    pub fn has_attributes(&self, id: PatId) -> bool {
+        self.flags(id).has_attributes
+    }
+
+    pub fn has_data(&self, id: PatId) -> bool {
+        self.flags(id).has_data
+    }
+
+    fn intern(&mut self, pat: Pat) -> PatId {
+        if let Some(id) = self.base.and_then(|b| b.index.get(&pat)) {
+            return *id;
+        }
+        if let Some(id) = self.local.index.get(&pat) {
+            return *id;
+        }
+
+        let flags = match pat {
+            Pat::Empty | Pat::Text => Flags {
+                nullable: true,
+                has_attributes: false,
+                has_data: false,
+            },
+            Pat::Choice(a, b) | Pat::Interleave(a, b) | Pat::Group(a, b) => {
+                let (fa, fb) = (self.flags(a), self.flags(b));
+                Flags {
+                    nullable: if matches!(pat, Pat::Choice(..)) {
+                        fa.nullable || fb.nullable
+                    } else {
+                        fa.nullable && fb.nullable
+                    },
+                    has_attributes: fa.has_attributes || fb.has_attributes,
+                    has_data: fa.has_data || fb.has_data,
+                }
+            }
+            Pat::OneOrMore(a) => self.flags(a),
+            Pat::After(a, b) => {
+                let (fa, fb) = (self.flags(a), self.flags(b));
+                Flags {
+                    nullable: false,
+                    has_attributes: fa.has_attributes || fb.has_attributes,
+                    has_data: fa.has_data || fb.has_data,
+                }
+            }
+            Pat::Attribute(..) => Flags {
+                nullable: false,
+                has_attributes: true,
+                has_data: false,
+            },
+            Pat::List(_) | Pat::Data(..) | Pat::Value(_) => Flags {
+                nullable: false,
+                has_attributes: false,
+                has_data: true,
+            },
+            Pat::NotAllowed | Pat::Element(..) => Flags {
+                nullable: false,
+                has_attributes: false,
+                has_data: false,
+            },
+        };
+
+        let id = PatId((self.base_len() + self.local.pats.len()) as u32);
+        self.local.pats.push(pat);
+        self.local.flags.push(flags);
+        self.local.index.insert(pat, id);
+        id
+    }
+
+    /// Alternatives of a choice, flattened
+    pub fn choice_members(&self, p: PatId, out: &mut Vec<PatId>) {
+        match self.get(p) {
+            Pat::Choice(a, b) => {
+                self.choice_members(a, out);
+                self.choice_members(b, out);
+            }
+            _ => out.push(p),
+        }
+    }
+
+    /// Choices are kept flat, sorted and without duplicates, so equal sets
+    /// of alternatives intern to the same pattern
+    pub fn choice(&mut self, a: PatId, b: PatId) -> PatId {
+        if a == NOT_ALLOWED || a == b {
+            return b;
+        }
+        if b == NOT_ALLOWED {
+            return a;
+        }
+        let mut members = Vec::new();
+        self.choice_members(a, &mut members);
+        self.choice_members(b, &mut members);
+        members.sort_unstable();
+        members.dedup();
+        let mut members = members.into_iter().rev();
+        let mut p = members.next().expect("choice has members");
+        for m in members {
+            p = self.intern(Pat::Choice(m, p));
+        }
+        p
+    }
+
+    pub fn group(&mut self, a: PatId, b: PatId) -> PatId {
+        match (a, b) {
+            (NOT_ALLOWED, _) | (_, NOT_ALLOWED) => NOT_ALLOWED,
+            (EMPTY, p) | (p, EMPTY) => p,
+            _ => self.intern(Pat::Group(a, b)),
+        }
+    }
+
+    pub fn interleave(&mut self, a: PatId, b: PatId) -> PatId {
+        match (a, b) {
+            (NOT_ALLOWED, _) | (_, NOT_ALLOWED) => NOT_ALLOWED,
+            (EMPTY, p) | (p, EMPTY) => p,
+            _ => self.intern(Pat::Interleave(a, b)),
+        }
+    }
+
+    pub fn after(&mut self, a: PatId, b: PatId) -> PatId {
+        if a == NOT_ALLOWED || b == NOT_ALLOWED {
+            NOT_ALLOWED
+        } else {
+            self.intern(Pat::After(a, b))
+        }
+    }
+
+    pub fn one_or_more(&mut self, p: PatId) -> PatId {
+        match p {
+            NOT_ALLOWED | EMPTY => p,
+            _ => self.intern(Pat::OneOrMore(p)),
+        }
+    }
+
+    pub fn list(&mut self, p: PatId) -> PatId {
+        if p == NOT_ALLOWED {
+            p
+        } else {
+            self.intern(Pat::List(p))
+        }
+    }
+
+    pub fn data(&mut self, datatype: u32, except: PatId) -> PatId {
+        self.intern(Pat::Data(datatype, except))
+    }
+
+    pub fn value(&mut self, value: u32) -> PatId {
+        self.intern(Pat::Value(value))
+    }
+
+    pub fn attribute(&mut self, name_class: u32, p: PatId) -> PatId {
+        if p == NOT_ALLOWED {
+            p
+        } else {
+            self.intern(Pat::Attribute(name_class, p))
+        }
+    }
+
+    pub fn element(&mut self, name_class: u32, element: u32) -> PatId {
+        self.intern(Pat::Element(name_class, element))
+    }
+}
+
+/// Names an `element` or `attribute` pattern accepts
+#[derive(Debug)]
+pub(super) enum NameClass {
+    /// Namespace and local name
+    Name(String, String),
+    /// Local name in any namespace
+    LocalName(String),
+    AnyName(Option<Box<NameClass>>),
+    NsName(String, Option<Box<NameClass>>),
+    Choice(Vec<NameClass>),
+}
+
+impl NameClass {
+    pub fn from_qname(ns: &Option<String>, local: &str) -> Self {
+        match ns {
+            Some(ns) => NameClass::Name(ns.clone(), local.to_string()),
+            None => NameClass::LocalName(local.to_string()),
+        }
+    }
+
+    pub fn contains(&self, ns: &str, local: &str) -> bool {
+        match self {
+            NameClass::Name(n, l) => n == ns && l == local,
+            NameClass::LocalName(l) => l == local,
+            NameClass::AnyName(except) => !except.as_ref().is_some_and(|e| e.contains(ns, local)),
+            NameClass::NsName(n, except) => {
+                n == ns && !except.as_ref().is_some_and(|e| e.contains(ns, local))
+            }
+            NameClass::Choice(classes) => classes.iter().any(|c| c.contains(ns, local)),
+        }
+    }
+
+    /// Whether this is a plain name rather than a wildcard
+    pub fn is_name(&self) -> bool {
+        matches!(self, NameClass::Name(..) | NameClass::LocalName(_))
+    }
+
+    /// Human-readable names, for messages
+    pub fn describe(&self, out: &mut Vec<String>) {
+        match self {
+            NameClass::Name(_, local) | NameClass::LocalName(local) => out.push(local.clone()),
+            NameClass::AnyName(_) => out.push("*".to_string()),
+            NameClass::NsName(ns, _) => out.push(format!("{{{}}}*", ns)),
+            NameClass::Choice(classes) => classes.iter().for_each(|c| c.describe(out)),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::fix::Fix;
 use console::style;
 use serde::{Deserialize, Serialize};
 
@@ -13,6 +14,54 @@ pub struct Message {
     pub line: usize,
     pub column: usize,
     pub text: String,
+
+    /// Stable identifier of the rule that produced the message
+    pub rule_id: String,
+    pub severity: Severity,
+
+    /// How to fix the issue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+
+    /// Edits that fix the issue mechanically, for `validate --fix`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Box<Fix>>,
+}
+
+impl Message {
+    pub fn new(
+        rule_id: impl Into<String>,
+        severity: Severity,
+        line: usize,
+        column: usize,
+        text: impl Into<String>,
+    ) -> Self {
+        Self {
+            line,
+            column,
+            text: text.into(),
+            rule_id: rule_id.into(),
+            severity,
+            help: None,
+            fix: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(Box::new(fix));
+        self
+    }
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Report {
@@ -28,25 +77,23 @@ impl Report {
         self.errors.is_empty()
     }
 
-    pub fn push(
-        &mut self,
-        line: usize,
-        column: usize,
-        text: impl Into<String>,
-        severity: Severity,
-    ) {
-        let msg = Message {
-            line,
-            column,
-            text: text.into(),
-        };
-        match severity {
+    /// Add a message to the list of its severity
+    pub fn push(&mut self, msg: Message) {
+        match msg.severity {
             Severity::Error => self.errors.push(msg),
             Severity::Warning => self.warnings.push(msg),
             Severity::Info => self.info.push(msg),
         }
     }
 
+    /// All messages, errors first
+    pub fn messages(&self) -> impl Iterator<Item = &Message> {
+        self.errors
+            .iter()
+            .chain(self.warnings.iter())
+            .chain(self.info.iter())
+    }
+
     /// Pretty console output
     pub fn print(&self) {
         if self.is_valid() && self.warnings.is_empty() && self.info.is_empty() {
@@ -55,32 +102,26 @@ impl Report {
         }
 
         println!("\n{}", style("Validation Report").bold().underlined());
-        for msg in &self.errors {
-            println!(
-                "{} {}:{}  {}",
-                style("✗").red(),
-                msg.line,
-                msg.column,
-                msg.text
-            );
-        }
-        for msg in &self.warnings {
+        for msg in self.messages() {
+            let marker = match msg.severity {
+                Severity::Error => style("✗").red(),
+                Severity::Warning => style("⚠").yellow(),
+                Severity::Info => style("ℹ").blue(),
+            };
             println!(
-                "{} {}:{}  {}",
-                style("⚠").yellow(),
+                "{} {}:{}  {}  {}",
+                marker,
                 msg.line,
                 msg.column,
-                msg.text
-            );
-        }
-        for msg in &self.info {
-            println!(
-                "{} {}:{}  {}",
-                style("ℹ").blue(),
-                msg.line,
-                msg.column,
-                msg.text
+                msg.text,
+                style(format!("[{}]", msg.rule_id)).dim()
             );
+            if let Some(help) = &msg.help {
+                println!("    {} {}", style("help:").cyan(), help);
+            }
+            if let Some(fix) = &msg.fix {
+                println!("    {} {}", style("fix:").green(), fix.description);
+            }
         }
         println!("{:-<50}", "");
         println!(
@@ -91,15 +132,27 @@ impl Report {
         );
     }
 
-    /// JSON output (SARIF-ready skeleton)
+    /// JSON output; see `to_sarif` for SARIF 2.1.0
     pub fn to_json_string(&self) -> anyhow::Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Error,
     Warning,
     Info,
 }
+
+impl Severity {
+    /// Lowercase name, as used in configuration files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
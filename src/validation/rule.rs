@@ -0,0 +1,336 @@
+//! Validation rules with stable identifiers, and the registry that runs them.
+//!
+//! Library users can add their own checks next to the built-in ones:
+//!
+//! ```
+//! use roxmltree::Node;
+//! use xml_structer::validation::{Registry, Rule, RuleContext, Severity};
+//!
+//! struct NoEmptyP;
+//!
+//! impl Rule for NoEmptyP {
+//!     fn id(&self) -> &str {
+//!         "no-empty-p"
+//!     }
+//!     fn default_severity(&self) -> Severity {
+//!         Severity::Warning
+//!     }
+//!     fn description(&self) -> &str {
+//!         "<p> elements must have content"
+//!     }
+//!     fn check(&self, node: Node, ctx: &mut RuleContext) {
+//!         if node.has_tag_name("p") && !node.has_children() {
+//!             ctx.report(node, "empty <p>");
+//!         }
+//!     }
+//! }
+//!
+//! let mut registry = Registry::builtin();
+//! registry.register(NoEmptyP).unwrap();
+//! let report = registry.validate("<TEI><p/></TEI>");
+//! assert_eq!(report.warnings[0].rule_id, "no-empty-p");
+//! ```
+
+use super::builtin;
+use super::fix::Fix;
+use super::report::{Message, Report, Severity};
+use super::rules::{ConfigRule, RuleConfig};
+use anyhow::Result;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::path::Path;
+
+/// A check run against every element of a document
+pub trait Rule: Send + Sync {
+    /// Stable identifier, used in messages and for `explain`
+    fn id(&self) -> &str;
+
+    fn default_severity(&self) -> Severity;
+
+    /// One-line summary of what the rule checks
+    fn description(&self) -> &str;
+
+    /// How to fix violations, attached to each message
+    fn help(&self) -> Option<&str> {
+        None
+    }
+
+    /// Inspect one element and report violations through `ctx`
+    fn check(&self, node: Node, ctx: &mut RuleContext);
+}
+
+/// What a rule sees while checking: the document source and a sink for
+/// messages tagged with the rule's id and severity
+pub struct RuleContext<'a> {
+    source: &'a str,
+    path: Option<&'a Path>,
+    rule_id: &'a str,
+    severity: Severity,
+    help: Option<&'a str>,
+    report: &'a mut Report,
+}
+
+impl RuleContext<'_> {
+    /// Full text of the document being validated
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Path of the document, when validating a file (for resolving
+    /// references such as a DOCTYPE's system identifier)
+    pub fn path(&self) -> Option<&Path> {
+        self.path
+    }
+
+    /// Report a violation at the start of `node`
+    pub fn report(&mut self, node: Node, text: impl Into<String>) {
+        let (line, column) = node_position(node);
+        self.report_at(line, column, text);
+    }
+
+    /// Report a violation at a 1-based position
+    pub fn report_at(&mut self, line: usize, column: usize, text: impl Into<String>) {
+        let mut msg = Message::new(self.rule_id, self.severity, line, column, text);
+        msg.help = self.help.map(str::to_string);
+        self.report.push(msg);
+    }
+
+    /// Report a violation at the start of `node` that `fix` repairs
+    pub fn report_with_fix(&mut self, node: Node, text: impl Into<String>, fix: Fix) {
+        let (line, column) = node_position(node);
+        self.report_with_fix_at(line, column, text, fix);
+    }
+
+    /// Report a violation at a 1-based position that `fix` repairs
+    pub fn report_with_fix_at(
+        &mut self,
+        line: usize,
+        column: usize,
+        text: impl Into<String>,
+        fix: Fix,
+    ) {
+        let mut msg = Message::new(self.rule_id, self.severity, line, column, text).with_fix(fix);
+        msg.help = self.help.map(str::to_string);
+        self.report.push(msg);
+    }
+
+    /// Report a message with its own id and severity, for rules that bundle
+    /// sub-rules (such as the asserts of a Schematron schema)
+    pub fn report_message(&mut self, msg: Message) {
+        self.report.push(msg);
+    }
+}
+
+/// 1-based line and column of the start of a node
+pub fn node_position(node: Node) -> (usize, usize) {
+    let pos = node.document().text_pos_at(node.range().start);
+    (pos.row as usize, pos.col as usize)
+}
+
+/// The rules a validation run applies, in registration order
+pub struct Registry {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Registry {
+    /// A registry without any rules (documents are still parsed, so
+    /// well-formedness errors are reported)
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// The built-in TEI rules
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        for rule in builtin::rules() {
+            registry.rules.push(rule);
+        }
+        registry
+    }
+
+    /// The built-in rules followed by the configured `[[rules]]`
+    pub fn with_config(configs: &[RuleConfig]) -> Result<Self> {
+        let mut registry = Self::builtin();
+        for (i, config) in configs.iter().enumerate() {
+            registry.register(ConfigRule::compile(config, i + 1)?)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a rule; fails if its id is already taken
+    pub fn register(&mut self, rule: impl Rule + 'static) -> Result<()> {
+        if self.get(rule.id()).is_some() {
+            anyhow::bail!("Duplicate rule id: {}", rule.id());
+        }
+        self.rules.push(Box::new(rule));
+        Ok(())
+    }
+
+    /// Remove a rule; fails if no rule has this id
+    pub fn disable(&mut self, id: &str) -> Result<()> {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.id() != id);
+        if self.rules.len() == before {
+            anyhow::bail!("Unknown rule id: {}", id);
+        }
+        Ok(())
+    }
+
+    /// Swap a rule for another one with the same id, e.g. a built-in rule
+    /// for one built from the configuration; fails if no rule has this id
+    pub fn replace(&mut self, rule: impl Rule + 'static) -> Result<()> {
+        let Some(slot) = self.rules.iter_mut().find(|r| r.id() == rule.id()) else {
+            anyhow::bail!("Unknown rule id: {}", rule.id());
+        };
+        *slot = Box::new(rule);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&dyn Rule> {
+        self.rules.iter().find(|r| r.id() == id).map(|r| r.as_ref())
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|r| r.as_ref())
+    }
+
+    /// Parse `xml` and run every rule against every element
+    pub fn validate(&self, xml: &str) -> Report {
+        self.validate_source(xml, None, |_| ()).0
+    }
+
+    /// Like `validate`, for the contents of the file at `path`
+    pub fn validate_file(&self, xml: &str, path: &Path) -> Report {
+        self.validate_file_with(xml, path, |_| ()).0
+    }
+
+    /// Like `validate_file`, also handing the parsed document to `inspect`
+    /// so callers don't parse it again; `None` if `xml` is not well-formed
+    pub fn validate_file_with<T>(
+        &self,
+        xml: &str,
+        path: &Path,
+        inspect: impl FnOnce(&Document) -> T,
+    ) -> (Report, Option<T>) {
+        self.validate_source(xml, Some(path), inspect)
+    }
+
+    fn validate_source<T>(
+        &self,
+        xml: &str,
+        path: Option<&Path>,
+        inspect: impl FnOnce(&Document) -> T,
+    ) -> (Report, Option<T>) {
+        let mut report = Report::new();
+
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = match Document::parse_with_options(xml, options) {
+            Ok(doc) => doc,
+            Err(e) => {
+                report.push(
+                    Message::new(
+                        builtin::WELL_FORMED,
+                        Severity::Error,
+                        e.pos().row as usize,
+                        e.pos().col as usize,
+                        format!("XML parsing error: {}", e),
+                    )
+                    .with_help("Fix the markup; no other rule runs on malformed XML"),
+                );
+                return (report, None);
+            }
+        };
+
+        for node in doc.root_element().descendants().filter(|n| n.is_element()) {
+            for rule in &self.rules {
+                let mut ctx = RuleContext {
+                    source: xml,
+                    path,
+                    rule_id: rule.id(),
+                    severity: rule.default_severity(),
+                    help: rule.help(),
+                    report: &mut report,
+                };
+                rule.check(node, &mut ctx);
+            }
+        }
+        let inspected = inspect(&doc);
+        (report, Some(inspected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_rule_ids() {
+        let report = Registry::builtin().validate("<root><pb/><head/></root>");
+        let ids: Vec<&str> = report.messages().map(|m| m.rule_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "pb-required-attributes",
+                "pb-required-attributes",
+                "tei-root",
+                "head-inside-div"
+            ]
+        );
+        assert_eq!(report.errors[0].severity, Severity::Error);
+        assert!(report.errors[0].help.is_some());
+    }
+
+    #[test]
+    fn test_well_formed_error_has_rule_id() {
+        let report = Registry::builtin().validate("<TEI>");
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].rule_id, builtin::WELL_FORMED);
+    }
+
+    #[test]
+    fn test_validate_file_with_hands_back_document() {
+        let registry = Registry::builtin();
+        let path = Path::new("a.xml");
+
+        let (report, root) = registry.validate_file_with("<TEI><pb/></TEI>", path, |doc| {
+            doc.root_element().tag_name().name().to_string()
+        });
+        assert_eq!(root.as_deref(), Some("TEI"));
+        assert!(!report.is_valid());
+
+        let (report, root) = registry.validate_file_with("<TEI>", path, |_| ());
+        assert!(root.is_none());
+        assert_eq!(report.errors[0].rule_id, builtin::WELL_FORMED);
+    }
+
+    #[test]
+    fn test_register_and_disable() {
+        let mut registry = Registry::with_config(&[toml::from_str(
+            "element = \"p\"\nrequired_attributes = [\"n\"]",
+        )
+        .unwrap()])
+        .unwrap();
+        assert!(registry.get("config-1").is_some());
+
+        let report = registry.validate("<TEI><p/><pb ed='a' n='1'/></TEI>");
+        assert_eq!(report.warnings[0].rule_id, "config-1");
+
+        registry.disable("config-1").unwrap();
+        assert!(registry.validate("<TEI><p/></TEI>").warnings.is_empty());
+        assert!(registry.disable("config-1").is_err());
+
+        let config: RuleConfig = toml::from_str("element = \"p\"\nid = \"tei-root\"").unwrap();
+        let err = registry
+            .register(ConfigRule::compile(&config, 1).unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("Duplicate rule id: tei-root"));
+    }
+}
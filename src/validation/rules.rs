@@ -0,0 +1,396 @@
+//! Declarative validation rules from the `[[rules]]` configuration section.
+//!
+//! ```toml
+//! [[rules]]
+//! element = "div"
+//! severity = "error"
+//! required_attributes = ["type"]
+//! allowed_parents = ["body", "div", "front", "back"]
+//! attribute_values = { type = ["chapter", "section", "letter"] }
+//! attribute_patterns = { n = "[0-9]+" }
+//! child_counts = [{ child = "head", max = 1 }]
+//! ```
+
+use super::report::Severity;
+use super::rule::{Rule, RuleContext};
+use anyhow::{Context, Result};
+use regex_automata::meta::Regex;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Constraints on one element name, as written in the configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Stable rule id for messages and `explain` (default `config-<n>`)
+    #[serde(default)]
+    pub id: Option<String>,
+
+    /// Local name of the constrained element, or `*` for every element
+    pub element: String,
+
+    /// Shown by `explain`
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Attached to every message of the rule
+    #[serde(default)]
+    pub help: Option<String>,
+
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+
+    /// Attributes the element must have
+    #[serde(default)]
+    pub required_attributes: Vec<String>,
+
+    /// The parent must be one of these elements
+    #[serde(default)]
+    pub allowed_parents: Vec<String>,
+
+    /// The parent must not be one of these elements
+    #[serde(default)]
+    pub forbidden_parents: Vec<String>,
+
+    /// At least one ancestor must be one of these elements
+    #[serde(default)]
+    pub allowed_ancestors: Vec<String>,
+
+    /// No ancestor may be one of these elements
+    #[serde(default)]
+    pub forbidden_ancestors: Vec<String>,
+
+    /// Allowed value sets per attribute
+    #[serde(default)]
+    pub attribute_values: BTreeMap<String, Vec<String>>,
+
+    /// Regular expressions the whole attribute value must match
+    #[serde(default)]
+    pub attribute_patterns: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub child_counts: Vec<ChildCount>,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+/// Bounds on the number of element children, optionally of one name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChildCount {
+    /// Counted child name; every element child if omitted
+    #[serde(default)]
+    pub child: Option<String>,
+    #[serde(default)]
+    pub min: Option<usize>,
+    #[serde(default)]
+    pub max: Option<usize>,
+}
+
+/// A configured rule with its patterns compiled
+#[derive(Debug)]
+pub struct ConfigRule {
+    id: String,
+    description: String,
+    config: RuleConfig,
+    patterns: Vec<(String, String, Regex)>,
+}
+
+impl ConfigRule {
+    /// Compile the `index`th (1-based) configured rule; fails on invalid
+    /// patterns. Rules without an `id` are named `config-<index>`.
+    pub fn compile(config: &RuleConfig, index: usize) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for (attr, pattern) in &config.attribute_patterns {
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).with_context(|| {
+                format!(
+                    "Invalid pattern for <{}> @{}: {}",
+                    config.element, attr, pattern
+                )
+            })?;
+            patterns.push((attr.clone(), pattern.clone(), regex));
+        }
+        Ok(Self {
+            id: config
+                .id
+                .clone()
+                .unwrap_or_else(|| format!("config-{}", index)),
+            description: config
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("Configured constraints on <{}>", config.element)),
+            config: config.clone(),
+            patterns,
+        })
+    }
+}
+
+impl Rule for ConfigRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn default_severity(&self) -> Severity {
+        self.config.severity
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn help(&self) -> Option<&str> {
+        self.config.help.as_deref()
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        let config = &self.config;
+        let name = node.tag_name().name();
+        if config.element != "*" && config.element != name {
+            return;
+        }
+        let mut push = |text: String| ctx.report(node, text);
+
+        for attr in &config.required_attributes {
+            if local_attribute(node, attr).is_none() {
+                push(format!("<{}> missing @{}", name, attr));
+            }
+        }
+
+        let parent = node.parent_element().map(|p| p.tag_name().name());
+        if !config.allowed_parents.is_empty()
+            && !parent.is_some_and(|p| config.allowed_parents.iter().any(|a| a == p))
+        {
+            push(format!(
+                "<{}> not allowed inside {} (allowed parents: {})",
+                name,
+                parent.map_or("the document".to_string(), |p| format!("<{}>", p)),
+                config.allowed_parents.join(", ")
+            ));
+        }
+        if let Some(p) = parent.filter(|p| config.forbidden_parents.iter().any(|f| f == p)) {
+            push(format!("<{}> not allowed inside <{}>", name, p));
+        }
+
+        let mut ancestors = node.ancestors().skip(1).filter(|a| a.is_element());
+        if !config.allowed_ancestors.is_empty()
+            && !ancestors.clone().any(|a| {
+                config
+                    .allowed_ancestors
+                    .iter()
+                    .any(|x| x == a.tag_name().name())
+            })
+        {
+            let names: Vec<String> = config
+                .allowed_ancestors
+                .iter()
+                .map(|a| format!("<{}>", a))
+                .collect();
+            push(format!(
+                "<{}> should be inside {}",
+                name,
+                names.join(" or ")
+            ));
+        }
+        if let Some(a) = ancestors.find(|a| {
+            config
+                .forbidden_ancestors
+                .iter()
+                .any(|f| f == a.tag_name().name())
+        }) {
+            push(format!(
+                "<{}> must not be inside <{}>",
+                name,
+                a.tag_name().name()
+            ));
+        }
+
+        for (attr, allowed) in &config.attribute_values {
+            if let Some(value) = local_attribute(node, attr) {
+                if !allowed.iter().any(|v| v == value) {
+                    push(format!(
+                        "<{}> @{} has disallowed value '{}' (allowed: {})",
+                        name,
+                        attr,
+                        value,
+                        allowed.join(", ")
+                    ));
+                }
+            }
+        }
+        for (attr, pattern, regex) in &self.patterns {
+            if let Some(value) = local_attribute(node, attr) {
+                if !regex.is_match(value) {
+                    push(format!(
+                        "<{}> @{} value '{}' does not match /{}/",
+                        name, attr, value, pattern
+                    ));
+                }
+            }
+        }
+
+        for count in &config.child_counts {
+            let n = node
+                .children()
+                .filter(|c| c.is_element())
+                .filter(|c| {
+                    count
+                        .child
+                        .as_deref()
+                        .is_none_or(|x| x == c.tag_name().name())
+                })
+                .count();
+            let what = count
+                .child
+                .as_ref()
+                .map_or("element".to_string(), |c| format!("<{}>", c));
+            if let Some(min) = count.min.filter(|&min| n < min) {
+                push(format!(
+                    "<{}> has {} {} children, expected at least {}",
+                    name, n, what, min
+                ));
+            }
+            if let Some(max) = count.max.filter(|&max| n > max) {
+                push(format!(
+                    "<{}> has {} {} children, expected at most {}",
+                    name, n, what, max
+                ));
+            }
+        }
+    }
+}
+
+/// Attribute value by local name, so `id` also finds `xml:id`
+fn local_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name)
+        .map(|a| a.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{Registry, Report};
+
+    fn check(rules_toml: &str, xml: &str) -> Report {
+        #[derive(Deserialize)]
+        struct Rules {
+            rules: Vec<RuleConfig>,
+        }
+        let rules: Rules = toml::from_str(rules_toml).unwrap();
+        let mut registry = Registry::empty();
+        for (i, config) in rules.rules.iter().enumerate() {
+            registry
+                .register(ConfigRule::compile(config, i + 1).unwrap())
+                .unwrap();
+        }
+        registry.validate(xml)
+    }
+
+    fn texts(rep: &Report) -> Vec<&str> {
+        rep.messages().map(|m| m.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_required_attributes_and_severity() {
+        let rep = check(
+            r#"
+            [[rules]]
+            element = "pb"
+            severity = "error"
+            required_attributes = ["facs", "id"]
+            "#,
+            r#"<TEI xmlns:xml="http://www.w3.org/XML/1998/namespace"><pb xml:id="p1"/></TEI>"#,
+        );
+        assert_eq!(texts(&rep), vec!["<pb> missing @facs"]);
+        assert_eq!(rep.errors.len(), 1);
+        assert_eq!(rep.errors[0].rule_id, "config-1");
+    }
+
+    #[test]
+    fn test_parent_and_ancestor_relations() {
+        let rules = r#"
+            [[rules]]
+            element = "head"
+            allowed_parents = ["div", "lg"]
+            forbidden_ancestors = ["note"]
+
+            [[rules]]
+            element = "pb"
+            forbidden_parents = ["p"]
+            allowed_ancestors = ["body"]
+        "#;
+        let rep = check(
+            rules,
+            "<TEI><body><div><head/><note><lg><head/></lg></note></div><p><pb/></p></body><pb/></TEI>",
+        );
+        assert_eq!(
+            texts(&rep),
+            vec![
+                "<head> must not be inside <note>",
+                "<pb> not allowed inside <p>",
+                "<pb> should be inside <body>",
+            ]
+        );
+        assert_eq!(rep.warnings.len(), 3);
+
+        let rep = check(rules, "<TEI><head/></TEI>");
+        assert_eq!(
+            texts(&rep),
+            vec!["<head> not allowed inside <TEI> (allowed parents: div, lg)"]
+        );
+    }
+
+    #[test]
+    fn test_attribute_values_and_patterns() {
+        let rep = check(
+            r#"
+            [[rules]]
+            element = "div"
+            attribute_values = { type = ["chapter", "section"] }
+            attribute_patterns = { n = "[0-9]+" }
+            "#,
+            r#"<TEI><div type="chapter" n="1"/><div type="part" n="1a"/><div/></TEI>"#,
+        );
+        assert_eq!(
+            texts(&rep),
+            vec![
+                "<div> @type has disallowed value 'part' (allowed: chapter, section)",
+                "<div> @n value '1a' does not match /[0-9]+/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_child_counts() {
+        let rep = check(
+            r#"
+            [[rules]]
+            element = "div"
+            severity = "info"
+            child_counts = [{ child = "head", max = 1 }, { min = 2 }]
+            "#,
+            "<TEI><div><head/><head/></div><div><p/></div></TEI>",
+        );
+        assert_eq!(
+            texts(&rep),
+            vec![
+                "<div> has 2 <head> children, expected at most 1",
+                "<div> has 1 element children, expected at least 2",
+            ]
+        );
+        assert_eq!(rep.info.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let config = RuleConfig {
+            attribute_patterns: [("n".to_string(), "(".to_string())].into(),
+            ..toml::from_str("element = \"pb\"").unwrap()
+        };
+        let err = ConfigRule::compile(&config, 1).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid pattern for <pb> @n"));
+    }
+}
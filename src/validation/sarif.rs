@@ -0,0 +1,311 @@
+//! SARIF 2.1.0 logs for code-scanning dashboards.
+//!
+//! One run per log: the tool driver lists every rule of the registry, each
+//! message becomes a result located in its file, and unreadable files become
+//! tool execution notifications.
+
+use super::corpus::{CorpusReport, FileReport};
+use super::report::{Message, Report, Severity};
+use super::rule::Registry;
+use serde::Serialize;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+#[derive(Debug, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    pub schema: &'static str,
+    pub version: &'static str,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Run {
+    pub tool: Tool,
+    pub invocations: Vec<Invocation>,
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    pub driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Driver {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub rules: Vec<ReportingDescriptor>,
+}
+
+/// Metadata of one rule
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportingDescriptor {
+    pub id: String,
+    pub short_description: Text,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<Text>,
+    pub default_configuration: Configuration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Configuration {
+    pub level: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Text {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invocation {
+    pub execution_successful: bool,
+    pub tool_execution_notifications: Vec<Notification>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub level: &'static str,
+    pub message: Text,
+    pub locations: Vec<Location>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_index: Option<usize>,
+    pub level: &'static str,
+    pub message: Text,
+    pub locations: Vec<Location>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalLocation {
+    pub artifact_location: ArtifactLocation,
+
+    /// Omitted for document-level messages without a position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtifactLocation {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub start_line: usize,
+    pub start_column: usize,
+}
+
+impl SarifLog {
+    /// Log of a corpus validation run with the rules of `registry`
+    pub fn from_corpus(report: &CorpusReport, registry: &Registry) -> Self {
+        let rules: Vec<ReportingDescriptor> = registry
+            .rules()
+            .map(|rule| ReportingDescriptor {
+                id: rule.id().to_string(),
+                short_description: Text {
+                    text: rule.description().to_string(),
+                },
+                help: rule.help().map(|h| Text {
+                    text: h.to_string(),
+                }),
+                default_configuration: Configuration {
+                    level: level(rule.default_severity()),
+                },
+            })
+            .collect();
+
+        let results = report
+            .files
+            .iter()
+            .flat_map(|file| {
+                file.report
+                    .messages()
+                    .map(|msg| result(&file.file, msg, &rules))
+            })
+            .collect();
+
+        let notifications = report
+            .failures
+            .iter()
+            .map(|failure| Notification {
+                level: "error",
+                message: Text {
+                    text: failure.message.clone(),
+                },
+                locations: vec![location(&failure.file, None)],
+            })
+            .collect();
+
+        Self {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                        rules,
+                    },
+                },
+                invocations: vec![Invocation {
+                    execution_successful: report.failures.is_empty(),
+                    tool_execution_notifications: notifications,
+                }],
+                results,
+            }],
+        }
+    }
+
+    pub fn to_json_string(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Report {
+    /// SARIF log of this report, located in `file`
+    pub fn to_sarif(&self, file: &str, registry: &Registry) -> SarifLog {
+        let corpus = CorpusReport {
+            files: vec![FileReport {
+                file: file.to_string(),
+                report: self.clone(),
+            }],
+            failures: Vec::new(),
+        };
+        SarifLog::from_corpus(&corpus, registry)
+    }
+}
+
+fn result(file: &str, msg: &Message, rules: &[ReportingDescriptor]) -> SarifResult {
+    let region = (msg.line > 0).then_some(Region {
+        start_line: msg.line,
+        start_column: msg.column.max(1),
+    });
+    SarifResult {
+        rule_id: msg.rule_id.clone(),
+        rule_index: rules.iter().position(|r| r.id == msg.rule_id),
+        level: level(msg.severity),
+        message: Text {
+            text: msg.text.clone(),
+        },
+        locations: vec![location(file, region)],
+    }
+}
+
+fn location(file: &str, region: Option<Region>) -> Location {
+    Location {
+        physical_location: PhysicalLocation {
+            artifact_location: ArtifactLocation {
+                uri: file_uri(file),
+            },
+            region,
+        },
+    }
+}
+
+/// SARIF result level of a severity
+pub fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+/// URI reference for a path: relative paths stay relative (resolved against
+/// the repository root by dashboards), absolute paths become `file://` URIs
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    let mut uri = String::with_capacity(path.len());
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/:".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    if uri.starts_with('/') {
+        format!("file://{}", uri)
+    } else {
+        uri
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::corpus::ValidationFailure;
+
+    #[test]
+    fn test_sarif_log() {
+        let registry = Registry::builtin();
+        let report = CorpusReport {
+            files: vec![FileReport {
+                file: "corpus/my letter.xml".to_string(),
+                report: registry.validate("<root>\n  <pb n=\"1\"/>\n</root>"),
+            }],
+            failures: vec![ValidationFailure {
+                file: "/data/missing.xml".to_string(),
+                message: "Failed to read file".to_string(),
+            }],
+        };
+
+        let log = serde_json::to_value(SarifLog::from_corpus(&report, &registry)).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "xml_structer");
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), registry.rules().count());
+        assert_eq!(rules[0]["id"], "xml-well-formed");
+        assert_eq!(rules[0]["defaultConfiguration"]["level"], "error");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        let pb = &results[0];
+        assert_eq!(pb["ruleId"], "pb-required-attributes");
+        assert_eq!(pb["level"], "error");
+        assert_eq!(
+            rules[pb["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            "pb-required-attributes"
+        );
+        let location = &pb["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            "corpus/my%20letter.xml"
+        );
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 3);
+
+        // Document-level message without a position
+        assert_eq!(results[1]["ruleId"], "tei-root");
+        assert_eq!(results[1]["level"], "warning");
+        assert!(results[1]["locations"][0]["physicalLocation"]["region"].is_null());
+
+        let invocation = &run["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        assert_eq!(
+            invocation["toolExecutionNotifications"][0]["locations"][0]["physicalLocation"]
+                ["artifactLocation"]["uri"],
+            "file:///data/missing.xml"
+        );
+    }
+}
@@ -0,0 +1,702 @@
+//! ISO Schematron with XPath 1.0 tests, evaluated by the query engine.
+//!
+//! Supported: `pattern`, `rule` (first matching rule per pattern wins),
+//! abstract rules with `extends`, `assert`, `report`, `let` at schema,
+//! pattern and rule level, and `name`/`value-of` in messages. Constraints
+//! embedded in a TEI ODD (`constraintSpec`) are picked up too; asserts
+//! without an enclosing rule apply to the `elementSpec` they belong to.
+//!
+//! Phases, diagnostics and `include` are not supported. Name tests compare
+//! local names, so `sch:ns` prefix declarations are accepted but not needed.
+//! Expressions calling a function outside the query engine's library fail
+//! when the schema is loaded.
+//!
+//! Message ids come from the `id` of the assert or report, else of its rule,
+//! pattern or ODD `constraintSpec`. Severity comes from `role` (`fatal`,
+//! `error`, `warning`, `info`), defaulting to error.
+
+use super::report::{Message, Severity};
+use super::rule::{Rule, RuleContext};
+use crate::query::{Evaluator, Value, XNode, XPath, FUNCTIONS};
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const ISO_NS: &str = "http://purl.oclc.org/dsdl/schematron";
+const SCH15_NS: &str = "http://www.ascc.net/xml/schematron";
+
+/// A compiled Schematron schema, registered as one rule
+#[derive(Debug)]
+pub struct Schematron {
+    id: String,
+    description: String,
+    lets: Vec<Let>,
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Let {
+    name: String,
+    value: XPath,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    lets: Vec<Let>,
+    rules: Vec<SchRule>,
+}
+
+#[derive(Debug, Clone)]
+struct SchRule {
+    context: XPath,
+    lets: Vec<Let>,
+    checks: Vec<Check>,
+}
+
+#[derive(Debug, Clone)]
+struct Check {
+    /// `report` fires when the test is true, `assert` when it is false
+    is_report: bool,
+    id: String,
+    test: XPath,
+    severity: Severity,
+    message: Vec<MessagePart>,
+}
+
+#[derive(Debug, Clone)]
+enum MessagePart {
+    Text(String),
+    /// `<name/>`, optionally of `@path`
+    Name(Option<XPath>),
+    ValueOf(XPath),
+}
+
+/// Ids inherited by the checks below a schema element
+#[derive(Clone)]
+struct Defaults<'a> {
+    id: &'a str,
+    role: Option<&'a str>,
+}
+
+impl Schematron {
+    /// Load a standalone `.sch` file or an ODD with embedded Schematron
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read Schematron file: {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "schema".to_string());
+        Self::parse(&text, &name)
+            .with_context(|| format!("Invalid Schematron schema: {}", path.display()))
+    }
+
+    /// Compile a schema; `name` identifies it when the schema has no `id`
+    pub fn parse(text: &str, name: &str) -> Result<Self> {
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(text, options)?;
+        let root = doc.root_element();
+
+        let schema_id = root
+            .attribute("id")
+            .filter(|_| is_sch(root, "schema"))
+            .unwrap_or(name);
+        let title = root
+            .children()
+            .find(|n| is_sch(*n, "title"))
+            .and_then(|t| t.text())
+            .map(|t| t.trim().to_string());
+
+        let lets = if is_sch(root, "schema") {
+            parse_lets(root)?
+        } else {
+            Vec::new()
+        };
+
+        // Abstract rules, referenced by `<extends rule="…"/>`
+        let mut abstract_rules = HashMap::new();
+        for node in root.descendants().filter(|n| is_sch(*n, "rule")) {
+            if node.attribute("abstract") == Some("true") {
+                if let Some(id) = node.attribute("id") {
+                    abstract_rules.insert(id, node);
+                }
+            }
+        }
+
+        let mut patterns = Vec::new();
+        for node in root.descendants() {
+            if is_sch(node, "pattern") && node.attribute("abstract") != Some("true") {
+                let defaults = Defaults {
+                    id: node.attribute("id").unwrap_or("schematron"),
+                    role: None,
+                };
+                let defaults = odd_defaults(node, defaults);
+                let mut rules = Vec::new();
+                for rule in node.children().filter(|n| is_sch(*n, "rule")) {
+                    if rule.attribute("abstract") != Some("true") {
+                        rules.push(parse_rule(
+                            rule,
+                            None,
+                            &defaults,
+                            &abstract_rules,
+                            &mut Vec::new(),
+                        )?);
+                    }
+                }
+                patterns.push(Pattern {
+                    lets: parse_lets(node)?,
+                    rules,
+                });
+            } else if is_sch(node, "rule")
+                && node.attribute("abstract") != Some("true")
+                && !node.parent_element().is_some_and(|p| is_sch(p, "pattern"))
+            {
+                // A rule embedded directly in an ODD constraint
+                let defaults = odd_defaults(
+                    node,
+                    Defaults {
+                        id: "schematron",
+                        role: None,
+                    },
+                );
+                patterns.push(Pattern {
+                    lets: Vec::new(),
+                    rules: vec![parse_rule(
+                        node,
+                        None,
+                        &defaults,
+                        &abstract_rules,
+                        &mut Vec::new(),
+                    )?],
+                });
+            } else if (is_sch(node, "assert") || is_sch(node, "report"))
+                && !node.parent_element().is_some_and(|p| is_sch(p, "rule"))
+            {
+                // An ODD constraint without a rule: the context is the
+                // element the enclosing elementSpec describes
+                let Some(ident) = node
+                    .ancestors()
+                    .find(|a| a.tag_name().name() == "elementSpec")
+                    .and_then(|a| a.attribute("ident"))
+                else {
+                    continue;
+                };
+                let defaults = odd_defaults(
+                    node,
+                    Defaults {
+                        id: "schematron",
+                        role: None,
+                    },
+                );
+                let context = parse_xpath_pattern(ident)
+                    .with_context(|| format!("Invalid elementSpec ident: {}", ident))?;
+                patterns.push(Pattern {
+                    lets: Vec::new(),
+                    rules: vec![SchRule {
+                        context,
+                        lets: Vec::new(),
+                        checks: vec![parse_check(node, &defaults)?],
+                    }],
+                });
+            }
+        }
+
+        if patterns.is_empty() {
+            anyhow::bail!("No Schematron patterns, rules or asserts found");
+        }
+
+        let checks: usize = patterns
+            .iter()
+            .flat_map(|p| &p.rules)
+            .map(|r| r.checks.len())
+            .sum();
+        Ok(Self {
+            id: format!("schematron:{}", schema_id),
+            description: format!(
+                "{} ({} patterns, {} asserts and reports)",
+                title.unwrap_or_else(|| format!("Schematron schema {}", name)),
+                patterns.len(),
+                checks
+            ),
+            lets,
+            patterns,
+        })
+    }
+
+    /// Run every pattern against a document
+    pub fn check_document(&self, doc: &Document) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let root = XNode::Node(doc.root());
+
+        let mut evaluator = Evaluator::new();
+        if let Err(msg) = bind_lets(&mut evaluator, &self.lets, root, &self.id) {
+            return vec![msg];
+        }
+
+        for pattern in &self.patterns {
+            let mut evaluator = evaluator.clone();
+            if let Err(msg) = bind_lets(&mut evaluator, &pattern.lets, root, &self.id) {
+                messages.push(msg);
+                continue;
+            }
+
+            // Within a pattern, a node is handled by the first rule matching it
+            let mut fired: HashSet<(usize, Option<usize>)> = HashSet::new();
+            for rule in &pattern.rules {
+                let nodes = match evaluator.evaluate(&rule.context, root) {
+                    Ok(Value::Nodes(nodes)) => nodes,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        messages.push(eval_error(&self.id, root, &rule.context, e));
+                        continue;
+                    }
+                };
+                for node in nodes {
+                    if !fired.insert(node_key(node)) {
+                        continue;
+                    }
+                    let mut evaluator = evaluator.clone();
+                    if let Err(msg) = bind_lets(&mut evaluator, &rule.lets, node, &self.id) {
+                        messages.push(msg);
+                        continue;
+                    }
+                    for check in &rule.checks {
+                        match evaluator.evaluate(&check.test, node) {
+                            Ok(value) if value.to_bool() == check.is_report => {
+                                let (line, column) = node.position();
+                                messages.push(Message::new(
+                                    &check.id,
+                                    check.severity,
+                                    line,
+                                    column,
+                                    render_message(check, &evaluator, node),
+                                ));
+                            }
+                            Ok(_) => {}
+                            Err(e) => messages.push(eval_error(&check.id, node, &check.test, e)),
+                        }
+                    }
+                }
+            }
+        }
+        messages
+    }
+}
+
+impl Rule for Schematron {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        // Patterns select their own context nodes, so run once per document
+        if node.parent_element().is_none() {
+            for msg in self.check_document(node.document()) {
+                ctx.report_message(msg);
+            }
+        }
+    }
+}
+
+fn is_sch(node: Node, name: &str) -> bool {
+    node.has_tag_name((ISO_NS, name)) || node.has_tag_name((SCH15_NS, name))
+}
+
+/// Id of the enclosing ODD `constraintSpec`, if any, as fallback id
+fn odd_defaults<'a>(node: Node<'a, '_>, defaults: Defaults<'a>) -> Defaults<'a> {
+    if defaults.id != "schematron" {
+        return defaults;
+    }
+    match node
+        .ancestors()
+        .find(|a| a.tag_name().name() == "constraintSpec")
+        .and_then(|a| a.attribute("ident"))
+    {
+        Some(ident) => Defaults {
+            id: ident,
+            ..defaults
+        },
+        None => defaults,
+    }
+}
+
+/// Parse an expression of the schema. Functions the evaluator lacks (such
+/// as XPath 2.0 `tokenize()`) are rejected here; evaluating them would fail
+/// on every document and be reported under the id of the check.
+fn parse_xpath(source: &str) -> Result<XPath> {
+    supported(XPath::parse(source)?)
+}
+
+fn parse_xpath_pattern(source: &str) -> Result<XPath> {
+    supported(XPath::parse_pattern(source)?)
+}
+
+fn supported(xpath: XPath) -> Result<XPath> {
+    if let Some(name) = xpath
+        .functions()
+        .into_iter()
+        .find(|f| !FUNCTIONS.contains(f))
+    {
+        anyhow::bail!("Unsupported function {}()", name);
+    }
+    Ok(xpath)
+}
+
+fn parse_lets(node: Node) -> Result<Vec<Let>> {
+    node.children()
+        .filter(|n| is_sch(*n, "let"))
+        .map(|n| {
+            let name = n
+                .attribute("name")
+                .context("<let> without @name")?
+                .to_string();
+            let value = n.attribute("value").unwrap_or("''");
+            let value = parse_xpath(value)
+                .with_context(|| format!("Invalid XPath in <let name=\"{}\">", name))?;
+            Ok(Let { name, value })
+        })
+        .collect()
+}
+
+/// Parse a rule; `extending` holds the ids of the abstract rules whose
+/// `<extends>` are being expanded, to catch cycles
+fn parse_rule<'a>(
+    node: Node<'a, '_>,
+    context: Option<XPath>,
+    defaults: &Defaults,
+    abstract_rules: &HashMap<&str, Node<'a, '_>>,
+    extending: &mut Vec<&'a str>,
+) -> Result<SchRule> {
+    let context = match context {
+        Some(context) => context,
+        None => {
+            let source = node
+                .attribute("context")
+                .context("<rule> without @context")?;
+            parse_xpath_pattern(source)
+                .with_context(|| format!("Invalid rule context: {}", source))?
+        }
+    };
+    let defaults = Defaults {
+        id: node.attribute("id").unwrap_or(defaults.id),
+        role: node.attribute("role").or(defaults.role),
+    };
+
+    let mut lets = parse_lets(node)?;
+    let mut checks = Vec::new();
+    for child in node.children().filter(|n| n.is_element()) {
+        if is_sch(child, "assert") || is_sch(child, "report") {
+            checks.push(parse_check(child, &defaults)?);
+        } else if is_sch(child, "extends") {
+            let id = child.attribute("rule").context("<extends> without @rule")?;
+            let base = abstract_rules
+                .get(id)
+                .with_context(|| format!("<extends> refers to unknown abstract rule: {}", id))?;
+            if extending.contains(&id) {
+                anyhow::bail!("<extends> cycle through {}", id);
+            }
+            extending.push(id);
+            let base = parse_rule(
+                *base,
+                Some(context.clone()),
+                &defaults,
+                abstract_rules,
+                extending,
+            )?;
+            extending.pop();
+            lets.extend(base.lets);
+            checks.extend(base.checks);
+        }
+    }
+    Ok(SchRule {
+        context,
+        lets,
+        checks,
+    })
+}
+
+fn parse_check(node: Node, defaults: &Defaults) -> Result<Check> {
+    let test = node.attribute("test").context("<assert> without @test")?;
+    let test = parse_xpath(test).with_context(|| format!("Invalid XPath in test: {}", test))?;
+
+    let mut message = Vec::new();
+    for child in node.children() {
+        if child.is_text() {
+            message.push(MessagePart::Text(
+                child.text().unwrap_or_default().to_string(),
+            ));
+        } else if is_sch(child, "name") {
+            let path = child
+                .attribute("path")
+                .map(parse_xpath)
+                .transpose()
+                .context("Invalid XPath in <name path>")?;
+            message.push(MessagePart::Name(path));
+        } else if is_sch(child, "value-of") {
+            let select = child
+                .attribute("select")
+                .context("<value-of> without @select")?;
+            let select = parse_xpath(select)
+                .with_context(|| format!("Invalid XPath in <value-of>: {}", select))?;
+            message.push(MessagePart::ValueOf(select));
+        } else if child.is_element() {
+            // emph, dir, span: keep their text
+            let text: String = child.descendants().filter_map(|d| d.text()).collect();
+            message.push(MessagePart::Text(text));
+        }
+    }
+
+    Ok(Check {
+        is_report: is_sch(node, "report"),
+        id: node.attribute("id").unwrap_or(defaults.id).to_string(),
+        test,
+        severity: severity(node.attribute("role").or(defaults.role)),
+        message,
+    })
+}
+
+fn severity(role: Option<&str>) -> Severity {
+    match role.map(|r| r.to_lowercase()).as_deref() {
+        Some("warning" | "warn") => Severity::Warning,
+        Some("info" | "information" | "informational") => Severity::Info,
+        _ => Severity::Error,
+    }
+}
+
+fn bind_lets<'a, 'input>(
+    evaluator: &mut Evaluator<'a, 'input>,
+    lets: &[Let],
+    node: XNode<'a, 'input>,
+    id: &str,
+) -> Result<(), Message> {
+    for binding in lets {
+        let value = evaluator
+            .evaluate(&binding.value, node)
+            .map_err(|e| eval_error(id, node, &binding.value, e))?;
+        evaluator.set_variable(binding.name.clone(), value);
+    }
+    Ok(())
+}
+
+fn render_message(check: &Check, evaluator: &Evaluator, node: XNode) -> String {
+    let mut text = String::new();
+    for part in &check.message {
+        match part {
+            MessagePart::Text(t) => text.push_str(t),
+            MessagePart::Name(None) => text.push_str(&node.name()),
+            MessagePart::Name(Some(path)) => match evaluator.evaluate(path, node) {
+                Ok(Value::Nodes(nodes)) if !nodes.is_empty() => text.push_str(&nodes[0].name()),
+                _ => {}
+            },
+            MessagePart::ValueOf(select) => {
+                if let Ok(value) = evaluator.evaluate(select, node) {
+                    text.push_str(&value.to_string_value());
+                }
+            }
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        let kind = if check.is_report {
+            "Report"
+        } else {
+            "Assertion"
+        };
+        format!("{} failed: {}", kind, check.test)
+    } else {
+        text
+    }
+}
+
+fn eval_error(id: &str, node: XNode, xpath: &XPath, e: impl std::fmt::Display) -> Message {
+    let (line, column) = match node {
+        XNode::Node(n) if n.is_root() => (0, 0),
+        _ => node.position(),
+    };
+    Message::new(
+        id,
+        Severity::Error,
+        line,
+        column,
+        format!("Schematron expression '{}' failed: {}", xpath, e),
+    )
+}
+
+fn node_key(node: XNode) -> (usize, Option<usize>) {
+    match node {
+        XNode::Node(n) => (n.id().get_usize(), None),
+        XNode::Attribute(n, i) => (n.id().get_usize(), Some(i)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Registry;
+
+    const SCHEMA: &str = r#"
+        <schema xmlns="http://purl.oclc.org/dsdl/schematron" id="project">
+          <title>Project constraints</title>
+          <ns prefix="tei" uri="http://www.tei-c.org/ns/1.0"/>
+          <let name="editions" value="'A B'"/>
+          <pattern id="pages">
+            <rule context="tei:pb[@ed]" id="pb-ed">
+              <assert test="contains($editions, @ed)" role="warning">Unknown edition
+                <value-of select="@ed"/> on <name/></assert>
+            </rule>
+            <rule context="tei:pb">
+              <assert test="@ed" id="pb-ed-missing">Page break without edition</assert>
+            </rule>
+          </pattern>
+          <pattern>
+            <rule abstract="true" id="has-head">
+              <assert test="tei:head" id="div-head">A <name/> needs a head</assert>
+            </rule>
+            <rule context="tei:div">
+              <let name="n" value="count(tei:p)"/>
+              <extends rule="has-head"/>
+              <report test="$n &gt; 2" role="info" id="long-div"><value-of select="$n"/> paragraphs</report>
+            </rule>
+          </pattern>
+        </schema>"#;
+
+    #[test]
+    fn test_schematron_asserts_and_reports() {
+        let schematron = Schematron::parse(SCHEMA, "test").unwrap();
+        assert_eq!(schematron.id(), "schematron:project");
+        assert!(schematron.description().starts_with("Project constraints"));
+
+        let xml = r#"<TEI xmlns="http://www.tei-c.org/ns/1.0">
+<pb ed="C" n="1"/><pb ed="A" n="2"/><pb n="3"/>
+<div><p/><p/><p/></div>
+</TEI>"#;
+        let doc = Document::parse(xml).unwrap();
+        let messages = schematron.check_document(&doc);
+        let summary: Vec<(&str, Severity, &str, usize)> = messages
+            .iter()
+            .map(|m| (m.rule_id.as_str(), m.severity, m.text.as_str(), m.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("pb-ed", Severity::Warning, "Unknown edition C on pb", 2),
+                // The first rule already handled pb[@ed]
+                (
+                    "pb-ed-missing",
+                    Severity::Error,
+                    "Page break without edition",
+                    2
+                ),
+                ("div-head", Severity::Error, "A div needs a head", 3),
+                ("long-div", Severity::Info, "3 paragraphs", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_odd_constraints() {
+        let odd = r#"
+            <TEI xmlns="http://www.tei-c.org/ns/1.0" xmlns:sch="http://purl.oclc.org/dsdl/schematron">
+              <elementSpec ident="pb" mode="change">
+                <constraintSpec ident="pb-n-numeric" scheme="schematron">
+                  <constraint>
+                    <sch:assert test="number(@n) = number(@n)">@n must be numeric</sch:assert>
+                  </constraint>
+                </constraintSpec>
+              </elementSpec>
+            </TEI>"#;
+        let mut registry = Registry::empty();
+        registry
+            .register(Schematron::parse(odd, "project-odd").unwrap())
+            .unwrap();
+
+        let report = registry.validate(r#"<TEI><pb n="1"/><pb n="iv"/></TEI>"#);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].rule_id, "pb-n-numeric");
+        assert_eq!(report.errors[0].text, "@n must be numeric");
+        assert_eq!(report.errors[0].column, 17);
+    }
+
+    #[test]
+    fn test_matches_is_a_regular_expression() {
+        let schema = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern><rule context="pb">
+              <assert test="matches(@n, '^[0-9]+$')" id="pb-n-digits">@n must be digits</assert>
+            </rule></pattern>
+        </schema>"#;
+        let mut registry = Registry::empty();
+        registry
+            .register(Schematron::parse(schema, "x").unwrap())
+            .unwrap();
+
+        let report = registry.validate(r#"<TEI><pb n="12"/><pb n="12a"/><pb n="x12"/></TEI>"#);
+        let columns: Vec<(&str, usize)> = report
+            .errors
+            .iter()
+            .map(|m| (m.rule_id.as_str(), m.column))
+            .collect();
+        assert_eq!(columns, vec![("pb-n-digits", 18), ("pb-n-digits", 31)]);
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        let err = Schematron::parse(
+            "<schema xmlns='http://purl.oclc.org/dsdl/schematron'/>",
+            "x",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("No Schematron patterns"));
+
+        let bad_test = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern><rule context="p"><assert test="count(">x</assert></rule></pattern>
+        </schema>"#;
+        let err = Schematron::parse(bad_test, "x").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid XPath in test: count("));
+
+        let unsupported = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern><rule context="p">
+              <assert test="count(tokenize(., ' ')) &lt; 100">x</assert>
+            </rule></pattern>
+        </schema>"#;
+        let err = Schematron::parse(unsupported, "x").unwrap_err();
+        assert!(format!("{:#}", err).contains("Unsupported function tokenize()"));
+
+        let bad_regex = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern><rule context="p"><assert test="matches(., '[a-')">x</assert></rule></pattern>
+        </schema>"#;
+        let err = Schematron::parse(bad_regex, "x").unwrap_err();
+        assert!(format!("{:#}", err).contains("invalid regular expression \"[a-\""));
+
+        let cycle = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern>
+              <rule abstract="true" id="a"><extends rule="b"/></rule>
+              <rule abstract="true" id="b"><extends rule="a"/></rule>
+              <rule context="p"><extends rule="a"/></rule>
+            </pattern>
+        </schema>"#;
+        let err = Schematron::parse(cycle, "x").unwrap_err();
+        assert!(format!("{:#}", err).contains("<extends> cycle through a"));
+
+        let self_cycle = r#"<schema xmlns="http://purl.oclc.org/dsdl/schematron">
+            <pattern>
+              <rule abstract="true" id="a"><extends rule="a"/></rule>
+              <rule context="p"><extends rule="a"/></rule>
+            </pattern>
+        </schema>"#;
+        let err = Schematron::parse(self_cycle, "x").unwrap_err();
+        assert!(format!("{:#}", err).contains("<extends> cycle through a"));
+    }
+}
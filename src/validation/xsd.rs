@@ -0,0 +1,1472 @@
+//! XML Schema 1.0 validation for a practical subset, translated to a RELAX
+//! NG grammar and checked by the same engine.
+//!
+//! Supported: global and local elements and attributes with their form
+//! defaults, named and anonymous complex and simple types, `sequence`,
+//! `choice`, `all`, `any`, `anyAttribute`, model and attribute groups,
+//! occurrence bounds, `complexContent` and `simpleContent` extension and
+//! restriction, restriction facets (flattened onto the built-in base type),
+//! `list`, `union`, substitution groups, and `include` / `import` of local
+//! schema files. Identity constraints, `redefine` bodies, `xsi:type` and the
+//! unique particle attribution rule are not checked; `xsi:*` attributes are
+//! allowed on every element.
+
+use super::local_path;
+use super::relaxng::{Elem, RelaxNg, XSD_LIBRARY};
+use super::report::{Message, Severity};
+use super::rule::{Rule, RuleContext};
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const XS_NS: &str = "http://www.w3.org/2001/XMLSchema";
+pub(crate) const XSI_NS: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Occurrence bounds up to this are expanded exactly; a larger maxOccurs is
+/// treated as unbounded and a larger minOccurs is rejected
+const MAX_OCCURS_EXPANDED: u64 = 32;
+
+/// Define for the content of `xs:anyType` and wildcard elements
+const ANY_TYPE: &str = "xs:anyType";
+
+/// A compiled XML Schema, registered as one rule
+#[derive(Debug)]
+pub struct XmlSchema {
+    inner: RelaxNg,
+}
+
+impl XmlSchema {
+    /// Load and translate a schema file and the local files it includes or
+    /// imports
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "schema".to_string());
+        Self::from_files(&[path.to_path_buf()], format!("xsd:{}", name))
+    }
+
+    /// One schema from several files, e.g. the locations of an
+    /// `xsi:schemaLocation`
+    pub(crate) fn from_files(paths: &[PathBuf], id: String) -> Result<Self> {
+        let sources = load_sources(paths)?;
+        let docs = sources
+            .iter()
+            .map(|s| {
+                let options = ParsingOptions {
+                    allow_dtd: true,
+                    ..ParsingOptions::default()
+                };
+                Document::parse_with_options(&s.text, options)
+                    .with_context(|| format!("Failed to parse XML Schema: {}", s.path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let translator = Translator::new(&sources, &docs)?;
+        let grammar = translator.grammar()?;
+        let files: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+        let description = format!(
+            "Content model of the XML Schema {} ({} global element declarations)",
+            files.join(", "),
+            translator.elements.len()
+        );
+        let inner = RelaxNg::from_grammar(&grammar, id, description)
+            .with_context(|| format!("Invalid XML Schema: {}", files.join(", ")))?;
+        Ok(Self { inner })
+    }
+
+    /// Validate a parsed document; messages are in document order
+    pub fn validate_document(&self, doc: &Document) -> Vec<Message> {
+        self.inner.validate_document(doc)
+    }
+}
+
+impl Rule for XmlSchema {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn help(&self) -> Option<&str> {
+        Some("Change the markup to follow the element and type declarations of the schema")
+    }
+
+    fn check(&self, node: Node, ctx: &mut RuleContext) {
+        self.inner.check(node, ctx)
+    }
+}
+
+/// A schema document to translate
+struct Source {
+    path: PathBuf,
+    text: String,
+    /// Target namespace taken over from the including schema, for an
+    /// included schema without its own
+    chameleon_ns: Option<String>,
+}
+
+/// Read the schema files and everything they include or import
+fn load_sources(paths: &[PathBuf]) -> Result<Vec<Source>> {
+    let mut sources: Vec<Source> = Vec::new();
+    let mut seen: Vec<(PathBuf, Option<String>)> = Vec::new();
+    let mut pending: Vec<(PathBuf, Option<String>)> =
+        paths.iter().rev().map(|p| (p.clone(), None)).collect();
+
+    while let Some((path, chameleon_ns)) = pending.pop() {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read XML Schema: {}", path.display()))?;
+        if seen.contains(&(canonical.clone(), chameleon_ns.clone())) {
+            continue;
+        }
+        seen.push((canonical, chameleon_ns.clone()));
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read XML Schema: {}", path.display()))?;
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(&text, options)
+            .with_context(|| format!("Failed to parse XML Schema: {}", path.display()))?;
+        let root = doc.root_element();
+        if !is_xs(root, "schema") {
+            anyhow::bail!(
+                "{} is not an XML Schema (root <{}> is not xs:schema)",
+                path.display(),
+                root.tag_name().name()
+            );
+        }
+        let target = root
+            .attribute("targetNamespace")
+            .map(str::to_string)
+            .or_else(|| chameleon_ns.clone());
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for child in root.children().filter(|c| c.is_element()) {
+            let Some(location) = child.attribute("schemaLocation") else {
+                continue;
+            };
+            let included_ns = match child.tag_name().name() {
+                "include" | "redefine" | "override" => target.clone(),
+                "import" => None,
+                _ => continue,
+            };
+            if child.tag_name().namespace() != Some(XS_NS) {
+                continue;
+            }
+            let included = local_path(base, location)
+                .map_err(|e| anyhow::anyhow!("{} in {}", e, path.display()))?;
+            pending.push((included, included_ns));
+        }
+
+        // Only a schema without its own target namespace is a chameleon
+        let chameleon_ns = match root.attribute("targetNamespace") {
+            Some(_) => None,
+            None => chameleon_ns,
+        };
+        drop(doc);
+        sources.push(Source {
+            path,
+            text,
+            chameleon_ns,
+        });
+    }
+    Ok(sources)
+}
+
+fn is_xs(node: Node, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().namespace() == Some(XS_NS)
+        && node.tag_name().name() == name
+}
+
+/// Element children in the XML Schema namespace, without annotations
+fn xs_children<'a, 'i>(node: Node<'a, 'i>) -> impl Iterator<Item = Node<'a, 'i>> {
+    node.children().filter(|c| {
+        c.is_element()
+            && c.tag_name().namespace() == Some(XS_NS)
+            && c.tag_name().name() != "annotation"
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Kind {
+    Element,
+    ComplexType,
+    SimpleType,
+    Group,
+    AttributeGroup,
+    Attribute,
+}
+
+impl Kind {
+    fn from_tag(name: &str) -> Option<Self> {
+        Some(match name {
+            "element" => Kind::Element,
+            "complexType" => Kind::ComplexType,
+            "simpleType" => Kind::SimpleType,
+            "group" => Kind::Group,
+            "attributeGroup" => Kind::AttributeGroup,
+            "attribute" => Kind::Attribute,
+            _ => return None,
+        })
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Element => "element",
+            Kind::ComplexType | Kind::SimpleType => "type",
+            Kind::Group => "group",
+            Kind::AttributeGroup => "attribute group",
+            Kind::Attribute => "attribute",
+        }
+    }
+
+    /// Prefix of the define holding a component of this kind
+    fn prefix(self) -> &'static str {
+        match self {
+            Kind::Element => "e",
+            Kind::ComplexType => "ct",
+            Kind::SimpleType => "st",
+            Kind::Group => "g",
+            Kind::AttributeGroup => "ag",
+            Kind::Attribute => "a",
+        }
+    }
+}
+
+type QName = (String, String);
+
+fn define_name(kind: Kind, (ns, local): &QName) -> String {
+    format!("{}:{{{}}}{}", kind.prefix(), ns, local)
+}
+
+/// Elements that may appear where the global element is referenced: itself
+/// and the members of its substitution group
+fn substitutes_name(qname: &QName) -> String {
+    format!("sg:{{{}}}{}", qname.0, qname.1)
+}
+
+/// Target namespace and defaults of a schema document
+struct SchemaInfo {
+    file: String,
+    target: String,
+    chameleon: bool,
+    element_qualified: bool,
+    attribute_qualified: bool,
+}
+
+/// A global component and the schema document declaring it
+type Global<'a, 'i> = (Node<'a, 'i>, usize);
+
+struct Translator<'a, 'i> {
+    infos: Vec<SchemaInfo>,
+    globals: HashMap<(Kind, QName), Global<'a, 'i>>,
+    /// Global elements in declaration order
+    elements: Vec<QName>,
+    /// Substitution group heads and their direct members
+    substitutions: HashMap<QName, Vec<QName>>,
+}
+
+impl<'a, 'i> Translator<'a, 'i> {
+    fn new(sources: &[Source], docs: &'a [Document<'i>]) -> Result<Self> {
+        let mut translator = Self {
+            infos: Vec::new(),
+            globals: HashMap::new(),
+            elements: Vec::new(),
+            substitutions: HashMap::new(),
+        };
+        for (source, doc) in sources.iter().zip(docs) {
+            let root = doc.root_element();
+            let s = translator.infos.len();
+            translator.infos.push(SchemaInfo {
+                file: source.path.display().to_string(),
+                target: root
+                    .attribute("targetNamespace")
+                    .or(source.chameleon_ns.as_deref())
+                    .unwrap_or_default()
+                    .to_string(),
+                chameleon: source.chameleon_ns.is_some(),
+                element_qualified: root.attribute("elementFormDefault") == Some("qualified"),
+                attribute_qualified: root.attribute("attributeFormDefault") == Some("qualified"),
+            });
+
+            for child in xs_children(root) {
+                let Some(kind) = Kind::from_tag(child.tag_name().name()) else {
+                    continue;
+                };
+                let name = child.attribute("name").with_context(|| {
+                    format!(
+                        "Global <xs:{}> without @name at {}",
+                        child.tag_name().name(),
+                        translator.location(child, s)
+                    )
+                })?;
+                let qname = (translator.infos[s].target.clone(), name.to_string());
+                if translator.globals.contains_key(&(kind, qname.clone())) {
+                    continue;
+                }
+                if kind == Kind::Element {
+                    translator.elements.push(qname.clone());
+                    if let Some(heads) = child.attribute("substitutionGroup") {
+                        for head in heads.split_whitespace() {
+                            let head = translator.qname(child, head, s)?;
+                            translator
+                                .substitutions
+                                .entry(head)
+                                .or_default()
+                                .push(qname.clone());
+                        }
+                    }
+                }
+                translator.globals.insert((kind, qname), (child, s));
+            }
+        }
+        Ok(translator)
+    }
+
+    fn location(&self, node: Node, s: usize) -> String {
+        let pos = node.document().text_pos_at(node.range().start);
+        format!("{}:{}", self.infos[s].file, pos.row)
+    }
+
+    /// Resolve a QName in an attribute value
+    fn qname(&self, node: Node, value: &str, s: usize) -> Result<QName> {
+        let (ns, local) = match value.split_once(':') {
+            Some((prefix, local)) => {
+                let ns = node.lookup_namespace_uri(Some(prefix)).with_context(|| {
+                    format!(
+                        "Undeclared namespace prefix in {} at {}",
+                        value,
+                        self.location(node, s)
+                    )
+                })?;
+                (ns.to_string(), local)
+            }
+            None => {
+                let ns = node.lookup_namespace_uri(None).unwrap_or_default();
+                let info = &self.infos[s];
+                if ns.is_empty() && info.chameleon {
+                    (info.target.clone(), value)
+                } else {
+                    (ns.to_string(), value)
+                }
+            }
+        };
+        Ok((ns, local.to_string()))
+    }
+
+    /// A reference to a global component named by `attr`
+    fn reference(&self, kind: Kind, node: Node, attr: &str, s: usize) -> Result<Elem> {
+        let value = node.attribute(attr).with_context(|| {
+            format!(
+                "<xs:{}> without @{} at {}",
+                node.tag_name().name(),
+                attr,
+                self.location(node, s)
+            )
+        })?;
+        let qname = self.qname(node, value, s)?;
+        if !self.globals.contains_key(&(kind, qname.clone())) {
+            anyhow::bail!(
+                "Undefined {} {} at {}",
+                kind.describe(),
+                value,
+                self.location(node, s)
+            );
+        }
+        let name = match kind {
+            Kind::Element => substitutes_name(&qname),
+            _ => define_name(kind, &qname),
+        };
+        Ok(Elem::new("ref").with_attr("name", &name))
+    }
+
+    /// The grammar: a define per global component, any global element as
+    /// the root
+    fn grammar(&self) -> Result<Elem> {
+        let start = Elem::new("choice").with_children(
+            self.elements
+                .iter()
+                .filter(|q| !self.is_abstract(q))
+                .map(|q| Elem::new("ref").with_attr("name", &define_name(Kind::Element, q))),
+        );
+        let mut grammar = Elem::new("grammar")
+            .with_child(Elem::new("start").with_child(start))
+            .with_child(define(ANY_TYPE, any_type()));
+
+        let mut globals: Vec<_> = self.globals.iter().collect();
+        globals.sort_by(|a, b| a.0.cmp(b.0));
+        for ((kind, qname), (node, s)) in globals {
+            let (node, s) = (*node, *s);
+            let pattern = match kind {
+                Kind::Element => {
+                    grammar = grammar
+                        .with_child(define(&substitutes_name(qname), self.substitutes(qname)));
+                    self.element(node, s, true)?
+                }
+                Kind::ComplexType => self.complex_type(node, s)?,
+                Kind::SimpleType => self.simple_type(node, s)?,
+                Kind::Group => {
+                    let particles = xs_children(node)
+                        .map(|c| self.particle(c, s))
+                        .collect::<Result<Vec<_>>>()?;
+                    group(particles.into_iter().flatten().collect())
+                }
+                Kind::AttributeGroup => self.content_model(node, s)?,
+                Kind::Attribute => self.attribute(node, s, true)?,
+            };
+            grammar = grammar.with_child(define(&define_name(*kind, qname), pattern));
+        }
+        Ok(grammar)
+    }
+
+    fn is_abstract(&self, qname: &QName) -> bool {
+        self.globals
+            .get(&(Kind::Element, qname.clone()))
+            .is_some_and(|(node, _)| node.attribute("abstract") == Some("true"))
+    }
+
+    fn substitutes(&self, qname: &QName) -> Elem {
+        let mut choices = Vec::new();
+        if !self.is_abstract(qname) {
+            choices.push(Elem::new("ref").with_attr("name", &define_name(Kind::Element, qname)));
+        }
+        for member in self.substitutions.get(qname).into_iter().flatten() {
+            choices.push(Elem::new("ref").with_attr("name", &substitutes_name(member)));
+        }
+        match choices.len() {
+            0 => Elem::new("notAllowed"),
+            1 => choices.pop().expect("one choice"),
+            _ => Elem::new("choice").with_children(choices),
+        }
+    }
+
+    /// A declared element (or a reference to a global one)
+    fn element(&self, node: Node, s: usize, global: bool) -> Result<Elem> {
+        if node.has_attribute("ref") {
+            return self.reference(Kind::Element, node, "ref", s);
+        }
+        let name = node.attribute("name").with_context(|| {
+            format!(
+                "<xs:element> without @name or @ref at {}",
+                self.location(node, s)
+            )
+        })?;
+        let qualified = global
+            || match node.attribute("form") {
+                Some(form) => form == "qualified",
+                None => self.infos[s].element_qualified,
+            };
+        let ns = if qualified {
+            self.infos[s].target.as_str()
+        } else {
+            ""
+        };
+
+        let mut content = self.element_type(node, s)?;
+        if node.attribute("nillable") == Some("true") {
+            content = Elem::new("choice")
+                .with_child(content)
+                .with_child(Elem::new("empty"));
+        }
+        Ok(Elem::new("element")
+            .with_name(Some(ns), name)
+            .with_child(xsi_attributes())
+            .with_child(content)
+            .at(&self.location(node, s)))
+    }
+
+    fn element_type(&self, node: Node, s: usize) -> Result<Elem> {
+        if let Some(type_name) = node.attribute("type") {
+            return self.type_ref(node, type_name, s);
+        }
+        for child in xs_children(node) {
+            match child.tag_name().name() {
+                "complexType" => return self.complex_type(child, s),
+                "simpleType" => return self.simple_type(child, s),
+                _ => {}
+            }
+        }
+        // Without a type, a member of a substitution group has the type of
+        // its head
+        if let Some(head) = node.attribute("substitutionGroup") {
+            let head = self.qname(node, head.split_whitespace().next().unwrap_or(head), s)?;
+            if let Some((head, hs)) = self.globals.get(&(Kind::Element, head)) {
+                return self.element_type(*head, *hs);
+            }
+        }
+        Ok(any_type_ref())
+    }
+
+    /// The pattern of a named type
+    fn type_ref(&self, node: Node, value: &str, s: usize) -> Result<Elem> {
+        let qname = self.qname(node, value, s)?;
+        if qname.0 == XS_NS {
+            return Ok(match qname.1.as_str() {
+                "anyType" => any_type_ref(),
+                "anySimpleType" => Elem::new("text"),
+                name => xsd_data(name).at(&self.location(node, s)),
+            });
+        }
+        for kind in [Kind::ComplexType, Kind::SimpleType] {
+            if self.globals.contains_key(&(kind, qname.clone())) {
+                return Ok(Elem::new("ref").with_attr("name", &define_name(kind, &qname)));
+            }
+        }
+        anyhow::bail!("Undefined type {} at {}", value, self.location(node, s))
+    }
+
+    fn complex_type(&self, node: Node, s: usize) -> Result<Elem> {
+        let mixed = node.attribute("mixed") == Some("true");
+        for child in xs_children(node) {
+            match child.tag_name().name() {
+                "simpleContent" => return self.simple_content(child, s),
+                "complexContent" => {
+                    let mixed = match child.attribute("mixed") {
+                        Some(value) => value == "true",
+                        None => mixed,
+                    };
+                    return self.complex_content(child, s, mixed);
+                }
+                _ => {}
+            }
+        }
+        let content = self.content_model(node, s)?;
+        Ok(with_mixed(content, mixed))
+    }
+
+    fn complex_content(&self, node: Node, s: usize, mixed: bool) -> Result<Elem> {
+        let derivation = xs_children(node).next().with_context(|| {
+            format!(
+                "<xs:complexContent> without a derivation at {}",
+                self.location(node, s)
+            )
+        })?;
+        let own = self.content_model(derivation, s)?;
+        let base = derivation.attribute("base").unwrap_or("xs:anyType");
+        let from_any_type =
+            self.qname(derivation, base, s)? == (XS_NS.to_string(), "anyType".to_string());
+        let content = match derivation.tag_name().name() {
+            "extension" if !from_any_type => group(vec![self.type_ref(derivation, base, s)?, own]),
+            // A restriction restates the content it keeps, and inherits the
+            // attribute uses it doesn't restate or prohibit
+            _ if !from_any_type => {
+                let base = self.qname(derivation, base, s)?;
+                let mut items = vec![own];
+                for (node, ns) in self.inherited_attributes(derivation, &base, &mut Vec::new())? {
+                    match node.tag_name().name() {
+                        "attributeGroup" => {
+                            items.push(self.reference(Kind::AttributeGroup, node, "ref", ns)?)
+                        }
+                        _ => items.extend(self.attribute_use(node, ns)?),
+                    }
+                }
+                group(items)
+            }
+            _ => own,
+        };
+        Ok(with_mixed(content, mixed))
+    }
+
+    /// The `attribute` and `attributeGroup` uses of the complex type `base`
+    /// (with those it inherits) that `derivation` doesn't declare itself;
+    /// `seen` guards against circular derivations
+    fn inherited_attributes(
+        &self,
+        derivation: Node<'a, 'i>,
+        base: &QName,
+        seen: &mut Vec<QName>,
+    ) -> Result<Vec<Global<'a, 'i>>> {
+        let Some((base_node, bs)) = self.globals.get(&(Kind::ComplexType, base.clone())) else {
+            return Ok(Vec::new());
+        };
+        if seen.contains(base) {
+            anyhow::bail!("Circular derivation of type {}:{}", base.0, base.1);
+        }
+        seen.push(base.clone());
+
+        // The attribute uses sit on the type, or on its derivation
+        let holder = xs_children(*base_node)
+            .find(|c| is_xs(*c, "complexContent") || is_xs(*c, "simpleContent"))
+            .and_then(|content| xs_children(content).next())
+            .unwrap_or(*base_node);
+        let mut uses: Vec<Global> = xs_children(holder)
+            .filter(|c| is_xs(*c, "attribute") || is_xs(*c, "attributeGroup"))
+            .map(|c| (c, *bs))
+            .collect();
+        if let Some(grand_base) = holder.attribute("base").filter(|_| holder != *base_node) {
+            let grand_base = self.qname(holder, grand_base, *bs)?;
+            uses.extend(self.inherited_attributes(holder, &grand_base, seen)?);
+        }
+
+        let key = |node: Node| {
+            let name = node.attribute("name").or(node.attribute("ref"))?;
+            Some((
+                node.tag_name().name().to_string(),
+                name.rsplit(':').next()?.to_string(),
+            ))
+        };
+        let own: Vec<_> = xs_children(derivation).filter_map(key).collect();
+        Ok(uses
+            .into_iter()
+            .filter(|(node, _)| key(*node).is_none_or(|k| !own.contains(&k)))
+            .collect())
+    }
+
+    fn simple_content(&self, node: Node, s: usize) -> Result<Elem> {
+        let derivation = xs_children(node).next().with_context(|| {
+            format!(
+                "<xs:simpleContent> without a derivation at {}",
+                self.location(node, s)
+            )
+        })?;
+        let value = match derivation.tag_name().name() {
+            "extension" => {
+                let base = derivation.attribute("base").with_context(|| {
+                    format!(
+                        "<xs:extension> without @base at {}",
+                        self.location(derivation, s)
+                    )
+                })?;
+                self.type_ref(derivation, base, s)?
+            }
+            _ => self.restriction(derivation, s)?,
+        };
+        Ok(group(vec![value, self.content_model(derivation, s)?]))
+    }
+
+    /// The particle and attribute uses of a complex type, derivation or
+    /// attribute group
+    fn content_model(&self, node: Node, s: usize) -> Result<Elem> {
+        let mut items = Vec::new();
+        for child in xs_children(node) {
+            match child.tag_name().name() {
+                "attribute" => items.extend(self.attribute_use(child, s)?),
+                "attributeGroup" => {
+                    items.push(self.reference(Kind::AttributeGroup, child, "ref", s)?)
+                }
+                "anyAttribute" => items.push(
+                    Elem::new("zeroOrMore").with_child(
+                        Elem::new("attribute")
+                            .with_child(self.wildcard(child, s))
+                            .with_child(Elem::new("text")),
+                    ),
+                ),
+                _ => items.extend(self.particle(child, s)?),
+            }
+        }
+        Ok(group(items))
+    }
+
+    /// A particle with its occurrence bounds; `None` for other children
+    fn particle(&self, node: Node, s: usize) -> Result<Option<Elem>> {
+        let children = || -> Result<Vec<Elem>> {
+            let particles = xs_children(node)
+                .map(|c| self.particle(c, s))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(particles.into_iter().flatten().collect())
+        };
+        let pattern = match node.tag_name().name() {
+            "element" => self.element(node, s, false)?,
+            "sequence" => group(children()?),
+            "choice" => match children()? {
+                items if items.is_empty() => Elem::new("notAllowed"),
+                mut items if items.len() == 1 => items.pop().expect("one item"),
+                items => Elem::new("choice").with_children(items),
+            },
+            "all" => match children()? {
+                items if items.is_empty() => Elem::new("empty"),
+                mut items if items.len() == 1 => items.pop().expect("one item"),
+                items => Elem::new("interleave").with_children(items),
+            },
+            "group" => self.reference(Kind::Group, node, "ref", s)?,
+            "any" => Elem::new("element")
+                .with_child(self.wildcard(node, s))
+                .with_child(any_type_ref()),
+            _ => return Ok(None),
+        };
+        let bound = |attr: &str| -> Result<Option<u64>> {
+            match node.attribute(attr) {
+                None => Ok(Some(1)),
+                Some("unbounded") => Ok(None),
+                Some(value) => value.trim().parse().map(Some).with_context(|| {
+                    format!(
+                        "Invalid @{} {:?} at {}",
+                        attr,
+                        value,
+                        self.location(node, s)
+                    )
+                }),
+            }
+        };
+        let min = bound("minOccurs")?.unwrap_or(1);
+        if min > MAX_OCCURS_EXPANDED {
+            anyhow::bail!(
+                "minOccurs > {} not supported at {}",
+                MAX_OCCURS_EXPANDED,
+                self.location(node, s)
+            );
+        }
+        Ok(Some(occurs(pattern, min, bound("maxOccurs")?)))
+    }
+
+    /// Name class of an `any` or `anyAttribute`
+    fn wildcard(&self, node: Node, s: usize) -> Elem {
+        let target = self.infos[s].target.as_str();
+        let ns_name = |ns: &str| Elem::new("nsName").with_ns(ns);
+        let namespaces = node.attribute("namespace").unwrap_or("##any");
+        match namespaces.trim() {
+            "##any" => Elem::new("anyName"),
+            "##other" => Elem::new("anyName").with_child(
+                Elem::new("except")
+                    .with_child(ns_name(target))
+                    .with_child(ns_name("")),
+            ),
+            list => Elem::new("choice").with_children(list.split_whitespace().map(|ns| match ns {
+                "##targetNamespace" => ns_name(target),
+                "##local" => ns_name(""),
+                ns => ns_name(ns),
+            })),
+        }
+    }
+
+    fn attribute_use(&self, node: Node, s: usize) -> Result<Option<Elem>> {
+        let attribute = match node.attribute("use") {
+            Some("prohibited") => return Ok(None),
+            _ if node.has_attribute("ref") => self.reference(Kind::Attribute, node, "ref", s)?,
+            _ => self.attribute(node, s, false)?,
+        };
+        Ok(Some(match node.attribute("use") {
+            Some("required") => attribute,
+            _ => Elem::new("optional").with_child(attribute),
+        }))
+    }
+
+    /// A declared attribute
+    fn attribute(&self, node: Node, s: usize, global: bool) -> Result<Elem> {
+        let name = node.attribute("name").with_context(|| {
+            format!(
+                "<xs:attribute> without @name or @ref at {}",
+                self.location(node, s)
+            )
+        })?;
+        let qualified = global
+            || match node.attribute("form") {
+                Some(form) => form == "qualified",
+                None => self.infos[s].attribute_qualified,
+            };
+        let ns = if qualified {
+            self.infos[s].target.as_str()
+        } else {
+            ""
+        };
+
+        let value = if let Some(fixed) = node.attribute("fixed") {
+            let base = match node.attribute("type") {
+                Some(type_name) => self.builtin_base(node, type_name, s)?,
+                None => "string".to_string(),
+            };
+            Elem::new("value")
+                .with_attr("type", &base)
+                .with_datatype_library(XSD_LIBRARY)
+                .with_text(fixed)
+                .at(&self.location(node, s))
+        } else if let Some(type_name) = node.attribute("type") {
+            self.type_ref(node, type_name, s)?
+        } else if let Some(simple_type) = xs_children(node).find(|c| is_xs(*c, "simpleType")) {
+            self.simple_type(simple_type, s)?
+        } else {
+            Elem::new("text")
+        };
+        Ok(Elem::new("attribute")
+            .with_name(Some(ns), name)
+            .with_child(value))
+    }
+
+    fn simple_type(&self, node: Node, s: usize) -> Result<Elem> {
+        let derivation = xs_children(node).next().with_context(|| {
+            format!(
+                "<xs:simpleType> without a derivation at {}",
+                self.location(node, s)
+            )
+        })?;
+        match derivation.tag_name().name() {
+            "restriction" => self.restriction(derivation, s),
+            "list" => {
+                let item = match derivation.attribute("itemType") {
+                    Some(item) => self.type_ref(derivation, item, s)?,
+                    None => self.inline_simple_type(derivation, s)?,
+                };
+                Ok(Elem::new("list").with_child(Elem::new("zeroOrMore").with_child(item)))
+            }
+            "union" => {
+                let mut members = Vec::new();
+                for member in derivation
+                    .attribute("memberTypes")
+                    .unwrap_or("")
+                    .split_whitespace()
+                {
+                    members.push(self.type_ref(derivation, member, s)?);
+                }
+                for member in xs_children(derivation).filter(|c| is_xs(*c, "simpleType")) {
+                    members.push(self.simple_type(member, s)?);
+                }
+                Ok(Elem::new("choice").with_children(members))
+            }
+            other => anyhow::bail!(
+                "Unexpected <xs:{}> in <xs:simpleType> at {}",
+                other,
+                self.location(derivation, s)
+            ),
+        }
+    }
+
+    fn inline_simple_type(&self, node: Node, s: usize) -> Result<Elem> {
+        let simple_type = xs_children(node)
+            .find(|c| is_xs(*c, "simpleType"))
+            .with_context(|| {
+                format!(
+                    "<xs:{}> without a type at {}",
+                    node.tag_name().name(),
+                    self.location(node, s)
+                )
+            })?;
+        self.simple_type(simple_type, s)
+    }
+
+    /// A restriction, with the facets of its whole derivation chain applied
+    /// to the built-in type at its root. Facets on list or union types are
+    /// not checked, apart from enumerations.
+    fn restriction(&self, node: Node, s: usize) -> Result<Elem> {
+        let mut params: Vec<(String, String)> = Vec::new();
+        let mut values: Option<Vec<String>> = None;
+        let mut current = Some((node, s));
+        let mut builtin = None;
+        let mut base_pattern = None;
+
+        while let Some((restriction, rs)) = current.take() {
+            let mut patterns = Vec::new();
+            let mut enumeration = Vec::new();
+            for facet in xs_children(restriction) {
+                let value = facet.attribute("value").unwrap_or_default();
+                match facet.tag_name().name() {
+                    "enumeration" => enumeration.push(value.to_string()),
+                    "pattern" => patterns.push(format!("(?:{})", value)),
+                    "simpleType" | "attribute" | "attributeGroup" | "anyAttribute"
+                    | "whiteSpace" => {}
+                    // Facets of the derived type override those of its base
+                    name => {
+                        if !params.iter().any(|(p, _)| p == name) {
+                            params.push((name.to_string(), value.to_string()));
+                        }
+                    }
+                }
+            }
+            // Patterns of one type are alternatives; of a derivation chain,
+            // all apply
+            if !patterns.is_empty() {
+                params.push(("pattern".to_string(), patterns.join("|")));
+            }
+            if values.is_none() && !enumeration.is_empty() {
+                values = Some(enumeration);
+            }
+
+            let base = match restriction.attribute("base") {
+                Some(base) => self.qname(restriction, base, rs)?,
+                None => {
+                    let inline = xs_children(restriction)
+                        .find(|c| is_xs(*c, "simpleType"))
+                        .with_context(|| {
+                            format!(
+                                "<xs:restriction> without a base at {}",
+                                self.location(restriction, rs)
+                            )
+                        })?;
+                    match xs_children(inline).next() {
+                        Some(derivation) if is_xs(derivation, "restriction") => {
+                            current = Some((derivation, rs))
+                        }
+                        _ => base_pattern = Some(self.simple_type(inline, rs)?),
+                    }
+                    continue;
+                }
+            };
+            if base.0 == XS_NS {
+                builtin = Some(match base.1.as_str() {
+                    "anySimpleType" | "anyType" => "string".to_string(),
+                    name => name.to_string(),
+                });
+            } else if let Some((st, ss)) = self.globals.get(&(Kind::SimpleType, base.clone())) {
+                match xs_children(*st).next() {
+                    Some(derivation) if is_xs(derivation, "restriction") => {
+                        current = Some((derivation, *ss))
+                    }
+                    _ => {
+                        base_pattern = Some(
+                            Elem::new("ref")
+                                .with_attr("name", &define_name(Kind::SimpleType, &base)),
+                        )
+                    }
+                }
+            } else if self
+                .globals
+                .contains_key(&(Kind::ComplexType, base.clone()))
+            {
+                // Restricting the simple content of a complex type
+                base_pattern = Some(Elem::new("text"));
+            } else {
+                anyhow::bail!(
+                    "Undefined type {}:{} at {}",
+                    base.0,
+                    base.1,
+                    self.location(restriction, rs)
+                );
+            }
+        }
+
+        let location = self.location(node, s);
+        Ok(match (builtin, values) {
+            (builtin, Some(values)) => {
+                let (library, name) = match &builtin {
+                    Some(name) => (XSD_LIBRARY, name.as_str()),
+                    None => ("", "token"),
+                };
+                Elem::new("choice").with_children(values.iter().map(|v| {
+                    Elem::new("value")
+                        .with_attr("type", name)
+                        .with_datatype_library(library)
+                        .with_text(v)
+                        .at(&location)
+                }))
+            }
+            (Some(builtin), None) => xsd_data(&builtin)
+                .with_children(params.iter().map(|(name, value)| {
+                    Elem::new("param").with_attr("name", name).with_text(value)
+                }))
+                .at(&location),
+            (None, None) => base_pattern.unwrap_or_else(|| Elem::new("text")),
+        })
+    }
+
+    /// The built-in type a named simple type derives from
+    fn builtin_base(&self, node: Node, value: &str, s: usize) -> Result<String> {
+        let mut qname = self.qname(node, value, s)?;
+        for _ in 0..64 {
+            if qname.0 == XS_NS {
+                return Ok(qname.1);
+            }
+            let next = self
+                .globals
+                .get(&(Kind::SimpleType, qname.clone()))
+                .and_then(|(st, ss)| {
+                    let base = xs_children(*st).next()?.attribute("base")?;
+                    Some(self.qname(*st, base, *ss))
+                });
+            match next {
+                Some(base) => qname = base?,
+                None => break,
+            }
+        }
+        Ok("string".to_string())
+    }
+}
+
+fn define(name: &str, pattern: Elem) -> Elem {
+    Elem::new("define")
+        .with_attr("name", name)
+        .with_child(pattern)
+}
+
+fn xsd_data(name: &str) -> Elem {
+    Elem::new("data")
+        .with_attr("type", name)
+        .with_datatype_library(XSD_LIBRARY)
+}
+
+fn any_type_ref() -> Elem {
+    Elem::new("ref").with_attr("name", ANY_TYPE)
+}
+
+/// Any attributes, text and elements, recursively
+fn any_type() -> Elem {
+    Elem::new("mixed").with_child(
+        Elem::new("zeroOrMore").with_child(
+            Elem::new("choice")
+                .with_child(
+                    Elem::new("attribute")
+                        .with_child(Elem::new("anyName"))
+                        .with_child(Elem::new("text")),
+                )
+                .with_child(
+                    Elem::new("element")
+                        .with_child(Elem::new("anyName"))
+                        .with_child(any_type_ref()),
+                ),
+        ),
+    )
+}
+
+/// `xsi:type`, `xsi:nil` and the schema location hints
+fn xsi_attributes() -> Elem {
+    Elem::new("zeroOrMore").with_child(
+        Elem::new("attribute")
+            .with_child(Elem::new("nsName").with_ns(XSI_NS))
+            .with_child(Elem::new("text")),
+    )
+}
+
+fn with_mixed(content: Elem, mixed: bool) -> Elem {
+    if mixed {
+        Elem::new("mixed").with_child(content)
+    } else {
+        content
+    }
+}
+
+fn group(mut items: Vec<Elem>) -> Elem {
+    items.retain(|e| e.name != "empty");
+    match items.len() {
+        0 => Elem::new("empty"),
+        1 => items.pop().expect("one item"),
+        _ => Elem::new("group").with_children(items),
+    }
+}
+
+/// `pattern` repeated `min` to `max` (`None`: unbounded) times
+fn occurs(pattern: Elem, min: u64, max: Option<u64>) -> Elem {
+    let max = max.filter(|m| *m <= MAX_OCCURS_EXPANDED);
+    match (min, max) {
+        (_, Some(0)) => Elem::new("empty"),
+        (1, Some(1)) => pattern,
+        (0, Some(1)) => Elem::new("optional").with_child(pattern),
+        (0, None) => Elem::new("zeroOrMore").with_child(pattern),
+        (1, None) => Elem::new("oneOrMore").with_child(pattern),
+        (min, None) => {
+            let mut items = vec![pattern.clone(); min as usize - 1];
+            items.push(Elem::new("oneOrMore").with_child(pattern));
+            group(items)
+        }
+        (min, Some(max)) => {
+            let mut items = vec![pattern.clone(); min as usize];
+            let optional = Elem::new("optional").with_child(pattern);
+            items.extend(std::iter::repeat_n(
+                optional,
+                max.saturating_sub(min) as usize,
+            ));
+            group(items)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn messages(schema: &XmlSchema, xml: &str) -> Vec<(usize, String)> {
+        let doc = Document::parse(xml).unwrap();
+        schema
+            .validate_document(&doc)
+            .into_iter()
+            .map(|m| (m.line, m.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_types_groups_and_facets() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("common.xsd"),
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:simpleType name="year">
+    <xs:restriction base="xs:integer">
+      <xs:minInclusive value="1400"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:simpleType name="earlyYear">
+    <xs:restriction base="year">
+      <xs:maxInclusive value="1699"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:attributeGroup name="dating">
+    <xs:attribute name="when" type="earlyYear"/>
+  </xs:attributeGroup>
+</xs:schema>"#,
+        )
+        .unwrap();
+        let path = dir.path().join("letters.xsd");
+        fs::write(
+            &path,
+            r###"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+           xmlns:l="urn:letters" targetNamespace="urn:letters"
+           elementFormDefault="qualified">
+  <xs:include schemaLocation="common.xsd"/>
+  <xs:element name="letters">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element ref="l:letter" maxOccurs="unbounded"/>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="letter" type="l:letterType"/>
+  <xs:element name="postcard" substitutionGroup="l:letter"/>
+  <xs:complexType name="base">
+    <xs:sequence>
+      <xs:element name="from" type="xs:string"/>
+    </xs:sequence>
+    <xs:attribute name="id" type="xs:ID" use="required"/>
+  </xs:complexType>
+  <xs:complexType name="letterType">
+    <xs:complexContent>
+      <xs:extension base="l:base">
+        <xs:sequence>
+          <xs:element name="p" type="l:para" minOccurs="1" maxOccurs="2"/>
+        </xs:sequence>
+        <xs:attributeGroup ref="l:dating"/>
+        <xs:attribute name="status">
+          <xs:simpleType>
+            <xs:restriction base="xs:token">
+              <xs:enumeration value="draft"/>
+              <xs:enumeration value="sent"/>
+            </xs:restriction>
+          </xs:simpleType>
+        </xs:attribute>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+  <xs:complexType name="para" mixed="true">
+    <xs:choice minOccurs="0" maxOccurs="unbounded">
+      <xs:element name="hi" type="xs:string"/>
+      <xs:any namespace="##other" processContents="lax"/>
+    </xs:choice>
+  </xs:complexType>
+</xs:schema>"###,
+        )
+        .unwrap();
+        let schema = XmlSchema::from_file(&path).unwrap();
+        assert_eq!(schema.id(), "xsd:letters");
+
+        let ok = r#"<letters xmlns="urn:letters" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+    xsi:schemaLocation="urn:letters letters.xsd">
+<letter id="a1" when="1620" status="sent"><from>Ann</from><p>Dear <hi>Sir</hi> <x:b xmlns:x="urn:x" y="1">!</x:b></p></letter>
+<postcard id="a2"><from>Ben</from><p/><p/></postcard>
+</letters>"#;
+        assert_eq!(messages(&schema, ok), vec![]);
+
+        let bad = r#"<letters xmlns="urn:letters">
+<letter when="1720" status="lost"><p>x</p></letter>
+<postcard id="b"><from>Ben</from><p/><p/><p/></postcard>
+<note/>
+</letters>"#;
+        assert_eq!(
+            messages(&schema, bad),
+            vec![
+                (
+                    2,
+                    r#"Invalid value "1720" for @when on <letter>"#.to_string()
+                ),
+                (
+                    2,
+                    r#"Invalid value "lost" for @status on <letter>"#.to_string()
+                ),
+                (
+                    2,
+                    "Element <letter> is missing required attribute @id".to_string()
+                ),
+                (
+                    2,
+                    "Element <p> not allowed in <letter>; expected <from>".to_string()
+                ),
+                (
+                    2,
+                    "Element <letter> is incomplete; expected <from>".to_string()
+                ),
+                (3, "Element <p> not allowed in <postcard>".to_string()),
+                (
+                    4,
+                    "Element <note> not allowed in <letters>; expected <letter> or <postcard>"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unqualified_locals_and_occurrence_bounds() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("list.xsd");
+        fs::write(
+            &path,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" targetNamespace="urn:list">
+  <xs:element name="list">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="item" minOccurs="2" maxOccurs="3">
+          <xs:simpleType>
+            <xs:list itemType="xs:integer"/>
+          </xs:simpleType>
+        </xs:element>
+      </xs:sequence>
+      <xs:attribute name="n" fixed="1" type="xs:integer"/>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        )
+        .unwrap();
+        let schema = XmlSchema::from_file(&path).unwrap();
+
+        let ok = r#"<l:list xmlns:l="urn:list" n="01"><item>1 2</item><item/></l:list>"#;
+        assert_eq!(messages(&schema, ok), vec![]);
+
+        let bad = r#"<l:list xmlns:l="urn:list" n="2"><item>a</item></l:list>"#;
+        assert_eq!(
+            messages(&schema, bad),
+            vec![
+                (1, r#"Invalid value "2" for @n on <list>"#.to_string()),
+                (1, r#"Invalid value "a" in <item>"#.to_string()),
+                (
+                    1,
+                    "Element <list> is incomplete; expected <item>".to_string()
+                ),
+            ]
+        );
+
+        fs::write(
+            &path,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:import namespace="urn:tei" schemaLocation="http://example.org/tei.xsd"/>
+</xs:schema>"#,
+        )
+        .unwrap();
+        let err = XmlSchema::from_file(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Only local schema files are supported"));
+
+        // A minimum that isn't expanded would accept too few items
+        fs::write(
+            &path,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="list">
+    <xs:complexType>
+      <xs:sequence><xs:element name="item" minOccurs="50" maxOccurs="unbounded"/></xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        )
+        .unwrap();
+        let err = XmlSchema::from_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("minOccurs > 32 not supported"));
+    }
+
+    fn load(dir: &TempDir, schema: &str) -> XmlSchema {
+        let path = dir.path().join("test.xsd");
+        fs::write(&path, schema).unwrap();
+        XmlSchema::from_file(&path).unwrap()
+    }
+
+    #[test]
+    fn test_substitution_groups() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(
+            &dir,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="body">
+    <xs:complexType>
+      <xs:sequence><xs:element ref="block" maxOccurs="unbounded"/></xs:sequence>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="block" abstract="true"/>
+  <xs:element name="p" type="xs:string" substitutionGroup="block"/>
+  <xs:element name="list" substitutionGroup="block">
+    <xs:complexType>
+      <xs:sequence><xs:element ref="item" maxOccurs="unbounded"/></xs:sequence>
+    </xs:complexType>
+  </xs:element>
+  <xs:element name="numbered" substitutionGroup="list"/>
+  <xs:element name="item" type="xs:string"/>
+</xs:schema>"#,
+        );
+
+        let ok = "<body><p>x</p><list><item/></list><numbered><item/></numbered></body>";
+        assert_eq!(messages(&schema, ok), vec![]);
+
+        // The abstract head can't appear itself
+        let bad = "<body><block/><item/></body>";
+        let expected = "expected <list>, <numbered> or <p>";
+        assert_eq!(
+            messages(&schema, bad),
+            vec![
+                (
+                    1,
+                    format!("Element <block> not allowed in <body>; {}", expected)
+                ),
+                (
+                    1,
+                    format!("Element <item> not allowed in <body>; {}", expected)
+                ),
+                (1, format!("Element <body> is incomplete; {}", expected)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_and_union() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(
+            &dir,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:simpleType name="unknown">
+    <xs:restriction base="xs:token"><xs:enumeration value="unknown"/></xs:restriction>
+  </xs:simpleType>
+  <xs:simpleType name="yearOrUnknown">
+    <xs:union memberTypes="xs:gYear unknown"/>
+  </xs:simpleType>
+  <xs:simpleType name="years">
+    <xs:list itemType="yearOrUnknown"/>
+  </xs:simpleType>
+  <xs:element name="date">
+    <xs:complexType>
+      <xs:simpleContent>
+        <xs:extension base="years">
+          <xs:attribute name="cert">
+            <xs:simpleType>
+              <xs:union>
+                <xs:simpleType>
+                  <xs:restriction base="xs:decimal">
+                    <xs:minInclusive value="0"/><xs:maxInclusive value="1"/>
+                  </xs:restriction>
+                </xs:simpleType>
+                <xs:simpleType>
+                  <xs:restriction base="xs:token">
+                    <xs:enumeration value="high"/><xs:enumeration value="low"/>
+                  </xs:restriction>
+                </xs:simpleType>
+              </xs:union>
+            </xs:simpleType>
+          </xs:attribute>
+        </xs:extension>
+      </xs:simpleContent>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        );
+
+        assert_eq!(
+            messages(&schema, r#"<date cert="0.5"> 1620 unknown 1621 </date>"#),
+            vec![]
+        );
+        assert_eq!(messages(&schema, r#"<date cert="low"/>"#), vec![]);
+        assert_eq!(
+            messages(&schema, r#"<date cert="2">1620 soon</date>"#),
+            vec![
+                (1, r#"Invalid value "2" for @cert on <date>"#.to_string()),
+                (1, r#"Invalid value "1620 soon" in <date>"#.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_group() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(
+            &dir,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="person">
+    <xs:complexType>
+      <xs:all>
+        <xs:element name="name" type="xs:string"/>
+        <xs:element name="birth" type="xs:string"/>
+        <xs:element name="death" type="xs:string" minOccurs="0"/>
+      </xs:all>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        );
+
+        assert_eq!(
+            messages(&schema, "<person><birth/><name/></person>"),
+            vec![]
+        );
+        assert_eq!(
+            messages(&schema, "<person><death/><name/><birth/></person>"),
+            vec![]
+        );
+        assert_eq!(
+            messages(&schema, "<person><name/><name/><death/></person>"),
+            vec![
+                (
+                    1,
+                    "Element <name> not allowed in <person>; expected <birth> or <death>"
+                        .to_string()
+                ),
+                (
+                    1,
+                    "Element <person> is incomplete; expected <birth>".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complex_content_restriction() {
+        let dir = TempDir::new().unwrap();
+        let schema = load(
+            &dir,
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:complexType name="name">
+    <xs:sequence>
+      <xs:element name="forename" type="xs:string" minOccurs="0" maxOccurs="unbounded"/>
+      <xs:element name="surname" type="xs:string"/>
+    </xs:sequence>
+    <xs:attribute name="type" type="xs:string"/>
+    <xs:attribute name="ref" type="xs:anyURI"/>
+  </xs:complexType>
+  <xs:complexType name="surnameOnly">
+    <xs:complexContent>
+      <xs:restriction base="name">
+        <xs:sequence>
+          <xs:element name="surname" type="xs:string"/>
+        </xs:sequence>
+        <xs:attribute name="ref" use="prohibited"/>
+      </xs:restriction>
+    </xs:complexContent>
+  </xs:complexType>
+  <xs:element name="names">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="persName" type="name"/>
+        <xs:element name="family" type="surnameOnly"/>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        );
+
+        let ok = r#"<names><persName ref="a.xml"><forename/><surname/></persName><family type="x"><surname/></family></names>"#;
+        assert_eq!(messages(&schema, ok), vec![]);
+
+        // Content is restated; attributes are inherited unless prohibited
+        let bad = r#"<names><persName><surname/></persName><family ref="a.xml"><forename/><surname/></family></names>"#;
+        assert_eq!(
+            messages(&schema, bad),
+            vec![
+                (1, "Attribute @ref not allowed on <family>".to_string()),
+                (
+                    1,
+                    "Element <forename> not allowed in <family>; expected <surname>".to_string()
+                ),
+            ]
+        );
+    }
+}
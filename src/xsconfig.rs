@@ -1,3 +1,4 @@
+use crate::validation::{PbSequenceConfig, RuleConfig};
 use anyhow::Result;
 use config::{Config as ConfigLoader, File};
 use serde::{Deserialize, Serialize};
@@ -8,6 +9,14 @@ pub struct XsConfig {
     pub processing: ProcessingConfig,
     pub output: OutputConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub examples: ExamplesConfig,
+    /// Declarative validation rules (`[[rules]]` tables)
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Numbering schemes of the page-break sequence check
+    #[serde(default)]
+    pub pb_sequence: PbSequenceConfig,
 }
 
 impl XsConfig {
@@ -20,26 +29,6 @@ impl XsConfig {
         Ok(settings.try_deserialize()?)
     }
 
-    /// Create default configuration
-    pub fn default() -> Self {
-        Self {
-            processing: ProcessingConfig {
-                num_threads: 0,
-                max_depth: 0,
-                file_extensions: vec!["xml".to_string(), "tei".to_string()],
-            },
-            output: OutputConfig {
-                output_file: "xml_structures.json".to_string(),
-                pretty_print: true,
-                include_paths: true,
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                log_file: None,
-            },
-        }
-    }
-
     /// Get log file path if specified
     pub fn log_file_path(&self) -> Option<PathBuf> {
         self.logging.log_file.as_ref().map(PathBuf::from)
@@ -64,6 +53,34 @@ impl XsConfig {
     }
 }
 
+impl Default for XsConfig {
+    /// Create default configuration
+    fn default() -> Self {
+        Self {
+            processing: ProcessingConfig {
+                num_threads: 0,
+                max_depth: 0,
+                file_extensions: vec!["xml".to_string(), "tei".to_string()],
+            },
+            output: OutputConfig {
+                output_file: "xml_structures.json".to_string(),
+                pretty_print: true,
+                include_paths: true,
+                format: OutputFormat::default(),
+                html_report: None,
+                dot_graph: None,
+            },
+            logging: LoggingConfig {
+                level: "info".to_string(),
+                log_file: None,
+            },
+            examples: ExamplesConfig::default(),
+            rules: Vec::new(),
+            pb_sequence: PbSequenceConfig::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingConfig {
     pub num_threads: usize,
@@ -76,6 +93,41 @@ pub struct OutputConfig {
     pub output_file: String,
     pub pretty_print: bool,
     pub include_paths: bool,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Optional self-contained HTML report written alongside the results
+    #[serde(default)]
+    pub html_report: Option<String>,
+    /// Optional Graphviz DOT graph of groups linked by similarity
+    #[serde(default)]
+    pub dot_graph: Option<String>,
+}
+
+/// Output format for grouping results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// One JSON document with all groups
+    #[default]
+    Json,
+    /// One JSON record per file
+    Ndjson,
+    /// One row per file, plus a separate groups CSV
+    Csv,
+    /// SQLite database with files, groups, paths and attribute keys
+    Sqlite,
+}
+
+impl OutputFormat {
+    /// File extension for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Sqlite => "sqlite",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +136,44 @@ pub struct LoggingConfig {
     pub log_file: Option<String>,
 }
 
+/// How representative example files are chosen for each structure group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExamplesConfig {
+    /// Number of examples kept per group (0 = none)
+    pub count: usize,
+    pub policy: ExamplePolicy,
+    /// Seed for the `random` policy
+    pub seed: u64,
+    /// Store example paths only, without their full structures
+    pub paths_only: bool,
+}
+
+impl Default for ExamplesConfig {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            policy: ExamplePolicy::Lexicographic,
+            seed: 0,
+            paths_only: false,
+        }
+    }
+}
+
+/// Deterministic policy for picking examples, independent of processing order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExamplePolicy {
+    /// Smallest files first
+    Smallest,
+    /// Largest files first
+    Largest,
+    /// Lexicographically first paths
+    Lexicographic,
+    /// Seeded random sample
+    Random,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,7 +182,7 @@ mod tests {
     fn test_default_config() {
         let config = XsConfig::default();
         assert_eq!(config.processing.file_extensions, vec!["xml", "tei"]);
-        assert_eq!(config.output.pretty_print, true);
+        assert!(config.output.pretty_print);
         assert_eq!(config.logging.level, "info");
     }
 
@@ -111,4 +201,76 @@ mod tests {
         let path = config.output_file_path();
         assert_eq!(path, PathBuf::from("xml_structures.json"));
     }
+
+    #[test]
+    fn test_config_without_examples_section() {
+        let toml = r#"
+            [processing]
+            num_threads = 0
+            max_depth = 0
+            file_extensions = ["xml"]
+
+            [output]
+            output_file = "out.json"
+            pretty_print = true
+            include_paths = true
+
+            [logging]
+            level = "info"
+        "#;
+        let config: XsConfig = ConfigLoader::builder()
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(config.examples.count, 1);
+        assert_eq!(config.examples.policy, ExamplePolicy::Lexicographic);
+        assert!(config.rules.is_empty());
+        assert_eq!(config.pb_sequence, PbSequenceConfig::default());
+    }
+
+    #[test]
+    fn test_config_with_rules() {
+        let toml = r#"
+            [processing]
+            num_threads = 0
+            max_depth = 0
+            file_extensions = ["xml"]
+
+            [output]
+            output_file = "out.json"
+            pretty_print = true
+            include_paths = true
+
+            [logging]
+            level = "info"
+
+            [[rules]]
+            element = "pb"
+            severity = "error"
+            required_attributes = ["facs"]
+            attribute_patterns = { n = "[0-9]+" }
+
+            [[rules]]
+            element = "head"
+            child_counts = [{ max = 0 }]
+        "#;
+        let config: XsConfig = ConfigLoader::builder()
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].severity, crate::validation::Severity::Error);
+        assert_eq!(config.rules[0].attribute_patterns["n"], "[0-9]+");
+        assert_eq!(
+            config.rules[1].severity,
+            crate::validation::Severity::Warning
+        );
+        assert_eq!(config.rules[1].child_counts[0].max, Some(0));
+    }
 }
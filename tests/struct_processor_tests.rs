@@ -1,7 +1,9 @@
 use std::fs;
 use tempfile::TempDir;
 
-use xml_structer::struct_processor::{parse_xml_structure, process_xml_files};
+use xml_structer::struct_processor::{
+    load_result, parse_xml_structure, process_xml_files, write_result_to_file,
+};
 
 #[test]
 fn test_parse_simple_book() {
@@ -184,3 +186,21 @@ fn test_empty_element() {
     assert_eq!(structure.children[0].name, "empty");
     assert!(structure.children[0].children.is_empty());
 }
+
+#[test]
+fn test_result_roundtrip() {
+    let temp_dir = TempDir::new().unwrap();
+    let temp_path = temp_dir.path();
+
+    let file1 = temp_path.join("doc1.xml");
+    fs::write(&file1, r#"<book><title>Test</title></book>"#).unwrap();
+
+    let result = process_xml_files(vec![file1.to_string_lossy().to_string()], None).unwrap();
+    let output = temp_path.join("result.json");
+    write_result_to_file(&result, &output, false).unwrap();
+
+    let loaded = load_result(&output).unwrap();
+    assert_eq!(loaded.total_files, 1);
+    assert_eq!(loaded.groups[0].skeleton, result.groups[0].skeleton);
+    assert!(loaded.groups[0].example_structure().is_some());
+}